@@ -1,6 +1,11 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+mod profile;
+mod settings;
+pub use profile::{ProfileVoice, VoiceProfile};
+pub use settings::{SettingChange, Settings, DEFAULT_PREVIEW_TEXT, DEFAULT_SYNC_POLL_INTERVAL};
+
 // ===== 既存型（各crateから抽出） =====
 
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
@@ -64,6 +69,10 @@ fn default_one() -> f32 {
   1.0
 }
 
+fn default_true() -> bool {
+  true
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VoiceQuality {
   #[serde(default = "default_one")]
@@ -123,12 +132,101 @@ pub struct Style {
   pub style_id: Option<i32>,
 }
 
+/// 選択肢（`\__q`, `\q`）やアンカー（`\_a`）の読み上げ方
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChoiceReading {
+  /// 選択肢・アンカーの文字列も読み上げる
+  #[default]
+  All,
+  /// 選択肢・アンカーの文字列を読み上げない
+  SkipChoices,
+  /// 選択肢・アンカーを含む行を読み上げず、地の文だけを読み上げる
+  ProseOnly,
+}
+
+impl ChoiceReading {
+  pub fn name(&self) -> &'static str {
+    match self {
+      ChoiceReading::All => "すべて読む",
+      ChoiceReading::SkipChoices => "選択肢を読まない",
+      ChoiceReading::ProseOnly => "地の文のみ",
+    }
+  }
+
+  /// メニューで切り替える際の次の設定
+  pub fn next(&self) -> Self {
+    match self {
+      ChoiceReading::All => ChoiceReading::SkipChoices,
+      ChoiceReading::SkipChoices => ChoiceReading::ProseOnly,
+      ChoiceReading::ProseOnly => ChoiceReading::All,
+    }
+  }
+}
+
+/// 録音時に書き出す字幕の形式
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubtitleFormat {
+  #[default]
+  Off,
+  Srt,
+  WebVtt,
+  /// SRT と WebVTT の両方
+  Both,
+}
+
+impl SubtitleFormat {
+  pub fn name(&self) -> &'static str {
+    match self {
+      SubtitleFormat::Off => "書き出さない",
+      SubtitleFormat::Srt => "SRT",
+      SubtitleFormat::WebVtt => "WebVTT",
+      SubtitleFormat::Both => "SRTとWebVTT",
+    }
+  }
+
+  /// メニューで切り替える際の次の設定
+  pub fn next(&self) -> Self {
+    match self {
+      SubtitleFormat::Off => SubtitleFormat::Srt,
+      SubtitleFormat::Srt => SubtitleFormat::WebVtt,
+      SubtitleFormat::WebVtt => SubtitleFormat::Both,
+      SubtitleFormat::Both => SubtitleFormat::Off,
+    }
+  }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GhostVoiceInfo {
   pub devide_by_lines: bool,
   #[serde(default)]
   pub sync_speech_to_balloon: bool,
   pub voices: Vec<Option<CharacterVoice>>,
+  /// ゴースト別の読み上げルール（グローバルのルールより優先）
+  #[serde(default)]
+  pub speak_rules: Vec<SpeakRule>,
+  #[serde(default)]
+  pub choice_reading: ChoiceReading,
+  /// 数値・日付・単位などを読み上げ向けに正規化するか
+  #[serde(default = "default_true")]
+  pub normalize_text: bool,
+  /// 英単語をカタカナ読みに変換するか
+  #[serde(default)]
+  pub english_to_katakana: bool,
+  /// 読み上げた音声をファイルに書き出すか
+  #[serde(default)]
+  pub record: bool,
+  /// descript.txt のキャラクター名（スコープ順）。録音のファイル名などに使う
+  #[serde(default)]
+  pub character_names: Vec<String>,
+  /// 予備の声質（スコープ順）。割り当てた声質のエンジンが起動していないとき、順に試す
+  #[serde(default)]
+  pub fallback_voices: Vec<Vec<CharacterVoice>>,
+  /// 声質の設定の出どころ（スコープ順）。足りない分は User とみなす
+  #[serde(default)]
+  pub voice_sources: Vec<VoiceSource>,
+  /// シェル別の声質（シェル名 → スコープ順）。None のキャラクターはゴースト共通の声質を使う
+  #[serde(default)]
+  pub shell_voices: HashMap<String, Vec<Option<CharacterVoice>>>,
 }
 
 impl Default for GhostVoiceInfo {
@@ -139,6 +237,15 @@ impl Default for GhostVoiceInfo {
       devide_by_lines: false,
       sync_speech_to_balloon: false,
       voices: v,
+      speak_rules: Vec::new(),
+      choice_reading: ChoiceReading::default(),
+      normalize_text: true,
+      english_to_katakana: false,
+      record: false,
+      character_names: Vec::new(),
+      fallback_voices: Vec::new(),
+      voice_sources: Vec::new(),
+      shell_voices: HashMap::new(),
     }
   }
 }
@@ -151,17 +258,328 @@ impl GhostVoiceInfo {
       devide_by_lines: false,
       sync_speech_to_balloon: false,
       voices: v,
+      speak_rules: Vec::new(),
+      choice_reading: ChoiceReading::default(),
+      normalize_text: true,
+      english_to_katakana: false,
+      record: false,
+      character_names: Vec::new(),
+      fallback_voices: Vec::new(),
+      voice_sources: Vec::new(),
+      shell_voices: HashMap::new(),
+    }
+  }
+}
+
+impl GhostVoiceInfo {
+  /// シェルを考慮した各キャラクターの声質。シェル別の声質が無いキャラクターはゴースト共通の声質
+  pub fn voices_for_shell(&self, shell: Option<&str>) -> Vec<Option<CharacterVoice>> {
+    let profile = shell.and_then(|s| self.shell_voices.get(s));
+    self
+      .voices
+      .iter()
+      .enumerate()
+      .map(|(i, voice)| {
+        profile
+          .and_then(|p| p.get(i).cloned().flatten())
+          .or_else(|| voice.clone())
+      })
+      .collect()
+  }
+
+  /// メニューで編集する声質の一覧。シェル別の声質があればそれを、無ければゴースト共通の声質を返す
+  pub fn editable_voices(&mut self, shell: Option<&str>) -> &mut Vec<Option<CharacterVoice>> {
+    let len = self.voices.len();
+    match shell.filter(|s| self.shell_voices.contains_key(*s)) {
+      Some(s) => {
+        let profile = self.shell_voices.entry(s.to_string()).or_default();
+        if profile.len() < len {
+          profile.resize(len, None);
+        }
+        profile
+      }
+      None => &mut self.voices,
+    }
+  }
+
+  pub fn has_shell_profile(&self, shell: Option<&str>) -> bool {
+    shell.is_some_and(|s| self.shell_voices.contains_key(s))
+  }
+
+  pub fn voice_source(&self, scope: usize) -> VoiceSource {
+    self.voice_sources.get(scope).copied().unwrap_or_default()
+  }
+
+  pub fn set_voice_source(&mut self, scope: usize, source: VoiceSource) {
+    if self.voice_sources.len() <= scope {
+      self.voice_sources.resize(scope + 1, VoiceSource::User);
+    }
+    self.voice_sources[scope] = source;
+  }
+
+  /// 声質が未設定のキャラクターに、ゴーストが提案する声質を割り当てる。割り当てたら true
+  pub fn apply_suggested_voices(&mut self, descript: &HashMap<String, String>) -> bool {
+    let mut applied = false;
+    for scope in 0..self.voices.len() {
+      if self.voices[scope].is_some() {
+        continue;
+      }
+      if let Some(voice) = suggested_voice(descript, scope) {
+        self.voices[scope] = Some(voice);
+        self.set_voice_source(scope, VoiceSource::Ghost);
+        applied = true;
+      }
+    }
+    applied
+  }
+}
+
+// ===== ゴーストが提案する声質 =====
+
+/// 声質の設定の出どころ
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VoiceSource {
+  /// ユーザーがメニューで設定した（または未設定）
+  #[default]
+  User,
+  /// ゴーストの descript.txt / ghostspeaker.txt の提案
+  Ghost,
+}
+
+/// descript.txt でのキャラクターのキーの接頭辞
+fn character_key(scope: usize) -> String {
+  match scope {
+    0 => "sakura".to_string(),
+    1 => "kero".to_string(),
+    n => format!("char{}", n),
+  }
+}
+
+/// ゴーストが提案する声質を読む。
+/// `sakura.ghostspeaker.voice,VOICEVOX:<speaker_uuid>:<style_id>` の形で声質を、
+/// `sakura.ghostspeaker.speed`・`.pitch`・`.intonation` で音声調整を指定できる
+pub fn suggested_voice(descript: &HashMap<String, String>, scope: usize) -> Option<CharacterVoice> {
+  let prefix = format!("{}.ghostspeaker.", character_key(scope));
+  let spec = descript.get(&format!("{}voice", prefix))?;
+  let mut parts = spec.trim().splitn(3, ':');
+  let engine_name = parts.next()?.trim();
+  let engine = ENGINE_LIST
+    .iter()
+    .find(|e| e.name().eq_ignore_ascii_case(engine_name))?;
+  let speaker_uuid = parts.next()?.trim();
+  let style_id = parts.next()?.trim().parse::<i32>().ok()?;
+  if speaker_uuid.is_empty() {
+    return None;
+  }
+
+  let hint = |key: &str| {
+    descript
+      .get(&format!("{}{}", prefix, key))
+      .and_then(|v| v.trim().parse::<f32>().ok())
+  };
+  let mut voice_quality = VoiceQuality::default();
+  // メニューで調整できる範囲に収める
+  if let Some(v) = hint("speed") {
+    voice_quality.speed_scale = v.clamp(0.50, 2.00);
+  }
+  if let Some(v) = hint("pitch") {
+    voice_quality.pitch_scale = v.clamp(-0.15, 0.15);
+  }
+  if let Some(v) = hint("intonation") {
+    voice_quality.intonation_scale = v.clamp(0.00, 2.00);
+  }
+
+  Some(CharacterVoice {
+    port: engine.port(),
+    speaker_uuid: speaker_uuid.to_string(),
+    style_id,
+    voice_quality,
+  })
+}
+
+// ===== 読み上げルール =====
+
+/// イベントごとの読み上げ方法
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpeakAction {
+  /// 通常通り読み上げる（同期モードはゴーストの設定に従う）
+  Speak,
+  /// 読み上げない
+  Mute,
+  /// 常に非同期で読み上げる
+  AsyncOnly,
+  /// ゴーストの設定にかかわらず同期モードで読み上げる
+  Sync,
+}
+
+impl SpeakAction {
+  pub fn name(&self) -> &'static str {
+    match self {
+      SpeakAction::Speak => "読み上げ",
+      SpeakAction::Mute => "読み上げない",
+      SpeakAction::AsyncOnly => "非同期のみ",
+      SpeakAction::Sync => "同期",
+    }
+  }
+
+  /// メニューで切り替える際の次のアクション
+  pub fn next(&self) -> Self {
+    match self {
+      SpeakAction::Speak => SpeakAction::Mute,
+      SpeakAction::Mute => SpeakAction::AsyncOnly,
+      SpeakAction::AsyncOnly => SpeakAction::Sync,
+      SpeakAction::Sync => SpeakAction::Speak,
+    }
+  }
+}
+
+/// イベントIDのパターンと読み上げ方法の組。パターンには `*` と `?` のワイルドカードが使える
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeakRule {
+  pub event: String,
+  pub action: SpeakAction,
+}
+
+impl SpeakRule {
+  pub fn matches(&self, event_id: &str) -> bool {
+    wildcard_match(&self.event, event_id)
+  }
+}
+
+/// ルールを先頭から評価し、最初にマッチしたルールのアクションを返す
+pub fn find_speak_action(rules: &[SpeakRule], event_id: &str) -> Option<SpeakAction> {
+  rules.iter().find(|r| r.matches(event_id)).map(|r| r.action)
+}
+
+/// `*`（任意の文字列）と `?`（任意の1文字）によるワイルドカードマッチ
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+  let p: Vec<char> = pattern.chars().collect();
+  let t: Vec<char> = text.chars().collect();
+  let (mut pi, mut ti) = (0, 0);
+  // 直前の `*` の位置と、その `*` にマッチさせ始めた text の位置
+  let mut star: Option<(usize, usize)> = None;
+  while ti < t.len() {
+    if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+      pi += 1;
+      ti += 1;
+    } else if pi < p.len() && p[pi] == '*' {
+      star = Some((pi, ti));
+      pi += 1;
+    } else if let Some((sp, st)) = star {
+      // `*` にマッチさせる文字を1つ増やしてやり直す
+      pi = sp + 1;
+      ti = st + 1;
+      star = Some((sp, st + 1));
+    } else {
+      return false;
+    }
+  }
+  while pi < p.len() && p[pi] == '*' {
+    pi += 1;
+  }
+  pi == p.len()
+}
+
+// ===== 読み上げ前のフィルタ =====
+
+/// 絵文字の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EmojiFilter {
+  /// そのまま残す
+  Keep,
+  /// 除去する
+  #[default]
+  Drop,
+  /// 日本語の名前に置き換える
+  Name,
+}
+
+/// 読み上げに向かない文字列の除去・置換の設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextFilterSettings {
+  /// URLを短い読みに置き換える
+  #[serde(default = "default_true")]
+  pub url: bool,
+  /// ファイルパスを短い読みに置き換える
+  #[serde(default = "default_true")]
+  pub path: bool,
+  #[serde(default)]
+  pub emoji: EmojiFilter,
+  /// 顔文字を除去する
+  #[serde(default = "default_true")]
+  pub kaomoji: bool,
+}
+
+impl Default for TextFilterSettings {
+  fn default() -> Self {
+    Self {
+      url: true,
+      path: true,
+      emoji: EmojiFilter::default(),
+      kaomoji: true,
     }
   }
 }
 
+// ===== 読み替え辞書 =====
+
+/// 読み上げ時の読み替え。英単語のカタカナ変換でも優先して参照される
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubstitutionRule {
+  /// 読み替え前の文字列
+  pub from: String,
+  /// 読み替え後の文字列
+  pub to: String,
+}
+
 // ===== IPC メッセージ型 =====
 
+/// DLLとワーカー間の通信プロトコルのバージョン。互換性のない変更をしたら上げる
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// このビルドが対応している機能。Init でDLLとワーカーが互いに通知する
+pub const FEATURES: &[&str] = &[
+  "sync_duration",
+  "speak_rules",
+  "choice_reading",
+  "normalize_text",
+  "english_reading",
+  "substitution_rules",
+  "text_filter",
+  "audio_output",
+  "record",
+  "subtitles",
+  "speech_history",
+  "voice_preview",
+  "voice_fallback",
+  "shell_voices",
+  "settings_store",
+];
+
+pub fn features() -> Vec<String> {
+  FEATURES.iter().map(|f| f.to_string()).collect()
+}
+
+/// 相手が対応していない、こちらの機能の一覧を返す
+pub fn missing_features(theirs: &[String]) -> Vec<String> {
+  FEATURES
+    .iter()
+    .filter(|f| !theirs.iter().any(|t| t == *f))
+    .map(|f| f.to_string())
+    .collect()
+}
+
+// 未知のバリアントを Unknown として受け取れるよう、タグを "type" フィールドに持たせる
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum Command {
   Init {
     dll_dir: String,
-    config: WorkerConfig,
+    config: Box<WorkerConfig>,
+    #[serde(default)]
+    protocol_version: u32,
+    #[serde(default)]
+    features: Vec<String>,
   },
   Shutdown,
   SpeakAsync {
@@ -174,48 +592,82 @@ pub enum Command {
   },
   SyncPoll,
   SyncCancel,
-  PopDialog,
   GetEngineStatus,
-  UpdateVolume {
-    volume: f32,
+  /// 保存している設定を返す
+  GetSettings,
+  /// 設定の一部を変更して保存する。変更後の設定を返し、SettingsChanged でも通知する
+  PatchSettings {
+    changes: Vec<SettingChange>,
   },
-  UpdateGhostVoices {
+  /// ゴーストの現在のシェル。シェル別の声質を選ぶのに使う
+  UpdateShell {
     ghost_name: String,
-    info: GhostVoiceInfo,
+    shell_name: String,
   },
-  UpdateInitialVoice {
-    voice: CharacterVoice,
+  BootEngine {
+    engine: Engine,
   },
-  UpdateSpeakByPunctuation {
-    enabled: bool,
+  /// ゴーストの読み上げ履歴を新しい順に返す
+  GetSpeechHistory {
+    ghost_name: String,
   },
-  UpdateEngineAutoStart {
-    engine: Engine,
-    auto_start: bool,
+  /// 履歴のトークをキャッシュした音声で再生し直す。id が None なら直前のトーク
+  ReplaySpeech {
+    ghost_name: String,
+    id: Option<u64>,
   },
-  BootEngine {
-    engine: Engine,
+  /// 試聴: 割り当てを変えずに、指定した声質で text を読み上げる
+  PreviewVoice {
+    voice: CharacterVoice,
+    text: String,
   },
   ForceStopPlayback,
   GracefulShutdown,
+  /// 新しいバージョンで追加された、このビルドが知らないコマンド
+  #[serde(other)]
+  Unknown,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 合成した音声の出力先
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AudioOutput {
+  /// サウンドデバイスで再生する
+  #[default]
+  Device,
+  /// 再生せず、再生時間だけ待つ（音声デバイスの無い環境向け）
+  Null,
+  /// 再生する代わりに、1回の再生ごとに WAV ファイルとしてディレクトリへ書き出す。
+  /// 相対パスは dll_dir からの相対パスとして扱う
+  File { dir: String },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct WorkerConfig {
-  pub volume: f32,
-  pub speak_by_punctuation: bool,
-  pub ghosts_voices: HashMap<String, GhostVoiceInfo>,
-  pub initial_voice: CharacterVoice,
-  pub engine_auto_start: HashMap<Engine, bool>,
-  pub engine_path: HashMap<Engine, String>,
+  #[serde(default)]
+  pub audio_output: AudioOutput,
+  /// ゴーストごとの現在のシェル名
+  #[serde(default)]
+  pub current_shells: HashMap<String, String>,
+  /// 設定を dll_dir の vars.yaml から読み込み、変更を保存するか。
+  /// false なら既定の設定で始め、何も保存しない（コマンドラインからの動作確認用）
+  #[serde(default)]
+  pub persist_settings: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum Response {
   Ok,
+  /// Init への応答。ワーカーのプロトコルバージョンと対応機能を返す
+  Initialized {
+    protocol_version: u32,
+    features: Vec<String>,
+  },
   Error {
     message: String,
   },
+  /// first_segment が None で has_more が true のときは、最初のセグメントから SyncPoll で受け取る
   SyncStarted {
     first_segment: Option<SegmentInfo>,
     has_more: bool,
@@ -223,15 +675,54 @@ pub enum Response {
   SyncStatus {
     state: SyncState,
   },
-  Dialog {
-    message: Option<String>,
-  },
   EngineStatus {
     speakers_info: HashMap<Engine, Vec<SpeakerInfo>>,
     connection_status: HashMap<Engine, bool>,
-    engine_paths: HashMap<Engine, String>,
-    engine_auto_start: HashMap<Engine, bool>,
   },
+  /// GetSettings・PatchSettings への応答
+  Settings {
+    settings: Box<Settings>,
+  },
+  SpeechHistory {
+    entries: Vec<SpeechHistoryEntry>,
+  },
+  /// 新しいバージョンで追加された、このビルドが知らない応答
+  #[serde(other)]
+  Unknown,
+}
+
+/// DLL → ワーカーのメッセージ。応答は同じ id を付けて返される
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+  pub id: u64,
+  pub command: Command,
+}
+
+/// ワーカー → DLL のメッセージ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum WorkerMessage {
+  /// id が一致する Request への応答
+  Reply { id: u64, response: Response },
+  /// ワーカーから自発的に送られる通知
+  Event { event: WorkerEvent },
+  /// 終了処理に入ったため、これ以降はメッセージを送らない
+  Closed,
+  #[serde(other)]
+  Unknown,
+}
+
+/// ワーカーから自発的に送られる通知
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WorkerEvent {
+  /// ユーザーに表示するメッセージ（エンジンの接続・切断など）
+  Dialog { message: String },
+  /// 設定が変わった（DLL からの変更、vars.yaml の編集、エンジンの検出など）
+  SettingsChanged { settings: Box<Settings> },
+  /// 新しいバージョンで追加された、このビルドが知らない通知
+  #[serde(other)]
+  Unknown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -240,11 +731,17 @@ pub struct SegmentInfo {
   pub raw_text: String,
   pub scope: usize,
   pub is_ellipsis: bool,
+  /// このセグメントの音声の再生時間（ミリ秒）。音声がない場合は0
+  #[serde(default)]
+  pub duration_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SyncState {
-  Playing,
+  /// 再生中。remaining_ms は再生終了までの見込み時間（ミリ秒）
+  Playing {
+    remaining_ms: u64,
+  },
   Ready {
     segment: SegmentInfo,
     has_more: bool,
@@ -252,3 +749,249 @@ pub enum SyncState {
   Waiting,
   Complete,
 }
+
+/// 読み上げ履歴の1トーク。音声そのものはワーカーが持っている
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeechHistoryEntry {
+  pub id: u64,
+  /// キャラクターごとの台詞
+  pub lines: Vec<SpeechHistoryLine>,
+  /// 音声がキャッシュされていて再生し直せるか（棒読みちゃんのみのトークは false）
+  pub replayable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeechHistoryLine {
+  pub scope: usize,
+  pub text: String,
+  pub voice: CharacterVoice,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn unknown_command_is_tolerated() {
+    let cmd: Command = serde_json::from_str(r#"{"type":"SomeFutureCommand","x":1}"#).unwrap();
+    assert!(matches!(cmd, Command::Unknown));
+    let resp: Response = serde_json::from_str(r#"{"type":"SomeFutureResponse"}"#).unwrap();
+    assert!(matches!(resp, Response::Unknown));
+  }
+
+  #[test]
+  fn init_without_version_defaults_to_zero() {
+    let json = serde_json::json!({
+      "type": "Init",
+      "dll_dir": "dir",
+      "config": {
+        "audio_output": { "type": "Null" },
+        "current_shells": { "Ghost": "child" },
+        "persist_settings": true,
+      },
+    });
+    let cmd: Command = serde_json::from_value(json).unwrap();
+    match cmd {
+      Command::Init {
+        protocol_version,
+        features,
+        config,
+        ..
+      } => {
+        assert_eq!(protocol_version, 0);
+        assert!(features.is_empty());
+        assert_eq!(config.audio_output, AudioOutput::Null);
+        assert_eq!(config.current_shells["Ghost"], "child");
+        assert!(config.persist_settings);
+      }
+      other => panic!("unexpected command: {:?}", other),
+    }
+  }
+
+  /// 旧バージョン(v2.0.2)のワーカーが最初の行を読むときの形式。Request で包まない Command
+  #[allow(dead_code)]
+  #[derive(Deserialize)]
+  enum LegacyCommand {
+    Init {
+      dll_dir: String,
+      config: serde_json::Value,
+    },
+    Shutdown,
+  }
+
+  #[test]
+  fn legacy_worker_cannot_parse_init_request() {
+    let req = Request {
+      id: 1,
+      command: Command::Init {
+        dll_dir: "dir".to_string(),
+        config: Box::default(),
+        protocol_version: PROTOCOL_VERSION,
+        features: features(),
+      },
+    };
+    let json = serde_json::to_string(&req).unwrap();
+    // 旧ワーカーは読めずに何も返さず終了するので、DLL はバージョン不一致として扱う
+    assert!(serde_json::from_str::<LegacyCommand>(&json).is_err());
+    let legacy = r#"{"Init":{"dll_dir":"dir","config":{}}}"#;
+    assert!(serde_json::from_str::<LegacyCommand>(legacy).is_ok());
+  }
+
+  #[test]
+  fn engine_status_round_trip() {
+    let mut connection_status = HashMap::new();
+    connection_status.insert(Engine::VoiceVox, true);
+    let resp = Response::EngineStatus {
+      speakers_info: HashMap::new(),
+      connection_status,
+    };
+    let json = serde_json::to_string(&resp).unwrap();
+    match serde_json::from_str(&json).unwrap() {
+      Response::EngineStatus {
+        connection_status, ..
+      } => assert_eq!(connection_status.get(&Engine::VoiceVox), Some(&true)),
+      other => panic!("unexpected response: {:?}", other),
+    }
+  }
+
+  #[test]
+  fn worker_messages_round_trip() {
+    let req = Request {
+      id: 7,
+      command: Command::SyncPoll,
+    };
+    let json = serde_json::to_string(&req).unwrap();
+    let req: Request = serde_json::from_str(&json).unwrap();
+    assert_eq!(req.id, 7);
+    assert!(matches!(req.command, Command::SyncPoll));
+
+    let msg: WorkerMessage =
+      serde_json::from_str(r#"{"kind":"Reply","id":7,"response":{"type":"Ok"}}"#).unwrap();
+    assert!(matches!(
+      msg,
+      WorkerMessage::Reply {
+        id: 7,
+        response: Response::Ok
+      }
+    ));
+    let msg: WorkerMessage =
+      serde_json::from_str(r#"{"kind":"Event","event":{"type":"SomeFutureEvent"}}"#).unwrap();
+    assert!(matches!(
+      msg,
+      WorkerMessage::Event {
+        event: WorkerEvent::Unknown
+      }
+    ));
+  }
+
+  #[test]
+  fn suggested_voices_fill_unset_characters() {
+    let descript: HashMap<String, String> = [
+      ("sakura.ghostspeaker.voice", "voicevox:uuid-a:3"),
+      ("sakura.ghostspeaker.speed", "1.2"),
+      ("sakura.ghostspeaker.pitch", "0.5"),
+      ("kero.ghostspeaker.voice", "COEIROINKv2:uuid-b:0"),
+      ("char2.ghostspeaker.voice", "UNKNOWN:uuid-c:0"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+
+    let voice = suggested_voice(&descript, 0).unwrap();
+    assert_eq!(voice.port, Engine::VoiceVox.port());
+    assert_eq!((voice.speaker_uuid.as_str(), voice.style_id), ("uuid-a", 3));
+    assert_eq!(voice.voice_quality.speed_scale, 1.2);
+    assert_eq!(voice.voice_quality.pitch_scale, 0.15);
+    assert!(suggested_voice(&descript, 2).is_none());
+
+    let mut info = GhostVoiceInfo::new(3);
+    info.voices[1] = Some(CharacterVoice::no_voice());
+    assert!(info.apply_suggested_voices(&descript));
+    assert_eq!(info.voice_source(0), VoiceSource::Ghost);
+    // ユーザーが設定したキャラクターはそのまま
+    assert_eq!(info.voices[1].as_ref().unwrap().speaker_uuid, NO_VOICE_UUID);
+    assert_eq!(info.voice_source(1), VoiceSource::User);
+    assert!(info.voices[2].is_none());
+    assert!(!info.apply_suggested_voices(&descript));
+  }
+
+  #[test]
+  fn shell_profile_falls_back_to_ghost_voices() {
+    let voice = |uuid: &str| CharacterVoice {
+      speaker_uuid: uuid.to_string(),
+      ..CharacterVoice::no_voice()
+    };
+    let mut info = GhostVoiceInfo::new(2);
+    info.voices = vec![Some(voice("a")), Some(voice("b"))];
+    assert!(!info.has_shell_profile(Some("child")));
+    // シェル別の声質が無ければゴースト共通の声質を編集する
+    info.editable_voices(Some("child"))[0] = Some(voice("c"));
+    assert_eq!(info.voices[0].as_ref().unwrap().speaker_uuid, "c");
+
+    info.shell_voices.insert("child".to_string(), vec![None]);
+    info.editable_voices(Some("child"))[1] = Some(voice("d"));
+    let uuids = |voices: Vec<Option<CharacterVoice>>| {
+      voices
+        .into_iter()
+        .map(|v| v.unwrap().speaker_uuid)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(uuids(info.voices_for_shell(Some("child"))), ["c", "d"]);
+    assert_eq!(uuids(info.voices_for_shell(Some("other"))), ["c", "b"]);
+    assert_eq!(uuids(info.voices_for_shell(None)), ["c", "b"]);
+  }
+
+  #[test]
+  fn missing_features_lists_unsupported() {
+    let theirs = vec!["sync_duration".to_string()];
+    let missing = missing_features(&theirs);
+    assert!(!missing.contains(&"sync_duration".to_string()));
+    assert!(missing.contains(&"speak_rules".to_string()));
+    assert!(missing_features(&features()).is_empty());
+  }
+
+  #[test]
+  fn wildcard_exact() {
+    assert!(wildcard_match("OnBoot", "OnBoot"));
+    assert!(!wildcard_match("OnBoot", "OnBoot2"));
+    assert!(!wildcard_match("OnBoot", "OnBo"));
+  }
+
+  #[test]
+  fn wildcard_star() {
+    assert!(wildcard_match("OnMouse*", "OnMouseDoubleClick"));
+    assert!(wildcard_match("*Change", "OnSecondChange"));
+    assert!(wildcard_match("On*Click", "OnMouseDoubleClick"));
+    assert!(wildcard_match("*", ""));
+    assert!(!wildcard_match("OnMouse*", "OnBoot"));
+  }
+
+  #[test]
+  fn wildcard_question() {
+    assert!(wildcard_match("OnBoo?", "OnBoot"));
+    assert!(!wildcard_match("OnBoo?", "OnBoo"));
+  }
+
+  #[test]
+  fn find_speak_action_first_match_wins() {
+    let rules = vec![
+      SpeakRule {
+        event: "OnMouseDoubleClick".to_string(),
+        action: SpeakAction::Mute,
+      },
+      SpeakRule {
+        event: "OnMouse*".to_string(),
+        action: SpeakAction::AsyncOnly,
+      },
+    ];
+    assert_eq!(
+      find_speak_action(&rules, "OnMouseDoubleClick"),
+      Some(SpeakAction::Mute)
+    );
+    assert_eq!(
+      find_speak_action(&rules, "OnMouseClick"),
+      Some(SpeakAction::AsyncOnly)
+    );
+    assert_eq!(find_speak_action(&rules, "OnBoot"), None);
+  }
+}
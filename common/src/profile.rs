@@ -0,0 +1,424 @@
+//! 共有用の声質の設定（プロファイル）。
+//! 話者はエンジン名・UUID・名前をまとめて持ち、読み込むときは UUID → 名前の順に探し直す
+use crate::{
+  CharacterVoice, ChoiceReading, Engine, GhostVoiceInfo, SpeakRule, SpeakerInfo, Style,
+  VoiceQuality, VoiceSource, ENGINE_LIST, NO_VOICE_UUID,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const NO_VOICE_NAME: &str = "無し";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileVoice {
+  pub engine: String,
+  #[serde(default)]
+  pub speaker_uuid: Option<String>,
+  #[serde(default)]
+  pub speaker_name: Option<String>,
+  #[serde(default)]
+  pub style_id: Option<i32>,
+  #[serde(default)]
+  pub style_name: Option<String>,
+  #[serde(default)]
+  pub voice_quality: VoiceQuality,
+}
+
+/// 1つのゴーストの声質の設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceProfile {
+  pub ghost_name: String,
+  /// 参考用のキャラクター名（スコープ順）
+  #[serde(default)]
+  pub character_names: Vec<String>,
+  pub voices: Vec<Option<ProfileVoice>>,
+  #[serde(default)]
+  pub fallback_voices: Vec<Vec<ProfileVoice>>,
+  #[serde(default)]
+  pub shell_voices: HashMap<String, Vec<Option<ProfileVoice>>>,
+  #[serde(default)]
+  pub devide_by_lines: bool,
+  #[serde(default)]
+  pub sync_speech_to_balloon: bool,
+  #[serde(default)]
+  pub speak_rules: Vec<SpeakRule>,
+  #[serde(default)]
+  pub choice_reading: ChoiceReading,
+  #[serde(default = "crate::default_true")]
+  pub normalize_text: bool,
+  #[serde(default)]
+  pub english_to_katakana: bool,
+}
+
+impl VoiceProfile {
+  /// ゴーストの設定を書き出す。話者名・スタイル名は起動中のエンジンから引く
+  pub fn export(
+    ghost_name: &str,
+    info: &GhostVoiceInfo,
+    speakers_info: &HashMap<Engine, Vec<SpeakerInfo>>,
+  ) -> Self {
+    let export = |voice: &CharacterVoice| export_voice(voice, speakers_info);
+    VoiceProfile {
+      ghost_name: ghost_name.to_string(),
+      character_names: info.character_names.clone(),
+      voices: info.voices.iter().map(|v| v.as_ref().map(export)).collect(),
+      fallback_voices: info
+        .fallback_voices
+        .iter()
+        .map(|voices| voices.iter().map(export).collect())
+        .collect(),
+      shell_voices: info
+        .shell_voices
+        .iter()
+        .map(|(shell, voices)| {
+          (
+            shell.clone(),
+            voices.iter().map(|v| v.as_ref().map(export)).collect(),
+          )
+        })
+        .collect(),
+      devide_by_lines: info.devide_by_lines,
+      sync_speech_to_balloon: info.sync_speech_to_balloon,
+      speak_rules: info.speak_rules.clone(),
+      choice_reading: info.choice_reading,
+      normalize_text: info.normalize_text,
+      english_to_katakana: info.english_to_katakana,
+    }
+  }
+
+  /// 設定をゴーストに読み込む。見つからなかった声質は未設定にして、その説明を返す。
+  /// エンジンが起動していない声質は確かめられないため、そのまま読み込む
+  pub fn import_into(
+    &self,
+    info: &mut GhostVoiceInfo,
+    speakers_info: &HashMap<Engine, Vec<SpeakerInfo>>,
+  ) -> Vec<String> {
+    let mut unmatched = Vec::new();
+    let mut resolve =
+      |voice: &ProfileVoice, place: String| match resolve_voice(voice, speakers_info) {
+        Some(v) => Some(v),
+        None => {
+          unmatched.push(format!("{}: {}", place, describe(voice)));
+          None
+        }
+      };
+
+    let mut voices: Vec<Option<CharacterVoice>> = self
+      .voices
+      .iter()
+      .enumerate()
+      .map(|(i, v)| v.as_ref().and_then(|v| resolve(v, self.character_label(i))))
+      .collect();
+    // キャラクターの数はゴーストに合わせる（多い分は残す）
+    if voices.len() < info.voices.len() {
+      voices.resize(info.voices.len(), None);
+    }
+    let fallback_voices = self
+      .fallback_voices
+      .iter()
+      .enumerate()
+      .map(|(i, vs)| {
+        vs.iter()
+          .filter_map(|v| resolve(v, format!("{}の予備", self.character_label(i))))
+          .collect()
+      })
+      .collect();
+    let shell_voices = self
+      .shell_voices
+      .iter()
+      .map(|(shell, vs)| {
+        let voices = vs
+          .iter()
+          .enumerate()
+          .map(|(i, v)| {
+            v.as_ref()
+              .and_then(|v| resolve(v, format!("{}({})", self.character_label(i), shell)))
+          })
+          .collect();
+        (shell.clone(), voices)
+      })
+      .collect();
+
+    info.voices = voices;
+    info.fallback_voices = fallback_voices;
+    info.shell_voices = shell_voices;
+    info.voice_sources = vec![VoiceSource::User; info.voices.len()];
+    info.devide_by_lines = self.devide_by_lines;
+    info.sync_speech_to_balloon = self.sync_speech_to_balloon;
+    info.speak_rules = self.speak_rules.clone();
+    info.choice_reading = self.choice_reading;
+    info.normalize_text = self.normalize_text;
+    info.english_to_katakana = self.english_to_katakana;
+    unmatched
+  }
+
+  fn character_label(&self, scope: usize) -> String {
+    match self.character_names.get(scope) {
+      Some(name) if !name.is_empty() => name.clone(),
+      _ => format!("char{}", scope),
+    }
+  }
+}
+
+fn export_voice(
+  voice: &CharacterVoice,
+  speakers_info: &HashMap<Engine, Vec<SpeakerInfo>>,
+) -> ProfileVoice {
+  if voice.speaker_uuid == NO_VOICE_UUID {
+    return ProfileVoice {
+      engine: String::new(),
+      speaker_uuid: Some(NO_VOICE_UUID.to_string()),
+      speaker_name: Some(NO_VOICE_NAME.to_string()),
+      style_id: None,
+      style_name: None,
+      voice_quality: VoiceQuality::default(),
+    };
+  }
+  let engine = crate::engine_from_port(voice.port);
+  let speaker = engine
+    .and_then(|e| speakers_info.get(&e))
+    .and_then(|speakers| {
+      speakers
+        .iter()
+        .find(|s| s.speaker_uuid == voice.speaker_uuid)
+    });
+  let style_name = speaker
+    .and_then(|s| {
+      s.styles
+        .iter()
+        .find(|st| st.style_id == Some(voice.style_id))
+    })
+    .and_then(|st| st.style_name.clone());
+  ProfileVoice {
+    engine: engine.map(|e| e.name().to_string()).unwrap_or_default(),
+    speaker_uuid: Some(voice.speaker_uuid.clone()),
+    speaker_name: speaker.map(|s| s.speaker_name.clone()),
+    style_id: Some(voice.style_id),
+    style_name,
+    voice_quality: voice.voice_quality.clone(),
+  }
+}
+
+/// 書き出した声質を、今の環境の声質に探し直す
+fn resolve_voice(
+  voice: &ProfileVoice,
+  speakers_info: &HashMap<Engine, Vec<SpeakerInfo>>,
+) -> Option<CharacterVoice> {
+  if voice.speaker_uuid.as_deref() == Some(NO_VOICE_UUID) {
+    return Some(CharacterVoice::no_voice());
+  }
+  let named_engine = ENGINE_LIST
+    .iter()
+    .find(|e| e.name().eq_ignore_ascii_case(voice.engine.trim()))
+    .copied();
+  // 書き出したエンジンを先に、見つからなければ他のエンジンも探す
+  let engines: Vec<Engine> = named_engine
+    .into_iter()
+    .chain(
+      ENGINE_LIST
+        .iter()
+        .copied()
+        .filter(|e| Some(*e) != named_engine),
+    )
+    .collect();
+  let find = |matches: &dyn Fn(&SpeakerInfo) -> bool| {
+    engines.iter().find_map(|engine| {
+      speakers_info
+        .get(engine)?
+        .iter()
+        .find(|s| matches(s))
+        .map(|s| (*engine, s))
+    })
+  };
+  let by_uuid = voice
+    .speaker_uuid
+    .as_ref()
+    .and_then(|uuid| find(&|s: &SpeakerInfo| &s.speaker_uuid == uuid));
+  let by_name = || {
+    voice
+      .speaker_name
+      .as_ref()
+      .and_then(|name| find(&|s: &SpeakerInfo| &s.speaker_name == name))
+  };
+
+  match by_uuid.or_else(by_name) {
+    Some((engine, speaker)) => {
+      let style = find_style(&speaker.styles, voice)?;
+      Some(CharacterVoice {
+        port: engine.port(),
+        speaker_uuid: speaker.speaker_uuid.clone(),
+        style_id: style.style_id?,
+        voice_quality: voice.voice_quality.clone(),
+      })
+    }
+    None => {
+      // エンジンが起動していなければ確かめられないので、書き出したとおりに読み込む
+      let engine = named_engine?;
+      if speakers_info.contains_key(&engine) {
+        return None;
+      }
+      Some(CharacterVoice {
+        port: engine.port(),
+        speaker_uuid: voice.speaker_uuid.clone()?,
+        style_id: voice.style_id?,
+        voice_quality: voice.voice_quality.clone(),
+      })
+    }
+  }
+}
+
+fn find_style<'a>(styles: &'a [Style], voice: &ProfileVoice) -> Option<&'a Style> {
+  styles
+    .iter()
+    .find(|s| voice.style_id.is_some() && s.style_id == voice.style_id)
+    .or_else(|| {
+      styles
+        .iter()
+        .find(|s| voice.style_name.is_some() && s.style_name == voice.style_name)
+    })
+}
+
+fn describe(voice: &ProfileVoice) -> String {
+  format!(
+    "{} {} - {}",
+    voice.engine,
+    voice
+      .speaker_name
+      .as_deref()
+      .or(voice.speaker_uuid.as_deref())
+      .unwrap_or("不明な話者"),
+    voice.style_name.as_deref().unwrap_or("不明なスタイル"),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn speakers_info() -> HashMap<Engine, Vec<SpeakerInfo>> {
+    let speaker = |name: &str, uuid: &str, styles: &[(&str, i32)]| SpeakerInfo {
+      speaker_name: name.to_string(),
+      speaker_uuid: uuid.to_string(),
+      styles: styles
+        .iter()
+        .map(|(n, id)| Style {
+          style_name: Some(n.to_string()),
+          style_id: Some(*id),
+        })
+        .collect(),
+    };
+    HashMap::from([
+      (
+        Engine::VoiceVox,
+        vec![speaker(
+          "四国めたん",
+          "uuid-metan",
+          &[("ノーマル", 2), ("あまあま", 0)],
+        )],
+      ),
+      (
+        Engine::AivisSpeech,
+        vec![speaker("まお", "uuid-mao", &[("ノーマル", 100)])],
+      ),
+    ])
+  }
+
+  fn voice(engine: Engine, uuid: &str, style_id: i32) -> CharacterVoice {
+    CharacterVoice {
+      port: engine.port(),
+      speaker_uuid: uuid.to_string(),
+      style_id,
+      voice_quality: VoiceQuality {
+        speed_scale: 1.2,
+        ..Default::default()
+      },
+    }
+  }
+
+  #[test]
+  fn export_and_import_round_trip() {
+    let mut info = GhostVoiceInfo::new(3);
+    info.character_names = vec!["さくら".to_string(), "うにゅう".to_string()];
+    info.voices[0] = Some(voice(Engine::VoiceVox, "uuid-metan", 0));
+    info.voices[1] = Some(CharacterVoice::no_voice());
+    info.fallback_voices = vec![vec![voice(Engine::AivisSpeech, "uuid-mao", 100)]];
+    info.devide_by_lines = true;
+
+    let profile = VoiceProfile::export("Ghost", &info, &speakers_info());
+    let v0 = profile.voices[0].as_ref().unwrap();
+    assert_eq!(v0.engine, "VOICEVOX");
+    assert_eq!(v0.speaker_name.as_deref(), Some("四国めたん"));
+    assert_eq!(v0.style_name.as_deref(), Some("あまあま"));
+
+    let json = serde_json::to_string(&profile).unwrap();
+    let profile: VoiceProfile = serde_json::from_str(&json).unwrap();
+    let mut imported = GhostVoiceInfo::new(3);
+    assert!(profile
+      .import_into(&mut imported, &speakers_info())
+      .is_empty());
+    assert_eq!(imported.voices[0].as_ref().unwrap().style_id, 0);
+    assert_eq!(
+      imported.voices[0]
+        .as_ref()
+        .unwrap()
+        .voice_quality
+        .speed_scale,
+      1.2
+    );
+    assert_eq!(
+      imported.voices[1].as_ref().unwrap().speaker_uuid,
+      NO_VOICE_UUID
+    );
+    assert!(imported.voices[2].is_none());
+    assert_eq!(imported.fallback_voices[0][0].speaker_uuid, "uuid-mao");
+    assert!(imported.devide_by_lines);
+  }
+
+  #[test]
+  fn import_resolves_by_name_and_reports_unmatched() {
+    let by_name = ProfileVoice {
+      engine: "voicevox".to_string(),
+      speaker_uuid: None,
+      speaker_name: Some("四国めたん".to_string()),
+      style_id: None,
+      style_name: Some("ノーマル".to_string()),
+      voice_quality: VoiceQuality::default(),
+    };
+    let missing = ProfileVoice {
+      speaker_uuid: Some("uuid-unknown".to_string()),
+      speaker_name: Some("だれか".to_string()),
+      ..by_name.clone()
+    };
+    // 起動していないエンジンの声質は確かめずに読み込む
+    let offline = ProfileVoice {
+      engine: "COEIROINKv2".to_string(),
+      speaker_uuid: Some("uuid-coeiroink".to_string()),
+      speaker_name: Some("つくよみちゃん".to_string()),
+      style_id: Some(1),
+      ..by_name.clone()
+    };
+    let profile = VoiceProfile {
+      ghost_name: "Ghost".to_string(),
+      character_names: vec!["さくら".to_string()],
+      voices: vec![Some(by_name), Some(missing), Some(offline)],
+      fallback_voices: Vec::new(),
+      shell_voices: HashMap::new(),
+      devide_by_lines: false,
+      sync_speech_to_balloon: false,
+      speak_rules: Vec::new(),
+      choice_reading: ChoiceReading::default(),
+      normalize_text: true,
+      english_to_katakana: false,
+    };
+    let mut info = GhostVoiceInfo::new(2);
+    let unmatched = profile.import_into(&mut info, &speakers_info());
+    assert_eq!(unmatched, ["char1: voicevox だれか - ノーマル"]);
+    let v0 = info.voices[0].as_ref().unwrap();
+    assert_eq!((v0.speaker_uuid.as_str(), v0.style_id), ("uuid-metan", 2));
+    assert!(info.voices[1].is_none());
+    assert_eq!(
+      info.voices[2].as_ref().unwrap().port,
+      Engine::CoeiroInkV2.port()
+    );
+  }
+}
@@ -0,0 +1,177 @@
+//! 保存する設定と、その一部を書き換える変更。
+//! 設定はワーカーが持って vars.yaml に保存し、DLL は変更の通知を受けて写しを表示に使う
+use crate::{
+  CharacterVoice, Engine, GhostVoiceInfo, SpeakRule, SubstitutionRule, SubtitleFormat,
+  TextFilterSettings,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+pub const DEFAULT_SYNC_POLL_INTERVAL: u64 = 200;
+pub const DEFAULT_PREVIEW_TEXT: &str = "こんにちは。この声で読み上げます。";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+  /// vars.yaml の形式のバージョン
+  pub schema_version: u32,
+  pub engine_path: HashMap<Engine, String>,
+  pub engine_auto_start: HashMap<Engine, bool>,
+  pub volume: f32,
+  pub speak_by_punctuation: bool,
+  /// 同期読み上げで再生時間が分からないときのポーリング間隔（ミリ秒）
+  pub sync_poll_interval: u64,
+  pub ghosts_voices: HashMap<String, GhostVoiceInfo>,
+  /// 全ゴースト共通の読み上げルール
+  pub speak_rules: Vec<SpeakRule>,
+  /// 読み替え辞書
+  pub substitution_rules: Vec<SubstitutionRule>,
+  /// URL・パス・絵文字・顔文字の除去設定
+  pub text_filter: TextFilterSettings,
+  /// 録音時に、セッション中の全トークをつなげたファイルも書き出すか
+  pub record_session: bool,
+  /// 録音時に書き出す字幕の形式
+  pub subtitle_format: SubtitleFormat,
+  /// 予備の声質で読み上げたときに通知するか
+  pub fallback_notice: bool,
+  /// 声質の試聴で読み上げる文章
+  pub preview_text: String,
+  /// 最近選んだ声質。新しいものが先頭
+  pub recent_voices: Vec<CharacterVoice>,
+  pub initial_voice: CharacterVoice,
+  pub last_version: Option<String>,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Self {
+      schema_version: 0,
+      engine_path: HashMap::new(),
+      engine_auto_start: HashMap::new(),
+      volume: 1.0,
+      speak_by_punctuation: true,
+      sync_poll_interval: DEFAULT_SYNC_POLL_INTERVAL,
+      ghosts_voices: HashMap::new(),
+      speak_rules: Vec::new(),
+      substitution_rules: Vec::new(),
+      text_filter: TextFilterSettings::default(),
+      record_session: false,
+      subtitle_format: SubtitleFormat::Off,
+      fallback_notice: true,
+      preview_text: DEFAULT_PREVIEW_TEXT.to_string(),
+      recent_voices: Vec::new(),
+      initial_voice: CharacterVoice::no_voice(),
+      last_version: None,
+    }
+  }
+}
+
+/// 設定の一部の変更。path の位置の値を value で置き換え、value が null ならその項目を取り除く
+/// （既定値に戻す）。例: path が ["ghosts_voices", "ゴースト名"] ならそのゴーストの声質の設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingChange {
+  pub path: Vec<String>,
+  pub value: Value,
+}
+
+impl SettingChange {
+  pub fn new<T: Serialize>(path: &[&str], value: &T) -> Result<Self, String> {
+    Ok(Self {
+      path: path.iter().map(|p| p.to_string()).collect(),
+      value: serde_json::to_value(value).map_err(|e| e.to_string())?,
+    })
+  }
+}
+
+impl Settings {
+  /// 変更を順に適用した設定を返す。不明な項目や、型の合わない値を含む変更はエラーにする
+  pub fn apply_changes(&self, changes: &[SettingChange]) -> Result<Settings, String> {
+    let mut settings = serde_json::to_value(self).map_err(|e| e.to_string())?;
+    for change in changes {
+      let path = change.path.join(".");
+      let (last, parents) = change
+        .path
+        .split_last()
+        .ok_or("変更する設定が指定されていません")?;
+      if !settings
+        .as_object()
+        .is_some_and(|s| s.contains_key(&change.path[0]))
+      {
+        return Err(format!("不明な設定です: {}", path));
+      }
+      let mut target = &mut settings;
+      for key in parents {
+        target = target
+          .as_object_mut()
+          .ok_or_else(|| format!("変更できない設定です: {}", path))?
+          .entry(key.clone())
+          .or_insert_with(|| Value::Object(Map::new()));
+      }
+      let target = target
+        .as_object_mut()
+        .ok_or_else(|| format!("変更できない設定です: {}", path))?;
+      if change.value.is_null() {
+        target.remove(last);
+      } else {
+        target.insert(last.clone(), change.value.clone());
+      }
+    }
+    serde_json::from_value(settings).map_err(|e| format!("設定の値が正しくありません: {}", e))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn change(path: &[&str], value: Value) -> SettingChange {
+    SettingChange::new(path, &value).unwrap()
+  }
+
+  #[test]
+  fn applies_changes_by_path() {
+    let mut settings = Settings::default();
+    settings
+      .ghosts_voices
+      .insert("Old".to_string(), GhostVoiceInfo::new(2));
+
+    let info = serde_json::to_value(GhostVoiceInfo::new(3)).unwrap();
+    let changed = settings
+      .apply_changes(&[
+        change(&["volume"], Value::from(0.5)),
+        change(&["ghosts_voices", "New"], info),
+        change(&["ghosts_voices", "Old"], Value::Null),
+        change(&["engine_auto_start", "VoiceVox"], Value::from(true)),
+      ])
+      .unwrap();
+    assert_eq!(changed.volume, 0.5);
+    assert_eq!(changed.ghosts_voices["New"].voices.len(), 3);
+    assert!(!changed.ghosts_voices.contains_key("Old"));
+    assert_eq!(
+      changed.engine_auto_start.get(&Engine::VoiceVox),
+      Some(&true)
+    );
+
+    // null は既定値に戻す
+    let reset = changed
+      .apply_changes(&[change(&["volume"], Value::Null)])
+      .unwrap();
+    assert_eq!(reset.volume, 1.0);
+  }
+
+  #[test]
+  fn rejects_unknown_or_invalid_changes() {
+    let settings = Settings::default();
+    assert!(settings
+      .apply_changes(&[change(&["no_such_setting"], Value::from(1))])
+      .is_err());
+    assert!(settings
+      .apply_changes(&[change(&["volume"], Value::from("loud"))])
+      .is_err());
+    assert!(settings
+      .apply_changes(&[change(&["volume", "x"], Value::from(1))])
+      .is_err());
+    assert!(settings.apply_changes(&[change(&[], Value::Null)]).is_err());
+  }
+}
@@ -59,6 +59,17 @@ fn get_event(id: &str) -> Option<fn(&PluginRequest) -> PluginResponse> {
     "OnEngineStatusMenu" => Some(on_engine_status_menu),
     "OnVoiceSelecting" => Some(on_voice_selecting),
     "OnVoiceSelected" => Some(on_voice_selected),
+    "OnVoicePreview" => Some(on_voice_preview),
+    "OnVoiceListChanged" => Some(on_voice_list_changed),
+    "OnShellVoicesMenu" => Some(on_shell_voices_menu),
+    "OnVoiceProfileMenu" => Some(on_voice_profile_menu),
+    "OnVoiceProfileChanged" => Some(on_voice_profile_changed),
+    "OnShellVoicesChanged" => Some(on_shell_voices_changed),
+    "OnFallbackVoicesMenu" => Some(on_fallback_voices_menu),
+    "OnFallbackVoiceChanged" => Some(on_fallback_voice_changed),
+    "OnFallbackVoiceAdding" => Some(on_fallback_voice_adding),
+    "OnFallbackVoiceSelected" => Some(on_fallback_voice_selected),
+    "OnVoiceListFilterInput" => Some(on_voice_list_filter_input),
     "OnVolumeChange" => Some(on_volume_change),
     "OnDefaultVoiceSelecting" => Some(on_default_voice_selecting),
     "OnDefaultVoiceSelected" => Some(on_default_voice_selected),
@@ -71,8 +82,28 @@ fn get_event(id: &str) -> Option<fn(&PluginRequest) -> PluginResponse> {
     "OnVoiceQualityChange" => Some(on_voice_quality_change),
     "OnVoiceQualityReset" => Some(on_voice_quality_reset),
     "OnGhostBoot" => Some(on_ghost_boot),
+    "OnGhostInfoUpdate" => Some(on_ghost_info_update),
     "OnSyncSpeechContinue" => Some(on_sync_speech_continue),
     "OnSyncBalloonSettingChanged" => Some(on_sync_balloon_setting_changed),
+    "OnChoiceReadingChanged" => Some(on_choice_reading_changed),
+    "OnNormalizeTextChanged" => Some(on_normalize_text_changed),
+    "OnEnglishReadingChanged" => Some(on_english_reading_changed),
+    "OnSubstitutionMenu" => Some(on_substitution_menu),
+    "OnSubstitutionChanged" => Some(on_substitution_changed),
+    "OnSubstitutionAdding" => Some(on_substitution_adding),
+    "OnSubstitutionInput" => Some(on_substitution_input),
+    "OnSpeakRulesMenu" => Some(on_speak_rules_menu),
+    "OnSpeakRuleChanged" => Some(on_speak_rule_changed),
+    "OnSpeakRuleAdding" => Some(on_speak_rule_adding),
+    "OnSpeakRuleInput" => Some(on_speak_rule_input),
+    "OnRecordSettingChanged" => Some(on_record_setting_changed),
+    "OnRecordSessionSettingChanged" => Some(on_record_session_setting_changed),
+    "OnSubtitleFormatChanged" => Some(on_subtitle_format_changed),
+    "OnFallbackNoticeChanged" => Some(on_fallback_notice_changed),
+    "OnRecordingChange" => Some(on_recording_change),
+    "OnSpeechHistoryMenu" => Some(on_speech_history_menu),
+    "OnSpeechHistoryReplay" => Some(on_speech_history_replay),
+    "OnSpeechReplay" => Some(on_speech_replay),
     _ => None,
   }
 }
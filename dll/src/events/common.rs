@@ -52,19 +52,34 @@ pub(crate) fn get_references(req: &PluginRequest) -> Vec<&str> {
 }
 
 pub(crate) fn load_descript(file_path: String) -> HashMap<String, String> {
-  let mut descript = HashMap::new();
   let path = PathBuf::from(file_path)
     .join("ghost")
     .join("master")
     .join("descript.txt");
-  let buffer = match fs::read(path) {
-    Ok(b) => b,
+  match fs::read(path) {
+    Ok(b) => parse_descript(&b),
     Err(e) => {
       error!("Failed to read descript.txt: {}", e);
-      return descript;
+      HashMap::new()
     }
-  };
-  let mut result = SHIFT_JIS.decode(&buffer).0;
+  }
+}
+
+/// ゴーストが同梱する ghostspeaker.txt（descript.txt と同じ形式）を読む。無ければ空
+pub(crate) fn load_ghostspeaker_txt(file_path: &str) -> HashMap<String, String> {
+  let path = PathBuf::from(file_path)
+    .join("ghost")
+    .join("master")
+    .join("ghostspeaker.txt");
+  match fs::read(path) {
+    Ok(b) => parse_descript(&b),
+    Err(_) => HashMap::new(),
+  }
+}
+
+fn parse_descript(buffer: &[u8]) -> HashMap<String, String> {
+  let mut descript = HashMap::new();
+  let mut result = SHIFT_JIS.decode(buffer).0;
 
   if result
     .clone()
@@ -72,7 +87,7 @@ pub(crate) fn load_descript(file_path: String) -> HashMap<String, String> {
     .as_str()
     .contains("charset,UTF-8")
   {
-    result = UTF_8.decode(&buffer).0;
+    result = UTF_8.decode(buffer).0;
   }
 
   let input_text = result.into_owned();
@@ -1,20 +1,25 @@
 use crate::events::common::load_descript;
 use crate::events::common::*;
+use crate::events::other_ghost::{current_shell, set_current_shell};
 use crate::ipc::send_command_logged;
 use crate::plugin::request::PluginRequest;
 use crate::plugin::response::PluginResponse;
+use crate::settings::{ghost_voices, set_ghost_voices, set_setting};
 use crate::variables::*;
 use crate::variables::{PLUGIN_NAME, PLUGIN_UUID};
 use ghost_speaker_common::{
-  engine_from_port, CharacterVoice, Command, Engine, GhostVoiceInfo, Response, SpeakerInfo, Style,
-  VoiceQuality, ENGINE_LIST, NO_VOICE_UUID,
+  engine_from_port, CharacterVoice, Command, Engine, GhostVoiceInfo, Response, SpeakAction,
+  SpeakRule, SpeakerInfo, Style, SubstitutionRule, VoiceProfile, VoiceQuality, VoiceSource,
+  DEFAULT_PREVIEW_TEXT, ENGINE_LIST, NO_VOICE_UUID,
 };
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
 
 const DEFAULT_VOICE: &str = "【不明】";
 const NO_VOICE: &str = "無し";
 const UNSET_VOICE: &str = "未設定";
+const PROFILES_DIR: &str = "profiles";
 
 static ACTIVATED: Lazy<String> = Lazy::new(|| greened("有効"));
 static DEACTIVATED: Lazy<String> = Lazy::new(|| reded("無効"));
@@ -34,6 +39,201 @@ impl CharacterResizeMode {
   }
 }
 
+/// 読み上げルールの編集対象
+#[derive(Clone, Copy)]
+enum SpeakRuleTarget {
+  Global,
+  Ghost,
+}
+
+impl SpeakRuleTarget {
+  fn as_str(&self) -> &'static str {
+    match self {
+      Self::Global => "global",
+      Self::Ghost => "ghost",
+    }
+  }
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    match s {
+      "global" => Ok(Self::Global),
+      "ghost" => Ok(Self::Ghost),
+      _ => Err(format!("Invalid speak rule target: {}", s)),
+    }
+  }
+}
+
+/// 入力ボックスで追加中の読み上げルールの対象 (ghost_name, ghost_path, target)
+static PENDING_SPEAK_RULE: Lazy<Mutex<Option<(String, String, SpeakRuleTarget)>>> =
+  Lazy::new(|| Mutex::new(None));
+
+/// 入力ボックスで読み替えを追加中のメニューの戻り先 (ghost_name, ghost_path)
+static PENDING_SUBSTITUTION: Lazy<Mutex<Option<(String, String)>>> = Lazy::new(|| Mutex::new(None));
+
+/// 声質の一覧の1ページに表示する行数
+const VOICE_LIST_PAGE_SIZE: usize = 20;
+
+/// 声質の一覧の表示状態。一覧をメニューから開き直すと初期化する
+#[derive(Default)]
+struct VoiceListView {
+  filter: String,
+  page: usize,
+  /// 折りたたんだエンジン
+  collapsed_engines: HashSet<Engine>,
+  /// スタイルを展開した話者 (エンジン, 話者UUID)
+  expanded_speakers: HashSet<(Engine, String)>,
+}
+
+static VOICE_LIST_VIEW: Lazy<Mutex<VoiceListView>> =
+  Lazy::new(|| Mutex::new(VoiceListView::default()));
+
+/// 入力ボックスで声質を検索中の一覧
+static PENDING_VOICE_FILTER: Lazy<Mutex<Option<VoiceListTarget>>> = Lazy::new(|| Mutex::new(None));
+
+/// 声質の一覧の表示先。リンクの引数には
+/// "character,ゴースト名,キャラクター名,番号,パス"、"fallback,ゴースト名,キャラクター名,番号,パス"
+/// または "default,ゴースト名,パス" の形で埋め込む
+#[derive(Clone)]
+enum VoiceListTarget {
+  Character {
+    ghost_name: String,
+    character_name: String,
+    character_index: usize,
+    ghost_path: String,
+  },
+  /// キャラクターの予備の声質に加える
+  Fallback {
+    ghost_name: String,
+    character_name: String,
+    character_index: usize,
+    ghost_path: String,
+  },
+  Default {
+    ghost_name: String,
+    ghost_path: String,
+  },
+}
+
+impl VoiceListTarget {
+  fn from_refs(refs: &[&str]) -> Result<Self, String> {
+    match refs {
+      ["character", ghost_name, character_name, character_index, ghost_path, ..] => {
+        Ok(Self::Character {
+          ghost_name: ghost_name.to_string(),
+          character_name: character_name.to_string(),
+          character_index: character_index
+            .parse::<usize>()
+            .map_err(|e| format!("Invalid character_index: {}", e))?,
+          ghost_path: ghost_path.to_string(),
+        })
+      }
+      ["fallback", ghost_name, character_name, character_index, ghost_path, ..] => {
+        Ok(Self::Fallback {
+          ghost_name: ghost_name.to_string(),
+          character_name: character_name.to_string(),
+          character_index: character_index
+            .parse::<usize>()
+            .map_err(|e| format!("Invalid character_index: {}", e))?,
+          ghost_path: ghost_path.to_string(),
+        })
+      }
+      ["default", ghost_name, ghost_path, ..] => Ok(Self::Default {
+        ghost_name: ghost_name.to_string(),
+        ghost_path: ghost_path.to_string(),
+      }),
+      _ => Err(format!("Invalid voice list target: {:?}", refs)),
+    }
+  }
+
+  fn to_refs(&self) -> String {
+    match self {
+      Self::Character {
+        ghost_name,
+        character_name,
+        character_index,
+        ghost_path,
+      } => format!(
+        "character,{},{},{},{}",
+        ghost_name, character_name, character_index, ghost_path
+      ),
+      Self::Fallback {
+        ghost_name,
+        character_name,
+        character_index,
+        ghost_path,
+      } => format!(
+        "fallback,{},{},{},{}",
+        ghost_name, character_name, character_index, ghost_path
+      ),
+      Self::Default {
+        ghost_name,
+        ghost_path,
+      } => format!("default,{},{}", ghost_name, ghost_path),
+    }
+  }
+
+  fn page(&self) -> String {
+    match self {
+      Self::Character {
+        ghost_name,
+        character_name,
+        character_index,
+        ghost_path,
+      } => voice_selecting_page(ghost_name, character_name, *character_index, ghost_path),
+      Self::Fallback {
+        ghost_name,
+        character_name,
+        character_index,
+        ghost_path,
+      } => fallback_voice_selecting_page(ghost_name, character_name, *character_index, ghost_path),
+      Self::Default {
+        ghost_name,
+        ghost_path,
+      } => default_voice_selecting_page(ghost_name, ghost_path),
+    }
+  }
+
+  /// 選択中のキャラクター（またはデフォルト声質）の音声調整
+  fn voice_quality(&self) -> VoiceQuality {
+    match self {
+      Self::Character {
+        ghost_name,
+        character_index,
+        ..
+      }
+      | Self::Fallback {
+        ghost_name,
+        character_index,
+        ..
+      } => match SETTINGS.read() {
+        Ok(s) => s
+          .ghosts_voices
+          .get(ghost_name)
+          .and_then(|info| {
+            info
+              .voices_for_shell(current_shell(ghost_name).as_deref())
+              .get(*character_index)
+              .cloned()
+              .flatten()
+          })
+          .map(|v| v.voice_quality)
+          .unwrap_or_default(),
+        Err(e) => {
+          error!("Failed to read SETTINGS: {}", e);
+          VoiceQuality::default()
+        }
+      },
+      Self::Default { .. } => match SETTINGS.read() {
+        Ok(s) => s.initial_voice.voice_quality.clone(),
+        Err(e) => {
+          error!("Failed to read SETTINGS: {}", e);
+          VoiceQuality::default()
+        }
+      },
+    }
+  }
+}
+
 fn colored(s: &str, r: u8, g: u8, b: u8) -> String {
   format!("\\f[color,{},{},{}]{}\\f[color,default]", r, g, b, s)
 }
@@ -59,8 +259,6 @@ fn refresh_engine_status() {
   if let Some(Response::EngineStatus {
     speakers_info,
     connection_status,
-    engine_paths,
-    engine_auto_start,
   }) = send_command_logged(&Command::GetEngineStatus)
   {
     if let Ok(mut si) = SPEAKERS_INFO.write() {
@@ -69,12 +267,6 @@ fn refresh_engine_status() {
     if let Ok(mut cs) = CURRENT_CONNECTION_STATUS.write() {
       *cs = connection_status;
     }
-    if let Ok(mut ep) = ENGINE_PATH.write() {
-      *ep = engine_paths;
-    }
-    if let Ok(mut ea) = ENGINE_AUTO_START.write() {
-      *ea = engine_auto_start;
-    }
   }
 }
 
@@ -102,18 +294,24 @@ pub(crate) fn on_menu_exec(req: &PluginRequest) -> PluginResponse {
   };
   let characters = count_characters(ghost_description);
   let path_for_arg = refs[4].to_string().replace('\\', "\\\\");
+  // メニューから開き直したときは Reference2 が dummy になるので、覚えているシェルを使う
+  if let Some(shell_name) = refs.get(2).filter(|s| **s != "dummy") {
+    set_current_shell(&ghost_name, shell_name);
+  }
+  let shell = current_shell(&ghost_name);
   debug!("getting ghosts_voices");
-  let ghosts_voices = match GHOSTS_VOICES.read() {
-    Ok(gv) => gv,
+  let settings = match SETTINGS.read() {
+    Ok(s) => s.clone(),
     Err(e) => {
-      error!("Failed to read GHOSTS_VOICES: {}", e);
+      error!("Failed to read SETTINGS: {}", e);
       return new_response_with_script(String::new(), false);
     }
   };
+  let ghosts_voices = &settings.ghosts_voices;
   let character_voices = match ghosts_voices.get(&ghost_name) {
-    Some(info) => &info.voices,
+    Some(info) => info.voices_for_shell(shell.as_deref()),
     None => {
-      error!("Ghost not found in GHOSTS_VOICES: {}", ghost_name);
+      error!("Ghost not found in settings: {}", ghost_name);
       return new_response_with_script(String::new(), false);
     }
   };
@@ -169,13 +367,104 @@ pub(crate) fn on_menu_exec(req: &PluginRequest) -> PluginResponse {
     );
   }
 
+  let mut choice_reading_setting = String::from("-\\n");
+  if let Some(si) = ghosts_voices.get(&ghost_name) {
+    choice_reading_setting = format!(
+      "【現在 \\__q[OnChoiceReadingChanged,{},{}]{}\\__q】\\n",
+      ghost_name,
+      path_for_arg,
+      decorated(si.choice_reading.name(), "bold"),
+    );
+  }
+
+  let mut normalize_setting = String::from("-\\n");
+  if let Some(si) = ghosts_voices.get(&ghost_name) {
+    let switch = if si.normalize_text {
+      ACTIVATED.to_string()
+    } else {
+      DEACTIVATED.to_string()
+    };
+    normalize_setting = format!(
+      "【現在 \\__q[OnNormalizeTextChanged,{},{}]{}\\__q】\\n",
+      ghost_name,
+      path_for_arg,
+      decorated(&switch, "bold"),
+    );
+  }
+
+  let mut english_setting = String::from("-\\n");
+  if let Some(si) = ghosts_voices.get(&ghost_name) {
+    let switch = if si.english_to_katakana {
+      ACTIVATED.to_string()
+    } else {
+      DEACTIVATED.to_string()
+    };
+    english_setting = format!(
+      "【現在 \\__q[OnEnglishReadingChanged,{},{}]{}\\__q】{}\\n",
+      ghost_name,
+      path_for_arg,
+      decorated(&switch, "bold"),
+      bouyomichan_note,
+    );
+  }
+
+  let mut record_setting = String::from("-\\n");
+  if let Some(si) = ghosts_voices.get(&ghost_name) {
+    let switch = if si.record {
+      ACTIVATED.to_string()
+    } else {
+      DEACTIVATED.to_string()
+    };
+    record_setting = format!(
+      "【現在 \\__q[OnRecordSettingChanged,{},{}]{}\\__q】{}\\n",
+      ghost_name,
+      path_for_arg,
+      decorated(&switch, "bold"),
+      bouyomichan_note,
+    );
+  }
+
+  let switch = if settings.record_session {
+    ACTIVATED.to_string()
+  } else {
+    DEACTIVATED.to_string()
+  };
+  let record_session_setting = format!(
+    "【現在 \\__q[OnRecordSessionSettingChanged,{},{}]{}\\__q】\\n",
+    ghost_name,
+    path_for_arg,
+    decorated(&switch, "bold"),
+  );
+
+  let subtitle_setting = format!(
+    "【現在 \\__q[OnSubtitleFormatChanged,{},{}]{}\\__q】\\n",
+    ghost_name,
+    path_for_arg,
+    decorated(settings.subtitle_format.name(), "bold"),
+  );
+
+  let substitution_link = format!(
+    "【\\__q[OnSubstitutionMenu,{},{}]{}\\__q】\\n",
+    ghost_name,
+    path_for_arg,
+    decorated("編集", "bold"),
+  );
+
+  let speak_rules_link = format!(
+    "【\\__q[OnSpeakRulesMenu,{},{}]{}\\__q】\\n",
+    ghost_name,
+    path_for_arg,
+    decorated("編集", "bold"),
+  );
+
   for i in 0..character_voices.len() {
     characters_info.push_str(&chara_info(
       &characters,
       &ghost_name,
       i,
       &path_for_arg,
-      &ghosts_voices,
+      shell.as_deref(),
+      ghosts_voices,
     ));
   }
   let mut character_resize_buttons = String::new();
@@ -196,6 +485,29 @@ pub(crate) fn on_menu_exec(req: &PluginRequest) -> PluginResponse {
     decorated("+", "bold"),
   ));
   characters_info.push_str(&format!("【{}】\\n", character_resize_buttons));
+  if let Some(shell_name) = &shell {
+    let uses_profile = ghosts_voices
+      .get(&ghost_name)
+      .is_some_and(|info| info.has_shell_profile(Some(shell_name)));
+    characters_info.push_str(&format!(
+      "シェル: {} {} 【\\__q[OnShellVoicesMenu,{},{}]{}\\__q】\\n",
+      escaped(shell_name),
+      grayed(if uses_profile {
+        "(シェル別の声質)"
+      } else {
+        "(ゴースト共通の声質)"
+      }),
+      ghost_name,
+      path_for_arg,
+      decorated("設定", "bold"),
+    ));
+  }
+  characters_info.push_str(&format!(
+    "声質の設定の共有 【\\__q[OnVoiceProfileMenu,{},{}]{}\\__q】\\n",
+    ghost_name,
+    path_for_arg,
+    decorated("書き出し・読み込み", "bold"),
+  ));
 
   // エンジンサマリー: 起動中エンジン数 / 全エンジン数
   let running_count = ENGINE_LIST
@@ -211,13 +523,7 @@ pub(crate) fn on_menu_exec(req: &PluginRequest) -> PluginResponse {
   );
 
   let unit: f32 = 0.05;
-  let v = match VOLUME.read() {
-    Ok(volume) => *volume,
-    Err(e) => {
-      error!("Failed to read VOLUME: {}", e);
-      1.0
-    }
-  };
+  let v = settings.volume;
 
   let mut volume_changer = String::new();
   if v > unit {
@@ -240,14 +546,7 @@ pub(crate) fn on_menu_exec(req: &PluginRequest) -> PluginResponse {
     decorated(">>", "bold"),
   ));
 
-  let p = match SPEAK_BY_PUNCTUATION.read() {
-    Ok(sbp) => *sbp,
-    Err(e) => {
-      error!("Failed to read SPEAK_BY_PUNCTUATION: {}", e);
-      true
-    }
-  };
-  let switch = if p {
+  let switch = if settings.speak_by_punctuation {
     ACTIVATED.to_string()
   } else {
     DEACTIVATED.to_string()
@@ -263,13 +562,26 @@ pub(crate) fn on_menu_exec(req: &PluginRequest) -> PluginResponse {
     "【現在 \\__q[OnDefaultVoiceSelecting,{},{}]{}\\__q】\\n",
     ghost_name,
     path_for_arg,
-    match INITIAL_VOICE.read() {
-      Ok(iv) => get_voice(&Some(iv.clone())),
-      Err(e) => {
-        error!("Failed to read INITIAL_VOICE: {}", e);
-        get_voice(&None)
-      }
-    },
+    get_voice(&Some(settings.initial_voice.clone())),
+  );
+
+  let switch = if settings.fallback_notice {
+    ACTIVATED.to_string()
+  } else {
+    DEACTIVATED.to_string()
+  };
+  let fallback_notice_setting = format!(
+    "【現在 \\__q[OnFallbackNoticeChanged,{},{}]{}\\__q】\\n",
+    ghost_name,
+    path_for_arg,
+    decorated(&switch, "bold"),
+  );
+
+  let speech_history_link = format!(
+    "【\\__q[OnSpeechHistoryMenu,{},{}]{}\\__q】\\n",
+    ghost_name,
+    path_for_arg,
+    decorated("表示", "bold"),
   );
 
   let menu_script = format!(
@@ -294,7 +606,29 @@ pub(crate) fn on_menu_exec(req: &PluginRequest) -> PluginResponse {
     {}\
       \\![*]読み上げに文章表示を合わせる(ゴースト別)\\n\
     {}{}\\n\
+      \\![*]選択肢の読み上げ(ゴースト別)\\n\
+    {}\
+      \\![*]数字や記号の読みを整える(ゴースト別)\\n\
+    {}\
+      \\![*]英単語をカタカナで読む(ゴースト別)\\n\
+    {}\
+      \\![*]読み替え辞書(共通)\\n\
+    {}\
+      \\![*]イベント別の読み上げルール\\n\
+    {}\
       \\![*]デフォルト声質(共通)\\n\
+    {}\
+      \\![*]予備の声質を使ったら通知する(共通)\\n\
+    {}\
+      \\![*]読み上げ履歴(ゴースト別)\\n\
+    {}\
+      \\n\
+      ■ 録音 (保存先: プラグインフォルダの recordings)\\n\
+      \\![*]読み上げた音声を保存する(ゴースト別)\\n\
+    {}\
+      \\![*]トークをつなげたファイルも保存する(共通)\\n\
+    {}\
+      \\![*]字幕を書き出す(共通)\\n\
     {}\
       \\n\
       ■ エンジン設定 (起動中: {}/{})\
@@ -313,7 +647,17 @@ pub(crate) fn on_menu_exec(req: &PluginRequest) -> PluginResponse {
     division_setting,
     sync_balloon_setting,
     bouyomichan_note,
+    choice_reading_setting,
+    normalize_setting,
+    english_setting,
+    substitution_link,
+    speak_rules_link,
     default_voice_info,
+    fallback_notice_setting,
+    speech_history_link,
+    record_setting,
+    record_session_setting,
+    subtitle_setting,
     running_count,
     total_count,
     engine_summary,
@@ -349,10 +693,10 @@ pub(crate) fn on_engine_status_menu(req: &PluginRequest) -> PluginResponse {
     }
   };
 
-  let engine_auto_start = match ENGINE_AUTO_START.read() {
-    Ok(eas) => eas.clone(),
+  let engine_auto_start = match SETTINGS.read() {
+    Ok(s) => s.engine_auto_start.clone(),
     Err(e) => {
-      error!("Failed to read ENGINE_AUTO_START: {}", e);
+      error!("Failed to read SETTINGS: {}", e);
       return new_response_with_script(String::new(), false);
     }
   };
@@ -408,9 +752,10 @@ fn chara_info(
   ghost_name: &String,
   index: usize,
   ghost_path: &String,
-  ghosts_voices: &std::sync::RwLockReadGuard<HashMap<String, GhostVoiceInfo>>,
+  shell: Option<&str>,
+  ghosts_voices: &HashMap<String, GhostVoiceInfo>,
 ) -> String {
-  let voice = get_voice_from_ghost(ghost_name, index, ghosts_voices);
+  let voice = get_voice_from_ghost(ghost_name, index, shell, ghosts_voices);
   let index_tag = if index < 2 {
     format!("\\\\{}", index)
   } else {
@@ -423,8 +768,35 @@ fn chara_info(
     "".to_string()
   };
 
+  // シェル別の声質を使っているときは、その声質（無ければゴースト共通の声質）を編集する
+  let (own_voice, effective_voice, source) = match ghosts_voices.get(ghost_name) {
+    Some(info) if info.has_shell_profile(shell) => {
+      let own = shell
+        .and_then(|s| info.shell_voices.get(s))
+        .and_then(|p| p.get(index).cloned().flatten());
+      let source = if own.is_none() {
+        format!(" {}", grayed("(ゴースト共通)"))
+      } else {
+        String::new()
+      };
+      (
+        own,
+        info.voices_for_shell(shell).get(index).cloned().flatten(),
+        source,
+      )
+    }
+    Some(info) => {
+      let own = info.voices.get(index).cloned().flatten();
+      let source = match info.voice_source(index) {
+        VoiceSource::Ghost => format!(" {}", grayed("(ゴーストの推奨)")),
+        VoiceSource::User => String::new(),
+      };
+      (own.clone(), own, source)
+    }
+    None => (None, None, String::new()),
+  };
   let mut result = format!(
-    "{}{}\\n    \\__q[OnVoiceSelecting,{},{},{},{}]{}\\__q\\n",
+    "{}{}\\n    \\__q[OnVoiceSelecting,{},{},{},{}]{}\\__q{}\\n",
     index_tag,
     character_name,
     ghost_name,
@@ -432,11 +804,12 @@ fn chara_info(
     index,
     ghost_path,
     decorated(&voice, "bold"),
+    source,
   );
 
   // 声質が設定済み（NO_VOICE_UUIDでない）場合のみ音声調整リンクを表示
   if let Some(info) = ghosts_voices.get(ghost_name) {
-    if let Some(Some(cv)) = info.voices.get(index) {
+    if let Some(cv) = &own_voice {
       if cv.speaker_uuid != NO_VOICE_UUID {
         let vq = &cv.voice_quality;
         result.push_str(&format!(
@@ -452,6 +825,23 @@ fn chara_info(
         ));
       }
     }
+    // 「無し」を割り当てたキャラクターは読み上げないので、予備の声質も使わない
+    let silent = matches!(&effective_voice, Some(cv) if cv.speaker_uuid == NO_VOICE_UUID);
+    if !silent {
+      let count = info
+        .fallback_voices
+        .get(index)
+        .map(|v| v.len())
+        .unwrap_or(0);
+      result.push_str(&format!(
+        "    \\__q[OnFallbackVoicesMenu,{},{},{},{}]{}\\__q\\n",
+        ghost_name,
+        characters.get(index).unwrap_or(&String::from("")),
+        index,
+        ghost_path,
+        grayed(&format!("予備の声質 [{}件]", count)),
+      ));
+    }
   }
 
   result
@@ -460,10 +850,11 @@ fn chara_info(
 fn get_voice_from_ghost(
   ghost_name: &String,
   index: usize,
-  ghosts_voices: &std::sync::RwLockReadGuard<HashMap<String, GhostVoiceInfo>>,
+  shell: Option<&str>,
+  ghosts_voices: &HashMap<String, GhostVoiceInfo>,
 ) -> String {
   if let Some(si) = ghosts_voices.get(ghost_name) {
-    if let Some(c) = si.voices.get(index) {
+    if let Some(c) = si.voices_for_shell(shell).get(index) {
       return get_voice(c);
     }
   };
@@ -516,77 +907,364 @@ fn get_voice(c: &Option<CharacterVoice>) -> String {
 type ListCallback = Box<dyn Fn(&Engine, &SpeakerInfo, &Style) -> String>;
 type DummyCallback = Box<dyn Fn(String, &CharacterVoice) -> String>;
 
-fn list_available_voices(callbacks: (ListCallback, DummyCallback)) -> String {
+fn style_name(style: &Style) -> &str {
+  style.style_name.as_deref().unwrap_or("不明なスタイル")
+}
+
+/// 声質の一覧。最近使った声質・エンジンごと話者ごとの折りたたみ・検索・ページ送りを備える
+fn list_available_voices(
+  callbacks: (ListCallback, DummyCallback),
+  target: &VoiceListTarget,
+) -> String {
   let def = CharacterVoice::no_voice();
   let mut m = "\\b[2]".to_string();
+  let view = match VOICE_LIST_VIEW.lock() {
+    Ok(v) => v,
+    Err(e) => {
+      error!("Failed to lock VOICE_LIST_VIEW: {}", e);
+      return m;
+    }
+  };
+  let target_refs = target.to_refs();
+  let link = |op: &str, arg: &str, label: &str| {
+    format!(
+      "\\__q[OnVoiceListChanged,{},{},{}]{}\\__q",
+      op, arg, target_refs, label
+    )
+  };
+
+  if view.filter.is_empty() {
+    m.push_str(&format!(
+      "【{}】\\n",
+      link("search", "-", &decorated("検索", "bold"))
+    ));
+  } else {
+    m.push_str(&format!(
+      "検索: {} 【{}】【{}】\\n",
+      decorated(&escaped(&view.filter), "bold"),
+      link("search", "-", "変更"),
+      link("clear", "-", "解除"),
+    ));
+  }
   m.push_str(callbacks.1(NO_VOICE.to_string(), &def).as_str());
   let speakers_info = match SPEAKERS_INFO.read() {
     Ok(si) => si,
     Err(_) => return m,
   };
+
+  // 最近使った声質は、検索していないときの先頭のページに出す
+  if view.page == 0 && view.filter.is_empty() {
+    let recent_voices = match SETTINGS.read() {
+      Ok(s) => s.recent_voices.clone(),
+      Err(e) => {
+        error!("Failed to read SETTINGS: {}", e);
+        Vec::new()
+      }
+    };
+    let recent: Vec<String> = recent_voices
+      .iter()
+      .filter_map(|voice| {
+        let engine = engine_from_port(voice.port)?;
+        let speaker = speakers_info
+          .get(&engine)?
+          .iter()
+          .find(|s| s.speaker_uuid == voice.speaker_uuid)?;
+        let style = speaker
+          .styles
+          .iter()
+          .find(|s| s.style_id == Some(voice.style_id))?;
+        Some(callbacks.0(&engine, speaker, style))
+      })
+      .collect();
+    if !recent.is_empty() {
+      m.push_str("\\n■ 最近使った声質\\n");
+      m.push_str(&recent.concat());
+    }
+  }
+
+  // 表示する行を並べてからページに分ける。(エンジン, エンジンの見出しか, 行)
+  let filter = view.filter.to_lowercase();
+  let mut rows: Vec<(Engine, bool, String)> = Vec::new();
   for engine in ENGINE_LIST.iter() {
-    if let Some(speakers) = speakers_info.get(engine) {
-      if speakers.is_empty() {
+    let speakers = match speakers_info.get(engine) {
+      Some(speakers) if !speakers.is_empty() => speakers,
+      _ => continue,
+    };
+    let mut engine_rows = Vec::new();
+    let mut speaker_count = 0;
+    for speaker in speakers.iter() {
+      let speaker_matched = speaker.speaker_name.to_lowercase().contains(&filter);
+      let styles: Vec<&Style> = speaker
+        .styles
+        .iter()
+        .filter(|style| speaker_matched || style_name(style).to_lowercase().contains(&filter))
+        .collect();
+      if styles.is_empty() {
+        continue;
+      }
+      speaker_count += 1;
+      // スタイルが1つの話者と検索結果は、折りたたまずにスタイルを並べる
+      if speaker.styles.len() == 1 || !filter.is_empty() {
+        for style in styles {
+          engine_rows.push(callbacks.0(engine, speaker, style));
+        }
         continue;
       }
-      m.push_str(&format!("\\n■ {}\\n", engine.name()));
-      for speaker in speakers.iter() {
-        for style in speaker.styles.iter() {
-          m.push_str(callbacks.0(engine, speaker, style).as_str());
+      let expanded = view
+        .expanded_speakers
+        .contains(&(*engine, speaker.speaker_uuid.clone()));
+      engine_rows.push(format!(
+        "{}\\n",
+        link(
+          "speaker",
+          &format!("{}:{}", engine.port(), speaker.speaker_uuid),
+          &format!(
+            "{} {} ({})",
+            if expanded { "▼" } else { "▶" },
+            speaker.speaker_name,
+            speaker.styles.len()
+          ),
+        )
+      ));
+      if expanded {
+        for style in styles {
+          engine_rows.push(format!("　{}", callbacks.0(engine, speaker, style)));
         }
       }
     }
+    if engine_rows.is_empty() {
+      continue;
+    }
+    let collapsed = filter.is_empty() && view.collapsed_engines.contains(engine);
+    rows.push((
+      *engine,
+      true,
+      format!(
+        "\\n■ {}\\n",
+        link(
+          "engine",
+          &engine.port().to_string(),
+          &format!(
+            "{} {} ({}人)",
+            if collapsed { "▶" } else { "▼" },
+            engine.name(),
+            speaker_count
+          ),
+        )
+      ),
+    ));
+    if !collapsed {
+      rows.extend(engine_rows.into_iter().map(|row| (*engine, false, row)));
+    }
+  }
+  if rows.is_empty() && !filter.is_empty() {
+    m.push_str(&format!("\\n{}\\n", grayed("見つかりませんでした")));
+  }
+
+  let pages = rows.len().div_ceil(VOICE_LIST_PAGE_SIZE).max(1);
+  let page = view.page.min(pages - 1);
+  let start = page * VOICE_LIST_PAGE_SIZE;
+  if let Some((engine, false, _)) = rows.get(start) {
+    m.push_str(&format!("\\n■ {} (続き)\\n", engine.name()));
+  }
+  for (_, _, row) in rows.iter().skip(start).take(VOICE_LIST_PAGE_SIZE) {
+    m.push_str(row);
+  }
+  if pages > 1 {
+    let prev = if page > 0 {
+      link("page", &(page - 1).to_string(), "← 前へ")
+    } else {
+      grayed("← 前へ")
+    };
+    let next = if page + 1 < pages {
+      link("page", &(page + 1).to_string(), "次へ →")
+    } else {
+      grayed("次へ →")
+    };
+    m.push_str(&format!("\\n{}  {}/{}  {}\\n", prev, page + 1, pages, next));
   }
   m
 }
 
-fn list_callback_for_characters(
-  ghost_name: String,
-  character_index: usize,
-  ghost_path: String,
-) -> (ListCallback, DummyCallback) {
-  let gn = ghost_name.clone();
-  let gp = ghost_path.clone();
-  let list_callback = Box::new(
-    move |engine: &Engine, speaker: &SpeakerInfo, style: &Style| {
-      format!(
-        "\\![*]\\q[{} | {},OnVoiceSelected,{},{},{},{},{},{}]\\n",
-        speaker.speaker_name,
-        style
-          .style_name
-          .as_ref()
-          .unwrap_or(&"不明なスタイル".to_string()),
-        ghost_name,
-        character_index,
-        engine.port(),
-        speaker.speaker_uuid,
-        style.style_id.unwrap_or(-1),
-        ghost_path,
-      )
-    },
-  );
-  let dummy_callback = Box::new(move |voice: String, c: &CharacterVoice| {
-    format!(
-      "\\![*]\\q[{},OnVoiceSelected,{},{},{},{},{},{}]\\n",
-      voice, gn, character_index, c.port, c.speaker_uuid, c.style_id, gp,
-    )
-  });
-  (list_callback, dummy_callback)
+/// 一覧を開き直したときに、検索やページの状態を初期化する
+fn reset_voice_list_view() {
+  match VOICE_LIST_VIEW.lock() {
+    Ok(mut view) => *view = VoiceListView::default(),
+    Err(e) => error!("Failed to lock VOICE_LIST_VIEW: {}", e),
+  }
 }
 
-pub(crate) fn on_voice_selecting(req: &PluginRequest) -> PluginResponse {
+/// 声質の一覧のページ送り・折りたたみ・検索
+pub(crate) fn on_voice_list_changed(req: &PluginRequest) -> PluginResponse {
   let refs = get_references(req);
-  let ghost_name = match refs.first() {
-    Some(name) => name,
-    None => {
-      error!("Missing ghost_name parameter");
-      return new_response_with_script(String::new(), false);
-    }
-  };
-  let character_name = match refs.get(1) {
-    Some(name) => name,
-    None => {
-      error!("Missing character_name parameter");
+  if refs.len() < 3 {
+    error!("Missing parameters for OnVoiceListChanged");
+    return new_response_with_script(String::new(), false);
+  }
+  let target = match VoiceListTarget::from_refs(&refs[2..]) {
+    Ok(t) => t,
+    Err(e) => {
+      error!("{}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  let (op, arg) = (refs[0], refs[1]);
+  if op == "search" {
+    match PENDING_VOICE_FILTER.lock() {
+      Ok(mut p) => *p = Some(target),
+      Err(e) => {
+        error!("Failed to lock PENDING_VOICE_FILTER: {}", e);
+        return new_response_with_script(String::new(), false);
+      }
+    }
+    let m = format!(
+      "\\b[2]\\_q話者名またはスタイル名の一部を入力してください。\\n{}\\![open,inputbox,OnVoiceListFilterInput,0]",
+      grayed("空欄で検索を解除します"),
+    );
+    return new_response_with_script(m, false);
+  }
+
+  match VOICE_LIST_VIEW.lock() {
+    Ok(mut view) => match op {
+      "clear" => {
+        view.filter.clear();
+        view.page = 0;
+      }
+      "page" => view.page = arg.parse::<usize>().unwrap_or(0),
+      "engine" => {
+        if let Some(engine) = arg.parse::<i32>().ok().and_then(engine_from_port) {
+          if !view.collapsed_engines.remove(&engine) {
+            view.collapsed_engines.insert(engine);
+          }
+        }
+      }
+      "speaker" => {
+        let key = arg.split_once(':').and_then(|(port, uuid)| {
+          let engine = engine_from_port(port.parse::<i32>().ok()?)?;
+          Some((engine, uuid.to_string()))
+        });
+        if let Some(key) = key {
+          if !view.expanded_speakers.remove(&key) {
+            view.expanded_speakers.insert(key);
+          }
+        }
+      }
+      _ => error!("Invalid voice list operation: {}", op),
+    },
+    Err(e) => error!("Failed to lock VOICE_LIST_VIEW: {}", e),
+  }
+  new_response_with_script(target.page(), true)
+}
+
+pub(crate) fn on_voice_list_filter_input(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let input = refs
+    .first()
+    .map(|s| s.trim().to_string())
+    .unwrap_or_default();
+  let target = match PENDING_VOICE_FILTER.lock() {
+    Ok(mut p) => p.take(),
+    Err(e) => {
+      error!("Failed to lock PENDING_VOICE_FILTER: {}", e);
+      None
+    }
+  };
+  let target = match target {
+    Some(t) => t,
+    None => {
+      error!("No pending voice filter");
+      return new_response_nocontent();
+    }
+  };
+  match VOICE_LIST_VIEW.lock() {
+    Ok(mut view) => {
+      view.filter = input;
+      view.page = 0;
+    }
+    Err(e) => error!("Failed to lock VOICE_LIST_VIEW: {}", e),
+  }
+  new_response_with_script(target.page(), true)
+}
+
+/// 最近使った声質に加える
+fn remember_recent_voice(voice: &CharacterVoice) {
+  if voice.speaker_uuid == NO_VOICE_UUID {
+    return;
+  }
+  let mut recent_voices = match SETTINGS.read() {
+    Ok(s) => s.recent_voices.clone(),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      return;
+    }
+  };
+  recent_voices.retain(|v| {
+    !(v.port == voice.port && v.speaker_uuid == voice.speaker_uuid && v.style_id == voice.style_id)
+  });
+  recent_voices.insert(0, voice.clone());
+  recent_voices.truncate(RECENT_VOICES_LIMIT);
+  set_setting(&["recent_voices"], &recent_voices);
+}
+
+/// 声質の一覧の各スタイルに付ける試聴のリンク。target は試聴後に表示し直す一覧の指定
+fn preview_link(engine: &Engine, speaker: &SpeakerInfo, style: &Style, target: &str) -> String {
+  format!(
+    "\\_l[@0,]\\f[align,right]\\__q[OnVoicePreview,{},{},{},{}]{}\\__q",
+    engine.port(),
+    speaker.speaker_uuid,
+    style.style_id.unwrap_or(-1),
+    target,
+    decorated("試聴", "bold"),
+  )
+}
+
+fn list_callback_for_characters(
+  ghost_name: String,
+  character_index: usize,
+  ghost_path: String,
+  target: &VoiceListTarget,
+) -> (ListCallback, DummyCallback) {
+  let gn = ghost_name.clone();
+  let gp = ghost_path.clone();
+  let target = target.to_refs();
+  let list_callback = Box::new(
+    move |engine: &Engine, speaker: &SpeakerInfo, style: &Style| {
+      format!(
+        "\\![*]\\q[{} | {},OnVoiceSelected,{},{},{},{},{},{}]{}\\n",
+        speaker.speaker_name,
+        style_name(style),
+        ghost_name,
+        character_index,
+        engine.port(),
+        speaker.speaker_uuid,
+        style.style_id.unwrap_or(-1),
+        ghost_path,
+        preview_link(engine, speaker, style, &target),
+      )
+    },
+  );
+  let dummy_callback = Box::new(move |voice: String, c: &CharacterVoice| {
+    format!(
+      "\\![*]\\q[{},OnVoiceSelected,{},{},{},{},{},{}]\\n",
+      voice, gn, character_index, c.port, c.speaker_uuid, c.style_id, gp,
+    )
+  });
+  (list_callback, dummy_callback)
+}
+
+pub(crate) fn on_voice_selecting(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let ghost_name = match refs.first() {
+    Some(name) => name,
+    None => {
+      error!("Missing ghost_name parameter");
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  let character_name = match refs.get(1) {
+    Some(name) => name,
+    None => {
+      error!("Missing character_name parameter");
       return new_response_with_script(String::new(), false);
     }
   };
@@ -611,15 +1289,35 @@ pub(crate) fn on_voice_selecting(req: &PluginRequest) -> PluginResponse {
     }
   };
 
+  reset_voice_list_view();
+  new_response_with_script(
+    voice_selecting_page(ghost_name, character_name, character_index, ghost_path),
+    true,
+  )
+}
+
+fn voice_selecting_page(
+  ghost_name: &str,
+  character_name: &str,
+  character_index: usize,
+  ghost_path: &str,
+) -> String {
+  let target = VoiceListTarget::Character {
+    ghost_name: ghost_name.to_string(),
+    character_name: character_name.to_string(),
+    character_index,
+    ghost_path: ghost_path.to_string(),
+  };
   let callback = list_callback_for_characters(
     ghost_name.to_string(),
     character_index,
     ghost_path.to_string(),
+    &target,
   );
   let mut m = format!("\\C\\c\\b[2]\\_q{}\\n{}\\n\\n", ghost_name, character_name);
-  m.push_str(list_available_voices(callback).as_str());
+  m.push_str(list_available_voices(callback, &target).as_str());
   m.push_str("\\n\\q[×,]");
-  new_response_with_script(m.to_string(), true)
+  m
 }
 
 pub(crate) fn on_voice_selected(req: &PluginRequest) -> PluginResponse {
@@ -692,25 +1390,17 @@ pub(crate) fn on_voice_selected(req: &PluginRequest) -> PluginResponse {
     voice_quality: VoiceQuality::default(),
   };
 
-  let ghost_name_str = ghost_name.to_string();
-  let mut ghosts_voices = match GHOSTS_VOICES.write() {
-    Ok(gv) => gv,
-    Err(e) => {
-      error!("Failed to write GHOSTS_VOICES: {}", e);
-      return new_response_with_script(String::new(), false);
-    }
-  };
-  if let Some(info) = ghosts_voices.get_mut(*ghost_name) {
-    let voices = &mut info.voices;
+  remember_recent_voice(&voice);
+  let shell = current_shell(ghost_name);
+  if let Some(mut info) = ghost_voices(ghost_name) {
+    let in_profile = info.has_shell_profile(shell.as_deref());
+    let voices = info.editable_voices(shell.as_deref());
     voices.remove(character_index);
     voices.insert(character_index, Some(voice));
-    // ワーカーに更新を通知
-    let info_clone = info.clone();
-    drop(ghosts_voices);
-    send_command_logged(&Command::UpdateGhostVoices {
-      ghost_name: ghost_name_str,
-      info: info_clone,
-    });
+    if !in_profile {
+      info.set_voice_source(character_index, VoiceSource::User);
+    }
+    set_ghost_voices(ghost_name, &info);
   } else {
     error!("Ghost {} not found", ghost_name);
     return new_response_with_script(String::new(), false);
@@ -724,6 +1414,291 @@ pub(crate) fn on_voice_selected(req: &PluginRequest) -> PluginResponse {
   new_response_with_script(script, false)
 }
 
+/// キャラクターの予備の声質の一覧。順番の入れ替え・削除・追加ができる
+fn fallback_voices_page(
+  ghost_name: &str,
+  character_name: &str,
+  character_index: usize,
+  ghost_path: &str,
+) -> String {
+  let fallbacks = ghost_voices(ghost_name)
+    .and_then(|info| info.fallback_voices.get(character_index).cloned())
+    .unwrap_or_default();
+  let args = format!(
+    "{},{},{},{}",
+    ghost_name, character_name, character_index, ghost_path
+  );
+
+  let mut rows = String::new();
+  if fallbacks.is_empty() {
+    rows.push_str(&format!("    {}\\n", grayed("(なし)")));
+  }
+  for (i, voice) in fallbacks.iter().enumerate() {
+    let up = if i > 0 {
+      format!("\\__q[OnFallbackVoiceChanged,up,{},{}]↑\\__q", i, args)
+    } else {
+      grayed("↑")
+    };
+    rows.push_str(&format!(
+      "    {}. {}\\_l[@0,]\\f[align,right]{} \\__q[OnFallbackVoiceChanged,remove,{},{}]×\\__q\\n",
+      i + 1,
+      get_voice(&Some(voice.clone())),
+      up,
+      i,
+      args,
+    ));
+  }
+
+  format!(
+    "\
+    \\C\\c\\b[2]\\_q\
+    {}\\n{} の予備の声質\\n\\n\
+    {}\
+    【\\__q[OnFallbackVoiceAdding,{}]{}\\__q】\\n\
+    \\n\
+    {}\\n\
+    \\n\
+    \\__q[OnMenuExec,dummy,{},dummy,dummy,{}]{}\\__q\\n\
+    ",
+    ghost_name,
+    character_name,
+    rows,
+    args,
+    decorated("追加", "bold"),
+    grayed("割り当てた声質のエンジンが起動していないとき、上から順に試します。どれも使えなければデフォルト声質で読み上げます"),
+    ghost_name,
+    ghost_path,
+    decorated("戻る", "bold"),
+  )
+}
+
+/// refs の先頭から "ゴースト名,キャラクター名,番号,パス" を読む
+fn fallback_target(refs: &[&str]) -> Option<(String, String, usize, String)> {
+  match refs {
+    [ghost_name, character_name, character_index, ghost_path, ..] => {
+      match character_index.parse::<usize>() {
+        Ok(index) => Some((
+          ghost_name.to_string(),
+          character_name.to_string(),
+          index,
+          ghost_path.to_string(),
+        )),
+        Err(e) => {
+          error!("Failed to parse character_index: {}", e);
+          None
+        }
+      }
+    }
+    _ => {
+      error!("Missing parameters for fallback voices: {:?}", refs);
+      None
+    }
+  }
+}
+
+/// キャラクターの予備の声質を書き換え、ワーカーに送る
+fn update_fallback_voices(
+  ghost_name: &str,
+  character_index: usize,
+  f: impl FnOnce(&mut Vec<CharacterVoice>),
+) {
+  if let Some(mut info) = ghost_voices(ghost_name) {
+    if info.fallback_voices.len() <= character_index {
+      info.fallback_voices.resize(character_index + 1, Vec::new());
+    }
+    f(&mut info.fallback_voices[character_index]);
+    set_ghost_voices(ghost_name, &info);
+  } else {
+    error!("Ghost {} not found", ghost_name);
+  }
+}
+
+pub(crate) fn on_fallback_voices_menu(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  match fallback_target(&refs) {
+    Some((ghost_name, character_name, character_index, ghost_path)) => new_response_with_script(
+      fallback_voices_page(&ghost_name, &character_name, character_index, &ghost_path),
+      true,
+    ),
+    None => new_response_with_script(String::new(), false),
+  }
+}
+
+/// 予備の声質の並べ替え・削除
+pub(crate) fn on_fallback_voice_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 2 {
+    error!("Missing parameters for OnFallbackVoiceChanged");
+    return new_response_with_script(String::new(), false);
+  }
+  let position = match refs[1].parse::<usize>() {
+    Ok(p) => p,
+    Err(e) => {
+      error!("Failed to parse fallback position: {}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  let (ghost_name, character_name, character_index, ghost_path) = match fallback_target(&refs[2..])
+  {
+    Some(t) => t,
+    None => return new_response_with_script(String::new(), false),
+  };
+  let op = refs[0];
+  update_fallback_voices(&ghost_name, character_index, |voices| {
+    if position >= voices.len() {
+      return;
+    }
+    match op {
+      "up" if position > 0 => voices.swap(position - 1, position),
+      "remove" => {
+        voices.remove(position);
+      }
+      _ => {}
+    }
+  });
+  new_response_with_script(
+    fallback_voices_page(&ghost_name, &character_name, character_index, &ghost_path),
+    true,
+  )
+}
+
+pub(crate) fn on_fallback_voice_adding(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  match fallback_target(&refs) {
+    Some((ghost_name, character_name, character_index, ghost_path)) => {
+      reset_voice_list_view();
+      new_response_with_script(
+        fallback_voice_selecting_page(&ghost_name, &character_name, character_index, &ghost_path),
+        true,
+      )
+    }
+    None => new_response_with_script(String::new(), false),
+  }
+}
+
+fn fallback_voice_selecting_page(
+  ghost_name: &str,
+  character_name: &str,
+  character_index: usize,
+  ghost_path: &str,
+) -> String {
+  let target = VoiceListTarget::Fallback {
+    ghost_name: ghost_name.to_string(),
+    character_name: character_name.to_string(),
+    character_index,
+    ghost_path: ghost_path.to_string(),
+  };
+  let target_refs = target.to_refs();
+  let preview_target = target_refs.clone();
+  let list_callback = Box::new(
+    move |engine: &Engine, speaker: &SpeakerInfo, style: &Style| {
+      format!(
+        "\\![*]\\q[{} | {},OnFallbackVoiceSelected,{},{},{},{}]{}\\n",
+        speaker.speaker_name,
+        style_name(style),
+        engine.port(),
+        speaker.speaker_uuid,
+        style.style_id.unwrap_or(-1),
+        target_refs,
+        preview_link(engine, speaker, style, &preview_target),
+      )
+    },
+  );
+  // 予備の声質に「無し」は選べない
+  let dummy_callback = Box::new(|_: String, _: &CharacterVoice| String::new());
+  let mut m = format!(
+    "\\C\\c\\b[2]\\_q{}\\n{} の予備の声質を追加\\n\\n",
+    ghost_name, character_name
+  );
+  m.push_str(list_available_voices((list_callback, dummy_callback), &target).as_str());
+  m.push_str("\\n\\q[×,]");
+  m
+}
+
+pub(crate) fn on_fallback_voice_selected(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 4 {
+    error!("Missing parameters for OnFallbackVoiceSelected");
+    return new_response_with_script(String::new(), false);
+  }
+  let (port, style_id) = match (refs[0].parse::<i32>(), refs[2].parse::<i32>()) {
+    (Ok(port), Ok(style_id)) => (port, style_id),
+    _ => {
+      error!(
+        "Failed to parse voice in OnFallbackVoiceSelected: {:?}",
+        refs
+      );
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  let target = match VoiceListTarget::from_refs(&refs[3..]) {
+    Ok(t) => t,
+    Err(e) => {
+      error!("{}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  let (ghost_name, character_name, character_index, ghost_path) = match &target {
+    VoiceListTarget::Fallback {
+      ghost_name,
+      character_name,
+      character_index,
+      ghost_path,
+    } => (ghost_name, character_name, *character_index, ghost_path),
+    _ => {
+      error!("Invalid target for OnFallbackVoiceSelected: {:?}", refs);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  // 音声調整は割り当てた声質に合わせる
+  let voice = CharacterVoice {
+    port,
+    speaker_uuid: refs[1].to_string(),
+    style_id,
+    voice_quality: target.voice_quality(),
+  };
+
+  remember_recent_voice(&voice);
+  update_fallback_voices(ghost_name, character_index, |voices| {
+    let exists = voices.iter().any(|v| {
+      v.port == voice.port && v.speaker_uuid == voice.speaker_uuid && v.style_id == voice.style_id
+    });
+    if !exists {
+      voices.push(voice);
+    }
+  });
+  // \q の選択肢を通るとパスのエスケープが外れるので、エスケープし直す
+  new_response_with_script(
+    fallback_voices_page(
+      ghost_name,
+      character_name,
+      character_index,
+      &ghost_path.replace('\\', "\\\\"),
+    ),
+    true,
+  )
+}
+
+pub(crate) fn on_fallback_notice_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let ghost_name = refs[0].to_string();
+  let path_for_arg = refs[1].to_string();
+  let new_value = match SETTINGS.read() {
+    Ok(s) => !s.fallback_notice,
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  set_setting(&["fallback_notice"], &new_value);
+
+  let script = format!(
+    "\\![raiseplugin,{},OnMenuExec,dummy,{},dummy,dummy,{}]",
+    PLUGIN_UUID, ghost_name, path_for_arg
+  );
+  new_response_with_script(script, false)
+}
+
 pub(crate) fn on_volume_change(req: &PluginRequest) -> PluginResponse {
   let refs = get_references(req);
   let volume: f32 = match refs.first() {
@@ -739,18 +1714,14 @@ pub(crate) fn on_volume_change(req: &PluginRequest) -> PluginResponse {
       return new_response_with_script(String::new(), false);
     }
   };
-  let new_volume = match VOLUME.write() {
-    Ok(mut v) => {
-      *v += volume;
-      *v
-    }
+  let new_volume = match SETTINGS.read() {
+    Ok(s) => s.volume + volume,
     Err(e) => {
-      error!("Failed to write VOLUME: {}", e);
+      error!("Failed to read SETTINGS: {}", e);
       1.0
     }
   };
-  // ワーカーに音量変更を通知
-  send_command_logged(&Command::UpdateVolume { volume: new_volume });
+  set_setting(&["volume"], &new_volume);
   let script = format!(
     "\\![raiseplugin,{},OnMenuExec,dummy,{},dummy,dummy,{}]",
     PLUGIN_UUID, refs[1], refs[2]
@@ -761,23 +1732,23 @@ pub(crate) fn on_volume_change(req: &PluginRequest) -> PluginResponse {
 fn list_callback_for_defaultvoices(
   ghost_name: String,
   ghost_path: String,
+  target: &VoiceListTarget,
 ) -> (ListCallback, DummyCallback) {
   let gn = ghost_name.clone();
   let gp = ghost_path.clone();
+  let target = target.to_refs();
   let list_callback = Box::new(
     move |engine: &Engine, speaker: &SpeakerInfo, style: &Style| {
       format!(
-        "\\![*]\\q[{} | {},OnDefaultVoiceSelected,{},{},{},{},{}]\\n",
+        "\\![*]\\q[{} | {},OnDefaultVoiceSelected,{},{},{},{},{}]{}\\n",
         speaker.speaker_name,
-        style
-          .style_name
-          .as_ref()
-          .unwrap_or(&"不明なスタイル".to_string()),
+        style_name(style),
         engine.port(),
         speaker.speaker_uuid,
         style.style_id.unwrap_or(-1),
         ghost_name,
         ghost_path,
+        preview_link(engine, speaker, style, &target),
       )
     },
   );
@@ -806,11 +1777,61 @@ pub(crate) fn on_default_voice_selecting(req: &PluginRequest) -> PluginResponse
       return new_response_with_script(String::new(), false);
     }
   };
-  let callback = list_callback_for_defaultvoices(ghost_name.to_string(), ghost_path.to_string());
+  reset_voice_list_view();
+  new_response_with_script(default_voice_selecting_page(ghost_name, ghost_path), true)
+}
+
+fn default_voice_selecting_page(ghost_name: &str, ghost_path: &str) -> String {
+  let target = VoiceListTarget::Default {
+    ghost_name: ghost_name.to_string(),
+    ghost_path: ghost_path.to_string(),
+  };
+  let callback =
+    list_callback_for_defaultvoices(ghost_name.to_string(), ghost_path.to_string(), &target);
   let mut m = "\\_qデフォルトボイスの設定\\n\\n".to_string();
-  m.push_str(list_available_voices(callback).as_str());
+  m.push_str(list_available_voices(callback, &target).as_str());
   m.push_str("\\n\\q[×,]");
-  new_response_with_script(m.to_string(), true)
+  m
+}
+
+/// 試聴: 割り当てを変えずに、キャラクター（またはデフォルト声質）の現在の音声調整で読み上げ、一覧を表示し直す
+pub(crate) fn on_voice_preview(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 6 {
+    error!("Missing parameters for OnVoicePreview");
+    return new_response_with_script(String::new(), false);
+  }
+  let (port, style_id) = match (refs[0].parse::<i32>(), refs[2].parse::<i32>()) {
+    (Ok(port), Ok(style_id)) => (port, style_id),
+    _ => {
+      error!("Failed to parse voice in OnVoicePreview: {:?}", refs);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  let target = match VoiceListTarget::from_refs(&refs[3..]) {
+    Ok(t) => t,
+    Err(e) => {
+      error!("{}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  let text = match SETTINGS.read() {
+    Ok(s) => s.preview_text.clone(),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      DEFAULT_PREVIEW_TEXT.to_string()
+    }
+  };
+  send_command_logged(&Command::PreviewVoice {
+    voice: CharacterVoice {
+      port,
+      speaker_uuid: refs[1].to_string(),
+      style_id,
+      voice_quality: target.voice_quality(),
+    },
+    text,
+  });
+  new_response_with_script(target.page(), true)
 }
 
 pub(crate) fn on_default_voice_selected(req: &PluginRequest) -> PluginResponse {
@@ -874,12 +1895,8 @@ pub(crate) fn on_default_voice_selected(req: &PluginRequest) -> PluginResponse {
     voice_quality: VoiceQuality::default(),
   };
 
-  match INITIAL_VOICE.write() {
-    Ok(mut iv) => *iv = voice.clone(),
-    Err(e) => error!("Failed to write INITIAL_VOICE: {}", e),
-  }
-  // ワーカーにデフォルト声質変更を通知
-  send_command_logged(&Command::UpdateInitialVoice { voice });
+  remember_recent_voice(&voice);
+  set_setting(&["initial_voice"], &voice);
   let script = format!(
     "\\![raiseplugin,{},OnMenuExec,dummy,{},dummy,dummy,{}]",
     PLUGIN_UUID, ghost_name, path_for_arg
@@ -891,22 +1908,9 @@ pub(crate) fn on_division_setting_changed(req: &PluginRequest) -> PluginResponse
   let refs = get_references(req);
   let ghost_name = refs[0].to_string();
   let path_for_arg = refs[1].to_string();
-  let mut ghosts_voices = match GHOSTS_VOICES.write() {
-    Ok(gv) => gv,
-    Err(e) => {
-      error!("Failed to write GHOSTS_VOICES: {}", e);
-      return new_response_with_script(String::new(), false);
-    }
-  };
-  if let Some(info) = ghosts_voices.get_mut(&ghost_name) {
+  if let Some(mut info) = ghost_voices(&ghost_name) {
     info.devide_by_lines = !info.devide_by_lines;
-    // ワーカーに更新を通知
-    let info_clone = info.clone();
-    drop(ghosts_voices);
-    send_command_logged(&Command::UpdateGhostVoices {
-      ghost_name: ghost_name.clone(),
-      info: info_clone,
-    });
+    set_ghost_voices(&ghost_name, &info);
   }
 
   let script = format!(
@@ -920,22 +1924,9 @@ pub(crate) fn on_sync_balloon_setting_changed(req: &PluginRequest) -> PluginResp
   let refs = get_references(req);
   let ghost_name = refs[0].to_string();
   let path_for_arg = refs[1].to_string();
-  let mut ghosts_voices = match GHOSTS_VOICES.write() {
-    Ok(gv) => gv,
-    Err(e) => {
-      error!("Failed to write GHOSTS_VOICES: {}", e);
-      return new_response_with_script(String::new(), false);
-    }
-  };
-  if let Some(info) = ghosts_voices.get_mut(&ghost_name) {
+  if let Some(mut info) = ghost_voices(&ghost_name) {
     info.sync_speech_to_balloon = !info.sync_speech_to_balloon;
-    // ワーカーに更新を通知
-    let info_clone = info.clone();
-    drop(ghosts_voices);
-    send_command_logged(&Command::UpdateGhostVoices {
-      ghost_name: ghost_name.clone(),
-      info: info_clone,
-    });
+    set_ghost_voices(&ghost_name, &info);
   }
 
   let script = format!(
@@ -945,22 +1936,127 @@ pub(crate) fn on_sync_balloon_setting_changed(req: &PluginRequest) -> PluginResp
   new_response_with_script(script, false)
 }
 
-pub(crate) fn on_punctuation_setting_changed(req: &PluginRequest) -> PluginResponse {
+pub(crate) fn on_choice_reading_changed(req: &PluginRequest) -> PluginResponse {
   let refs = get_references(req);
   let ghost_name = refs[0].to_string();
   let path_for_arg = refs[1].to_string();
-  let new_value = match SPEAK_BY_PUNCTUATION.write() {
-    Ok(mut s) => {
-      *s = !*s;
-      *s
-    }
-    Err(e) => {
-      error!("Failed to write SPEAK_BY_PUNCTUATION: {}", e);
+  if let Some(mut info) = ghost_voices(&ghost_name) {
+    info.choice_reading = info.choice_reading.next();
+    set_ghost_voices(&ghost_name, &info);
+  }
+
+  let script = format!(
+    "\\![raiseplugin,{},OnMenuExec,dummy,{},dummy,dummy,{}]",
+    PLUGIN_UUID, ghost_name, path_for_arg
+  );
+  new_response_with_script(script, false)
+}
+
+pub(crate) fn on_normalize_text_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let ghost_name = refs[0].to_string();
+  let path_for_arg = refs[1].to_string();
+  if let Some(mut info) = ghost_voices(&ghost_name) {
+    info.normalize_text = !info.normalize_text;
+    set_ghost_voices(&ghost_name, &info);
+  }
+
+  let script = format!(
+    "\\![raiseplugin,{},OnMenuExec,dummy,{},dummy,dummy,{}]",
+    PLUGIN_UUID, ghost_name, path_for_arg
+  );
+  new_response_with_script(script, false)
+}
+
+pub(crate) fn on_english_reading_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let ghost_name = refs[0].to_string();
+  let path_for_arg = refs[1].to_string();
+  if let Some(mut info) = ghost_voices(&ghost_name) {
+    info.english_to_katakana = !info.english_to_katakana;
+    set_ghost_voices(&ghost_name, &info);
+  }
+
+  let script = format!(
+    "\\![raiseplugin,{},OnMenuExec,dummy,{},dummy,dummy,{}]",
+    PLUGIN_UUID, ghost_name, path_for_arg
+  );
+  new_response_with_script(script, false)
+}
+
+pub(crate) fn on_punctuation_setting_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let ghost_name = refs[0].to_string();
+  let path_for_arg = refs[1].to_string();
+  let new_value = match SETTINGS.read() {
+    Ok(s) => !s.speak_by_punctuation,
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  set_setting(&["speak_by_punctuation"], &new_value);
+
+  let script = format!(
+    "\\![raiseplugin,{},OnMenuExec,dummy,{},dummy,dummy,{}]",
+    PLUGIN_UUID, ghost_name, path_for_arg
+  );
+  new_response_with_script(script, false)
+}
+
+pub(crate) fn on_record_setting_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let ghost_name = refs[0].to_string();
+  let path_for_arg = refs[1].to_string();
+  set_recording(&ghost_name, None);
+
+  let script = format!(
+    "\\![raiseplugin,{},OnMenuExec,dummy,{},dummy,dummy,{}]",
+    PLUGIN_UUID, ghost_name, path_for_arg
+  );
+  new_response_with_script(script, false)
+}
+
+/// ゴーストの録音モードを切り替える。enabled が None なら反転する
+pub(crate) fn set_recording(ghost_name: &str, enabled: Option<bool>) {
+  if let Some(mut info) = ghost_voices(ghost_name) {
+    info.record = enabled.unwrap_or(!info.record);
+    set_ghost_voices(ghost_name, &info);
+  }
+}
+
+pub(crate) fn on_record_session_setting_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let ghost_name = refs[0].to_string();
+  let path_for_arg = refs[1].to_string();
+  let new_value = match SETTINGS.read() {
+    Ok(s) => !s.record_session,
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  set_setting(&["record_session"], &new_value);
+
+  let script = format!(
+    "\\![raiseplugin,{},OnMenuExec,dummy,{},dummy,dummy,{}]",
+    PLUGIN_UUID, ghost_name, path_for_arg
+  );
+  new_response_with_script(script, false)
+}
+
+pub(crate) fn on_subtitle_format_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let ghost_name = refs[0].to_string();
+  let path_for_arg = refs[1].to_string();
+  let new_value = match SETTINGS.read() {
+    Ok(s) => s.subtitle_format.next(),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
       return new_response_with_script(String::new(), false);
     }
   };
-  // ワーカーに句読点設定変更を通知
-  send_command_logged(&Command::UpdateSpeakByPunctuation { enabled: new_value });
+  set_setting(&["subtitle_format"], &new_value);
 
   let script = format!(
     "\\![raiseplugin,{},OnMenuExec,dummy,{},dummy,dummy,{}]",
@@ -988,25 +2084,18 @@ pub(crate) fn on_auto_start_toggled(req: &PluginRequest) -> PluginResponse {
       return new_response_with_script(String::new(), false);
     }
   };
-  let new_value = match ENGINE_AUTO_START.write() {
-    Ok(mut auto_start_guard) => {
-      if let Some(auto_start) = auto_start_guard.get_mut(&engine) {
-        *auto_start = !*auto_start;
-        *auto_start
-      } else {
-        return new_response_with_script(String::new(), false);
-      }
-    }
+  let mut engine_auto_start = match SETTINGS.read() {
+    Ok(s) => s.engine_auto_start.clone(),
     Err(e) => {
-      error!("Failed to write ENGINE_AUTO_START: {}", e);
+      error!("Failed to read SETTINGS: {}", e);
       return new_response_with_script(String::new(), false);
     }
   };
-  // ワーカーに自動起動設定変更を通知
-  send_command_logged(&Command::UpdateEngineAutoStart {
-    engine,
-    auto_start: new_value,
-  });
+  match engine_auto_start.get_mut(&engine) {
+    Some(auto_start) => *auto_start = !*auto_start,
+    None => return new_response_with_script(String::new(), false),
+  }
+  set_setting(&["engine_auto_start"], &engine_auto_start);
 
   let script = format!(
     "\\![raiseplugin,{},OnEngineStatusMenu,{},{}]",
@@ -1027,19 +2116,14 @@ pub(crate) fn on_character_resized(req: &PluginRequest) -> PluginResponse {
     }
   };
   let description_characters = count_characters(load_descript(ghost_path.clone()));
-  let characters = match GHOSTS_VOICES.write() {
-    Ok(gv) => match gv.get(&ghost_name) {
-      Some(info) => info.voices.clone(),
-      None => {
-        error!("Ghost {} not found", ghost_name);
-        return new_response_with_script(String::new(), false);
-      }
-    },
-    Err(e) => {
-      error!("Failed to read GHOSTS_VOICES: {}", e);
+  let mut info = match ghost_voices(&ghost_name) {
+    Some(info) => info,
+    None => {
+      error!("Ghost {} not found", ghost_name);
       return new_response_with_script(String::new(), false);
     }
   };
+  let characters = info.voices.clone();
   let mut new_characters = Vec::new();
   match CharacterResizeMode::from_usize(mode) {
     Ok(CharacterResizeMode::Expand) => {
@@ -1064,27 +2148,9 @@ pub(crate) fn on_character_resized(req: &PluginRequest) -> PluginResponse {
       return new_response_with_script(String::new(), false);
     }
   }
-  match GHOSTS_VOICES.write() {
-    Ok(mut gv) => match gv.get_mut(&ghost_name) {
-      Some(info) => {
-        info.voices = new_characters;
-        // ワーカーに更新を通知
-        let info_clone = info.clone();
-        drop(gv);
-        send_command_logged(&Command::UpdateGhostVoices {
-          ghost_name: ghost_name.clone(),
-          info: info_clone,
-        });
-      }
-      None => {
-        error!("Ghost {} not found for character resize", ghost_name);
-        return new_response_with_script(String::new(), false);
-      }
-    },
-    Err(e) => {
-      error!("Failed to write GHOSTS_VOICES for character resize: {}", e);
-      return new_response_with_script(String::new(), false);
-    }
+  info.voices = new_characters;
+  if !set_ghost_voices(&ghost_name, &info) {
+    return new_response_with_script(String::new(), false);
   }
 
   let script = format!(
@@ -1119,26 +2185,22 @@ pub(crate) fn on_voice_quality_menu(req: &PluginRequest) -> PluginResponse {
   };
   let character_name = refs.get(3).map(|s| s.to_string()).unwrap_or_default();
 
-  let ghosts_voices = match GHOSTS_VOICES.read() {
-    Ok(gv) => gv,
-    Err(e) => {
-      error!("Failed to read GHOSTS_VOICES: {}", e);
-      return new_response_with_script(String::new(), false);
-    }
-  };
-  let vq = match ghosts_voices
-    .get(&ghost_name)
-    .and_then(|info| info.voices.get(character_index))
-    .and_then(|v| v.as_ref())
-  {
-    Some(cv) => cv.voice_quality.clone(),
+  let shell = current_shell(&ghost_name);
+  let cv = match ghost_voices(&ghost_name).and_then(|info| {
+    info
+      .voices_for_shell(shell.as_deref())
+      .get(character_index)
+      .cloned()
+      .flatten()
+  }) {
+    Some(cv) => cv,
     None => {
       error!("Voice not found for quality menu");
       return new_response_with_script(String::new(), false);
     }
   };
-  let voice_name = get_voice_from_ghost(&ghost_name, character_index, &ghosts_voices);
-  drop(ghosts_voices);
+  let voice_name = get_voice(&Some(cv.clone()));
+  let vq = cv.voice_quality;
 
   // ghost_pathはrefsから受け取った値をそのまま使う（再エスケープしない）
   // 既にon_menu_exec時点でエスケープ済みの状態で\\__q/raisepluginを通じて渡されている
@@ -1255,15 +2317,13 @@ pub(crate) fn on_voice_quality_change(req: &PluginRequest) -> PluginResponse {
   };
   let character_name = refs.get(5).map(|s| s.to_string()).unwrap_or_default();
 
-  let mut ghosts_voices = match GHOSTS_VOICES.write() {
-    Ok(gv) => gv,
-    Err(e) => {
-      error!("Failed to write GHOSTS_VOICES: {}", e);
-      return new_response_with_script(String::new(), false);
-    }
-  };
-  if let Some(info) = ghosts_voices.get_mut(&ghost_name) {
-    if let Some(Some(cv)) = info.voices.get_mut(character_index) {
+  let shell = current_shell(&ghost_name);
+  if let Some(mut info) = ghost_voices(&ghost_name) {
+    let in_profile = info.has_shell_profile(shell.as_deref());
+    if let Some(Some(cv)) = info
+      .editable_voices(shell.as_deref())
+      .get_mut(character_index)
+    {
       let vq = &mut cv.voice_quality;
       match param_name.as_str() {
         "speed_scale" => vq.speed_scale = (vq.speed_scale + delta).clamp(0.50, 2.00),
@@ -1273,15 +2333,11 @@ pub(crate) fn on_voice_quality_change(req: &PluginRequest) -> PluginResponse {
           error!("Unknown voice quality parameter: {}", param_name);
         }
       }
+      if !in_profile {
+        info.set_voice_source(character_index, VoiceSource::User);
+      }
     }
-    let info_clone = info.clone();
-    drop(ghosts_voices);
-    send_command_logged(&Command::UpdateGhostVoices {
-      ghost_name: ghost_name.clone(),
-      info: info_clone,
-    });
-  } else {
-    drop(ghosts_voices);
+    set_ghost_voices(&ghost_name, &info);
   }
 
   // refsから受け取ったパスをそのまま渡す（再エスケープしない）
@@ -1317,31 +2373,971 @@ pub(crate) fn on_voice_quality_reset(req: &PluginRequest) -> PluginResponse {
   };
   let character_name = refs.get(3).map(|s| s.to_string()).unwrap_or_default();
 
-  let mut ghosts_voices = match GHOSTS_VOICES.write() {
-    Ok(gv) => gv,
+  let shell = current_shell(&ghost_name);
+  if let Some(mut info) = ghost_voices(&ghost_name) {
+    let in_profile = info.has_shell_profile(shell.as_deref());
+    if let Some(Some(cv)) = info
+      .editable_voices(shell.as_deref())
+      .get_mut(character_index)
+    {
+      cv.voice_quality = VoiceQuality::default();
+      if !in_profile {
+        info.set_voice_source(character_index, VoiceSource::User);
+      }
+    }
+    set_ghost_voices(&ghost_name, &info);
+  }
+
+  // refsから受け取ったパスをそのまま渡す（再エスケープしない）
+  let script = format!(
+    "\\![raiseplugin,{},OnVoiceQualityMenu,{},{},{},{}]",
+    PLUGIN_UUID, ghost_name, character_index, ghost_path, character_name,
+  );
+  new_response_with_script(script, false)
+}
+
+fn speak_rules_rows(
+  rules: &[SpeakRule],
+  ghost_name: &str,
+  path_for_arg: &str,
+  target: SpeakRuleTarget,
+) -> String {
+  let mut m = String::new();
+  if rules.is_empty() {
+    m.push_str(&format!("    {}\\n", grayed("(なし)")));
+  }
+  for (i, rule) in rules.iter().enumerate() {
+    let link = |op: &str, label: &str| {
+      format!(
+        "\\__q[OnSpeakRuleChanged,{},{},{},{},{}]{}\\__q",
+        ghost_name,
+        path_for_arg,
+        target.as_str(),
+        i,
+        op,
+        label,
+      )
+    };
+    let action = match rule.action {
+      SpeakAction::Mute => reded(rule.action.name()),
+      _ => greened(rule.action.name()),
+    };
+    m.push_str(&format!(
+      "    {}\\_l[@0,]\\f[align,right]{} {} {}\\n",
+      rule.event,
+      link("cycle", &decorated(&action, "bold")),
+      if i > 0 {
+        link("up", "↑")
+      } else {
+        grayed("↑")
+      },
+      link("delete", "×"),
+    ));
+  }
+  m.push_str(&format!(
+    "    \\__q[OnSpeakRuleAdding,{},{},{}]{}\\__q\\n",
+    ghost_name,
+    path_for_arg,
+    target.as_str(),
+    decorated("追加", "bold"),
+  ));
+  m
+}
+
+pub(crate) fn on_speak_rules_menu(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let ghost_name = match refs.first() {
+    Some(name) => name.to_string(),
+    None => {
+      error!("Missing ghost_name parameter");
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  let path_for_arg = match refs.get(1) {
+    Some(path) => path.to_string(),
+    None => {
+      error!("Missing ghost_path parameter");
+      return new_response_with_script(String::new(), false);
+    }
+  };
+
+  let (global_rules, ghost_rules) = match SETTINGS.read() {
+    Ok(s) => (
+      s.speak_rules.clone(),
+      s.ghosts_voices
+        .get(&ghost_name)
+        .map(|info| info.speak_rules.clone())
+        .unwrap_or_default(),
+    ),
     Err(e) => {
-      error!("Failed to write GHOSTS_VOICES: {}", e);
+      error!("Failed to read SETTINGS: {}", e);
       return new_response_with_script(String::new(), false);
     }
   };
-  if let Some(info) = ghosts_voices.get_mut(&ghost_name) {
-    if let Some(Some(cv)) = info.voices.get_mut(character_index) {
-      cv.voice_quality = VoiceQuality::default();
+
+  let m = format!(
+    "\
+    \\b[2]\\_q\
+    \\f[align,center]イベント別の読み上げルール\\f[align,left]\\n\\n\
+    ■ {}\\n\
+    {}\
+    \\n\
+    ■ 共通\\n\
+    {}\
+    \\n\
+    {}\\n\
+    \\n\
+    \\__q[OnMenuExec,dummy,{},dummy,dummy,{}]{}\\__q\\n\
+    ",
+    ghost_name,
+    speak_rules_rows(
+      &ghost_rules,
+      &ghost_name,
+      &path_for_arg,
+      SpeakRuleTarget::Ghost
+    ),
+    speak_rules_rows(
+      &global_rules,
+      &ghost_name,
+      &path_for_arg,
+      SpeakRuleTarget::Global
+    ),
+    grayed("ゴースト別 → 共通 の順に上から評価されます。イベントIDには * と ? が使えます"),
+    ghost_name,
+    path_for_arg,
+    decorated("戻る", "bold"),
+  );
+
+  new_response_with_script(m, true)
+}
+
+/// 読み上げルールを変更し、ワーカーに送る
+fn modify_speak_rules<F>(ghost_name: &str, target: SpeakRuleTarget, f: F)
+where
+  F: FnOnce(&mut Vec<SpeakRule>),
+{
+  match target {
+    SpeakRuleTarget::Global => {
+      let mut rules = match SETTINGS.read() {
+        Ok(s) => s.speak_rules.clone(),
+        Err(e) => {
+          error!("Failed to read SETTINGS: {}", e);
+          return;
+        }
+      };
+      f(&mut rules);
+      set_setting(&["speak_rules"], &rules);
+    }
+    SpeakRuleTarget::Ghost => {
+      if let Some(mut info) = ghost_voices(ghost_name) {
+        f(&mut info.speak_rules);
+        set_ghost_voices(ghost_name, &info);
+      } else {
+        error!("Ghost {} not found", ghost_name);
+      }
+    }
+  }
+}
+
+pub(crate) fn on_speak_rule_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 5 {
+    error!("Missing parameters for OnSpeakRuleChanged");
+    return new_response_with_script(String::new(), false);
+  }
+  let ghost_name = refs[0].to_string();
+  let path_for_arg = refs[1].to_string();
+  let target = match SpeakRuleTarget::from_str(refs[2]) {
+    Ok(t) => t,
+    Err(e) => {
+      error!("{}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  let index: usize = match refs[3].parse() {
+    Ok(i) => i,
+    Err(e) => {
+      error!("Failed to parse rule index: {}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  let op = refs[4].to_string();
+
+  modify_speak_rules(&ghost_name, target, |rules| {
+    if index >= rules.len() {
+      error!("Rule index out of range: {}", index);
+      return;
+    }
+    match op.as_str() {
+      "cycle" => rules[index].action = rules[index].action.next(),
+      "up" => {
+        if index > 0 {
+          rules.swap(index - 1, index);
+        }
+      }
+      "delete" => {
+        rules.remove(index);
+      }
+      _ => error!("Unknown speak rule operation: {}", op),
+    }
+  });
+
+  let script = format!(
+    "\\![raiseplugin,{},OnSpeakRulesMenu,{},{}]",
+    PLUGIN_UUID, ghost_name, path_for_arg
+  );
+  new_response_with_script(script, false)
+}
+
+pub(crate) fn on_speak_rule_adding(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 3 {
+    error!("Missing parameters for OnSpeakRuleAdding");
+    return new_response_with_script(String::new(), false);
+  }
+  let target = match SpeakRuleTarget::from_str(refs[2]) {
+    Ok(t) => t,
+    Err(e) => {
+      error!("{}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  match PENDING_SPEAK_RULE.lock() {
+    Ok(mut p) => *p = Some((refs[0].to_string(), refs[1].to_string(), target)),
+    Err(e) => {
+      error!("Failed to lock PENDING_SPEAK_RULE: {}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  }
+
+  let m = format!(
+    "\\b[2]\\_qイベントIDを入力してください。\\n{}\\![open,inputbox,OnSpeakRuleInput,0]",
+    grayed("例: OnBoot, OnMouse*, OnSecondChange"),
+  );
+  new_response_with_script(m, false)
+}
+
+pub(crate) fn on_speak_rule_input(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let input = refs
+    .first()
+    .map(|s| s.trim().to_string())
+    .unwrap_or_default();
+  let pending = match PENDING_SPEAK_RULE.lock() {
+    Ok(mut p) => p.take(),
+    Err(e) => {
+      error!("Failed to lock PENDING_SPEAK_RULE: {}", e);
+      None
+    }
+  };
+  let (ghost_name, path_for_arg, target) = match pending {
+    Some(p) => p,
+    None => {
+      error!("No pending speak rule");
+      return new_response_nocontent();
     }
-    let info_clone = info.clone();
-    drop(ghosts_voices);
-    send_command_logged(&Command::UpdateGhostVoices {
-      ghost_name: ghost_name.clone(),
-      info: info_clone,
+  };
+
+  if !input.is_empty() {
+    modify_speak_rules(&ghost_name, target, |rules| {
+      rules.push(SpeakRule {
+        event: input,
+        action: SpeakAction::Mute,
+      });
     });
-  } else {
-    drop(ghosts_voices);
   }
 
-  // refsから受け取ったパスをそのまま渡す（再エスケープしない）
   let script = format!(
-    "\\![raiseplugin,{},OnVoiceQualityMenu,{},{},{},{}]",
-    PLUGIN_UUID, ghost_name, character_index, ghost_path, character_name,
+    "\\![raiseplugin,{},OnSpeakRulesMenu,{},{}]",
+    PLUGIN_UUID, ghost_name, path_for_arg
   );
   new_response_with_script(script, false)
 }
+
+/// 履歴の台詞をメニューに表示するときの最大文字数
+const HISTORY_TEXT_LIMIT: usize = 40;
+
+/// 読み上げ履歴のページ
+fn speech_history_page(ghost_name: &str, path_for_arg: &str) -> String {
+  let entries = match send_command_logged(&Command::GetSpeechHistory {
+    ghost_name: ghost_name.to_string(),
+  }) {
+    Some(Response::SpeechHistory { entries }) => entries,
+    _ => Vec::new(),
+  };
+  let character_names = match SETTINGS.read() {
+    Ok(s) => s
+      .ghosts_voices
+      .get(ghost_name)
+      .map(|info| info.character_names.clone())
+      .unwrap_or_default(),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      Vec::new()
+    }
+  };
+
+  let mut rows = String::new();
+  if entries.is_empty() {
+    rows.push_str(&format!("    {}\\n", grayed("(なし)")));
+  }
+  for entry in entries.iter() {
+    let replay = if entry.replayable {
+      format!(
+        "\\__q[OnSpeechHistoryReplay,{},{},{}]{}\\__q",
+        ghost_name,
+        path_for_arg,
+        entry.id,
+        decorated("再生", "bold"),
+      )
+    } else {
+      grayed("再生")
+    };
+    for (i, line) in entry.lines.iter().enumerate() {
+      let name = match character_names.get(line.scope) {
+        Some(name) if !name.is_empty() => name.clone(),
+        _ => format!("char{}", line.scope),
+      };
+      rows.push_str(&format!(
+        "    {}: {}\\_l[@0,]\\f[align,right]{}\\n",
+        name,
+        escaped(&truncated(&line.text, HISTORY_TEXT_LIMIT)),
+        // 再生のリンクはトークの先頭の台詞にだけ付ける
+        if i == 0 {
+          replay.clone()
+        } else {
+          String::new()
+        },
+      ));
+      rows.push_str(&format!(
+        "      {}\\n",
+        grayed(&get_voice(&Some(line.voice.clone())))
+      ));
+    }
+  }
+
+  format!(
+    "\
+    \\b[2]\\_q\
+    \\f[align,center]読み上げ履歴\\f[align,left]\\n\\n\
+    {}\
+    \\n\
+    {}\\n\
+    \\n\
+    \\__q[OnMenuExec,dummy,{},dummy,dummy,{}]{}\\__q\\n\
+    ",
+    rows,
+    grayed("新しい順。再生は合成済みの音声を使います(棒読みちゃんは不可)"),
+    ghost_name,
+    path_for_arg,
+    decorated("戻る", "bold"),
+  )
+}
+
+fn truncated(s: &str, limit: usize) -> String {
+  if s.chars().count() > limit {
+    format!("{}…", s.chars().take(limit).collect::<String>())
+  } else {
+    s.to_string()
+  }
+}
+
+/// さくらスクリプトとして解釈されないようにする
+fn escaped(s: &str) -> String {
+  s.replace('\\', "\\\\").replace('%', "\\%")
+}
+
+pub(crate) fn on_speech_history_menu(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 2 {
+    error!("Missing parameters for OnSpeechHistoryMenu");
+    return new_response_with_script(String::new(), false);
+  }
+  new_response_with_script(speech_history_page(refs[0], refs[1]), true)
+}
+
+/// 履歴のページから再生し、ページを表示し直す
+pub(crate) fn on_speech_history_replay(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 3 {
+    error!("Missing parameters for OnSpeechHistoryReplay");
+    return new_response_with_script(String::new(), false);
+  }
+  let id = match refs[2].parse::<u64>() {
+    Ok(id) => id,
+    Err(e) => {
+      error!("Failed to parse history id: {}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  replay_speech(refs[0], Some(id));
+  new_response_with_script(speech_history_page(refs[0], refs[1]), true)
+}
+
+/// 履歴のトークをワーカーにキャッシュされた音声で再生する。id が None なら直前のトーク
+pub(crate) fn replay_speech(ghost_name: &str, id: Option<u64>) {
+  if let Some(Response::Error { message }) = send_command_logged(&Command::ReplaySpeech {
+    ghost_name: ghost_name.to_string(),
+    id,
+  }) {
+    debug!("replay failed: {}", message);
+  }
+}
+
+/// シェル別の声質の設定。現在のシェル用の声質を作る・消す、ゴースト共通や他のシェルからコピーする
+fn shell_voices_page(ghost_name: &str, path_for_arg: &str) -> String {
+  let shell = current_shell(ghost_name);
+  let (mut profiles, uses_profile) = match ghost_voices(ghost_name) {
+    Some(info) => (
+      info.shell_voices.keys().cloned().collect::<Vec<_>>(),
+      info.has_shell_profile(shell.as_deref()),
+    ),
+    None => (Vec::new(), false),
+  };
+  profiles.sort();
+  let link = |op: &str, arg: &str, label: &str| {
+    format!(
+      "\\__q[OnShellVoicesChanged,{},{},{},{}]{}\\__q",
+      op,
+      arg,
+      ghost_name,
+      path_for_arg,
+      decorated(label, "bold"),
+    )
+  };
+
+  let mut m = "\\b[2]\\_q\\f[align,center]シェル別の声質\\f[align,left]\\n\\n".to_string();
+  match &shell {
+    Some(shell) => {
+      m.push_str(&format!("現在のシェル: {}\\n", escaped(shell)));
+      if uses_profile {
+        m.push_str(&format!(
+          "    {}\\n    【{}】\\n",
+          grayed("このシェル用の声質を使っています"),
+          link("remove", shell, "削除してゴースト共通に戻す"),
+        ));
+      } else {
+        m.push_str(&format!(
+          "    {}\\n    【{}】\\n",
+          grayed("ゴースト共通の声質を使っています"),
+          link("create", "-", "このシェル用の声質を作る"),
+        ));
+      }
+      m.push_str("\\n■ 現在のシェルにコピー\\n");
+      m.push_str(&format!(
+        "    ゴースト共通\\_l[@0,]\\f[align,right]{}\\n",
+        link("copy_common", "-", "コピー")
+      ));
+      for profile in profiles.iter().filter(|p| *p != shell) {
+        m.push_str(&format!(
+          "    {}\\_l[@0,]\\f[align,right]{} {}\\n",
+          escaped(profile),
+          link("copy", profile, "コピー"),
+          link("remove", profile, "削除"),
+        ));
+      }
+    }
+    None => m.push_str(&format!(
+      "{}\\n",
+      grayed("シェルが分かりません。ゴーストのメニューから開き直してください")
+    )),
+  }
+
+  m.push_str(&format!(
+    "\\n{}\\n\\n\\__q[OnMenuExec,dummy,{},dummy,dummy,{}]{}\\__q\\n",
+    grayed("シェル別の声質で未設定のキャラクターは、ゴースト共通の声質で読み上げます"),
+    ghost_name,
+    path_for_arg,
+    decorated("戻る", "bold"),
+  ));
+  m
+}
+
+pub(crate) fn on_shell_voices_menu(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 2 {
+    error!("Missing parameters for OnShellVoicesMenu");
+    return new_response_with_script(String::new(), false);
+  }
+  new_response_with_script(shell_voices_page(refs[0], refs[1]), true)
+}
+
+/// シェル別の声質を作る・消す・コピーする
+pub(crate) fn on_shell_voices_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 4 {
+    error!("Missing parameters for OnShellVoicesChanged");
+    return new_response_with_script(String::new(), false);
+  }
+  let (op, arg, ghost_name, path_for_arg) = (refs[0], refs[1], refs[2], refs[3]);
+  let shell = match current_shell(ghost_name) {
+    Some(s) => s,
+    None => {
+      error!("Current shell of {} is unknown", ghost_name);
+      return new_response_with_script(shell_voices_page(ghost_name, path_for_arg), true);
+    }
+  };
+
+  if let Some(mut info) = ghost_voices(ghost_name) {
+    match op {
+      // 空のシェル別の声質は、すべてのキャラクターがゴースト共通の声質を使う
+      "create" => {
+        let len = info.voices.len();
+        info
+          .shell_voices
+          .entry(shell)
+          .or_insert_with(|| vec![None; len]);
+      }
+      "remove" => {
+        info.shell_voices.remove(arg);
+      }
+      "copy_common" => {
+        let voices = info.voices.clone();
+        info.shell_voices.insert(shell, voices);
+      }
+      "copy" => match info.shell_voices.get(arg).cloned() {
+        Some(voices) => {
+          info.shell_voices.insert(shell, voices);
+        }
+        None => error!("Shell profile not found: {}", arg),
+      },
+      _ => error!("Unknown shell voices operation: {}", op),
+    }
+    set_ghost_voices(ghost_name, &info);
+  } else {
+    error!("Ghost {} not found", ghost_name);
+  }
+  new_response_with_script(shell_voices_page(ghost_name, path_for_arg), true)
+}
+
+/// 声質の設定の共有ファイルを置くディレクトリ（プラグインフォルダの profiles）
+fn profiles_dir() -> Option<std::path::PathBuf> {
+  match DLL_DIR.read() {
+    Ok(d) if !d.is_empty() => Some(std::path::Path::new(d.as_str()).join(PROFILES_DIR)),
+    Ok(_) => None,
+    Err(e) => {
+      error!("Failed to read DLL_DIR: {}", e);
+      None
+    }
+  }
+}
+
+/// 読み込める共有ファイルの一覧（名前順）
+fn list_profile_files() -> Vec<String> {
+  let dir = match profiles_dir() {
+    Some(d) => d,
+    None => return Vec::new(),
+  };
+  let mut files: Vec<String> = match std::fs::read_dir(dir) {
+    Ok(entries) => entries
+      .filter_map(|e| e.ok())
+      .map(|e| e.file_name().to_string_lossy().into_owned())
+      .filter(|name| {
+        let lower = name.to_lowercase();
+        lower.ends_with(".yaml") || lower.ends_with(".yml") || lower.ends_with(".json")
+      })
+      // , や ] を含む名前は \__q の引数に渡せないので一覧に出さない
+      .filter(|name| !name.contains([',', ']']))
+      .collect(),
+    Err(_) => Vec::new(),
+  };
+  files.sort();
+  files
+}
+
+/// ファイル名に使えない文字と、読み込みのリンクに渡せない文字を置き換える
+fn profile_file_stem(ghost_name: &str) -> String {
+  ghost_name
+    .chars()
+    .map(|c| match c {
+      '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | ',' | '[' | ']' => '_',
+      c => c,
+    })
+    .collect()
+}
+
+fn export_voice_profile(ghost_name: &str, json: bool) -> Result<String, String> {
+  // 話者名・スタイル名を書き出すため、エンジンの情報を新しくしておく
+  refresh_engine_status();
+  let info =
+    ghost_voices(ghost_name).ok_or_else(|| format!("{} の設定がありません", ghost_name))?;
+  let speakers_info = SPEAKERS_INFO
+    .read()
+    .map_err(|e| format!("Failed to read SPEAKERS_INFO: {}", e))?
+    .clone();
+  let profile = VoiceProfile::export(ghost_name, &info, &speakers_info);
+  let (content, ext) = if json {
+    (
+      serde_json::to_string_pretty(&profile).map_err(|e| e.to_string())?,
+      "json",
+    )
+  } else {
+    (
+      serde_yaml::to_string(&profile).map_err(|e| e.to_string())?,
+      "yaml",
+    )
+  };
+  let dir = profiles_dir().ok_or("プラグインフォルダが分かりません")?;
+  std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+  let file_name = format!("{}.{}", profile_file_stem(ghost_name), ext);
+  std::fs::write(dir.join(&file_name), content).map_err(|e| e.to_string())?;
+  Ok(file_name)
+}
+
+/// 共有ファイルを読み込み、見つからなかった声質の説明を返す
+fn import_voice_profile(ghost_name: &str, file_name: &str) -> Result<Vec<String>, String> {
+  // profiles の外のファイルは読まない
+  if file_name.contains(['/', '\\']) || file_name.contains("..") {
+    return Err(format!("不正なファイル名です: {}", file_name));
+  }
+  let dir = profiles_dir().ok_or("プラグインフォルダが分かりません")?;
+  let content = std::fs::read_to_string(dir.join(file_name)).map_err(|e| e.to_string())?;
+  let profile: VoiceProfile = if file_name.to_lowercase().ends_with(".json") {
+    serde_json::from_str(&content).map_err(|e| e.to_string())?
+  } else {
+    serde_yaml::from_str(&content).map_err(|e| e.to_string())?
+  };
+
+  refresh_engine_status();
+  let speakers_info = SPEAKERS_INFO
+    .read()
+    .map_err(|e| format!("Failed to read SPEAKERS_INFO: {}", e))?
+    .clone();
+  let mut info =
+    ghost_voices(ghost_name).ok_or_else(|| format!("{} の設定がありません", ghost_name))?;
+  let unmatched = profile.import_into(&mut info, &speakers_info);
+  if !set_ghost_voices(ghost_name, &info) {
+    return Err("設定を保存できませんでした".to_string());
+  }
+  Ok(unmatched)
+}
+
+/// 声質の設定の共有: 書き出しと読み込み。notice は直前の操作の結果
+fn voice_profile_page(ghost_name: &str, path_for_arg: &str, notice: &str) -> String {
+  let link = |op: &str, arg: &str, label: &str| {
+    format!(
+      "\\__q[OnVoiceProfileChanged,{},{},{},{}]{}\\__q",
+      op,
+      arg,
+      ghost_name,
+      path_for_arg,
+      decorated(label, "bold"),
+    )
+  };
+  let mut m = "\\b[2]\\_q\\f[align,center]声質の設定の共有\\f[align,left]\\n\\n".to_string();
+  if !notice.is_empty() {
+    m.push_str(&format!("{}\\n\\n", notice));
+  }
+  m.push_str(&format!(
+    "■ 書き出す (保存先: プラグインフォルダの {})\\n    【{}】【{}】\\n",
+    PROFILES_DIR,
+    link("export", "yaml", "YAML"),
+    link("export", "json", "JSON"),
+  ));
+  m.push_str(&format!(
+    "\\n■ 読み込む\\n    {}\\n",
+    grayed("このゴーストの声質の設定を上書きします")
+  ));
+  let files = list_profile_files();
+  if files.is_empty() {
+    m.push_str(&format!("    {}\\n", grayed("(なし)")));
+  }
+  for file in files.iter() {
+    m.push_str(&format!(
+      "    {}\\_l[@0,]\\f[align,right]{}\\n",
+      escaped(file),
+      link("import", file, "読み込む"),
+    ));
+  }
+  m.push_str(&format!(
+    "\\n\\__q[OnMenuExec,dummy,{},dummy,dummy,{}]{}\\__q\\n",
+    ghost_name,
+    path_for_arg,
+    decorated("戻る", "bold"),
+  ));
+  m
+}
+
+pub(crate) fn on_voice_profile_menu(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 2 {
+    error!("Missing parameters for OnVoiceProfileMenu");
+    return new_response_with_script(String::new(), false);
+  }
+  new_response_with_script(voice_profile_page(refs[0], refs[1], ""), true)
+}
+
+/// 声質の設定を書き出す・読み込む
+pub(crate) fn on_voice_profile_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 4 {
+    error!("Missing parameters for OnVoiceProfileChanged");
+    return new_response_with_script(String::new(), false);
+  }
+  let (op, arg, ghost_name, path_for_arg) = (refs[0], refs[1], refs[2], refs[3]);
+  let notice = match op {
+    "export" => match export_voice_profile(ghost_name, arg == "json") {
+      Ok(file_name) => format!("{} に書き出しました", escaped(&file_name)),
+      Err(e) => {
+        error!("Failed to export voice profile: {}", e);
+        reded(&format!("書き出せませんでした: {}", escaped(&e)))
+      }
+    },
+    "import" => match import_voice_profile(ghost_name, arg) {
+      Ok(unmatched) if unmatched.is_empty() => format!("{} を読み込みました", escaped(arg)),
+      Ok(unmatched) => format!(
+        "{} を読み込みました。次の声質は見つからなかったため未設定にしました\\n{}",
+        escaped(arg),
+        unmatched
+          .iter()
+          .map(|u| format!("    {}", reded(&escaped(u))))
+          .collect::<Vec<_>>()
+          .join("\\n"),
+      ),
+      Err(e) => {
+        error!("Failed to import voice profile: {}", e);
+        reded(&format!("読み込めませんでした: {}", escaped(&e)))
+      }
+    },
+    _ => {
+      error!("Unknown voice profile operation: {}", op);
+      String::new()
+    }
+  };
+  new_response_with_script(voice_profile_page(ghost_name, path_for_arg, &notice), true)
+}
+
+pub(crate) fn on_substitution_menu(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 2 {
+    error!("Missing parameters for OnSubstitutionMenu");
+    return new_response_with_script(String::new(), false);
+  }
+  let ghost_name = refs[0].to_string();
+  let path_for_arg = refs[1].to_string();
+
+  let rules = match SETTINGS.read() {
+    Ok(s) => s.substitution_rules.clone(),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+
+  let mut rows = String::new();
+  if rules.is_empty() {
+    rows.push_str(&format!("    {}\\n", grayed("(なし)")));
+  }
+  for (i, rule) in rules.iter().enumerate() {
+    let link = |op: &str, label: &str| {
+      format!(
+        "\\__q[OnSubstitutionChanged,{},{},{},{}]{}\\__q",
+        ghost_name, path_for_arg, i, op, label,
+      )
+    };
+    rows.push_str(&format!(
+      "    {} → {}\\_l[@0,]\\f[align,right]{} {}\\n",
+      rule.from,
+      rule.to,
+      if i > 0 {
+        link("up", "↑")
+      } else {
+        grayed("↑")
+      },
+      link("delete", "×"),
+    ));
+  }
+
+  let m = format!(
+    "\
+    \\b[2]\\_q\
+    \\f[align,center]読み替え辞書\\f[align,left]\\n\\n\
+    {}\
+        \\__q[OnSubstitutionAdding,{},{}]{}\\__q\\n\
+    \\n\
+    {}\\n\
+    \\n\
+    \\__q[OnMenuExec,dummy,{},dummy,dummy,{}]{}\\__q\\n\
+    ",
+    rows,
+    ghost_name,
+    path_for_arg,
+    decorated("追加", "bold"),
+    grayed("上から順に置き換えます。英単語のカタカナ読みでは大文字小文字を区別せず優先されます"),
+    ghost_name,
+    path_for_arg,
+    decorated("戻る", "bold"),
+  );
+
+  new_response_with_script(m, true)
+}
+
+/// 読み替え辞書を変更し、ワーカーに送る
+fn modify_substitution_rules<F>(f: F)
+where
+  F: FnOnce(&mut Vec<SubstitutionRule>),
+{
+  let mut rules = match SETTINGS.read() {
+    Ok(s) => s.substitution_rules.clone(),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      return;
+    }
+  };
+  f(&mut rules);
+  set_setting(&["substitution_rules"], &rules);
+}
+
+pub(crate) fn on_substitution_changed(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 4 {
+    error!("Missing parameters for OnSubstitutionChanged");
+    return new_response_with_script(String::new(), false);
+  }
+  let ghost_name = refs[0].to_string();
+  let path_for_arg = refs[1].to_string();
+  let index: usize = match refs[2].parse() {
+    Ok(i) => i,
+    Err(e) => {
+      error!("Failed to parse substitution index: {}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  };
+  let op = refs[3].to_string();
+
+  modify_substitution_rules(|rules| {
+    if index >= rules.len() {
+      error!("Substitution index out of range: {}", index);
+      return;
+    }
+    match op.as_str() {
+      "up" => {
+        if index > 0 {
+          rules.swap(index - 1, index);
+        }
+      }
+      "delete" => {
+        rules.remove(index);
+      }
+      _ => error!("Unknown substitution operation: {}", op),
+    }
+  });
+
+  let script = format!(
+    "\\![raiseplugin,{},OnSubstitutionMenu,{},{}]",
+    PLUGIN_UUID, ghost_name, path_for_arg
+  );
+  new_response_with_script(script, false)
+}
+
+pub(crate) fn on_substitution_adding(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if refs.len() < 2 {
+    error!("Missing parameters for OnSubstitutionAdding");
+    return new_response_with_script(String::new(), false);
+  }
+  match PENDING_SUBSTITUTION.lock() {
+    Ok(mut p) => *p = Some((refs[0].to_string(), refs[1].to_string())),
+    Err(e) => {
+      error!("Failed to lock PENDING_SUBSTITUTION: {}", e);
+      return new_response_with_script(String::new(), false);
+    }
+  }
+
+  let m = format!(
+    "\\b[2]\\_q「読み替え前,読み替え後」の形式で入力してください。\\n{}\\![open,inputbox,OnSubstitutionInput,0]",
+    grayed("例: Ukagaka,ウカガカ"),
+  );
+  new_response_with_script(m, false)
+}
+
+pub(crate) fn on_substitution_input(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let input = refs.first().map(|s| s.to_string()).unwrap_or_default();
+  let pending = match PENDING_SUBSTITUTION.lock() {
+    Ok(mut p) => p.take(),
+    Err(e) => {
+      error!("Failed to lock PENDING_SUBSTITUTION: {}", e);
+      None
+    }
+  };
+  let (ghost_name, path_for_arg) = match pending {
+    Some(p) => p,
+    None => {
+      error!("No pending substitution");
+      return new_response_nocontent();
+    }
+  };
+
+  match input.split_once([',', '，']) {
+    Some((from, to)) if !from.trim().is_empty() => {
+      let rule = SubstitutionRule {
+        from: from.trim().to_string(),
+        to: to.trim().to_string(),
+      };
+      modify_substitution_rules(|rules| rules.push(rule));
+    }
+    _ => {
+      if !input.trim().is_empty() {
+        error!("Invalid substitution input: {}", input);
+      }
+    }
+  }
+
+  let script = format!(
+    "\\![raiseplugin,{},OnSubstitutionMenu,{},{}]",
+    PLUGIN_UUID, ghost_name, path_for_arg
+  );
+  new_response_with_script(script, false)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn voice_profile_yaml_round_trip() {
+    let mut info = GhostVoiceInfo::new(2);
+    info.character_names = vec!["さくら".to_string(), "うにゅう".to_string()];
+    info.voices[0] = Some(CharacterVoice {
+      port: Engine::VoiceVox.port(),
+      speaker_uuid: "uuid-metan".to_string(),
+      style_id: 2,
+      voice_quality: VoiceQuality {
+        speed_scale: 1.2,
+        ..Default::default()
+      },
+    });
+    info.voices[1] = Some(CharacterVoice::no_voice());
+    let speakers_info = HashMap::from([(
+      Engine::VoiceVox,
+      vec![SpeakerInfo {
+        speaker_name: "四国めたん".to_string(),
+        speaker_uuid: "uuid-metan".to_string(),
+        styles: vec![Style {
+          style_name: Some("ノーマル".to_string()),
+          style_id: Some(2),
+        }],
+      }],
+    )]);
+
+    let profile = VoiceProfile::export("Ghost", &info, &speakers_info);
+    let yaml = serde_yaml::to_string(&profile).unwrap();
+    let profile: VoiceProfile = serde_yaml::from_str(&yaml).unwrap();
+    let mut imported = GhostVoiceInfo::new(2);
+    let unmatched = profile.import_into(&mut imported, &speakers_info);
+    assert!(unmatched.is_empty());
+    let v0 = imported.voices[0].as_ref().unwrap();
+    assert_eq!((v0.speaker_uuid.as_str(), v0.style_id), ("uuid-metan", 2));
+    assert_eq!(v0.voice_quality.speed_scale, 1.2);
+    assert_eq!(
+      imported.voices[1].as_ref().unwrap().speaker_uuid,
+      NO_VOICE_UUID
+    );
+  }
+
+  #[test]
+  fn profile_file_names_can_be_passed_to_links() {
+    assert_eq!(profile_file_stem("a,b[c]d"), "a_b_c_d");
+  }
+}
@@ -18,8 +18,15 @@ const SYNC_EXCLUDED_EVENTS: &[&str] = &[
 ];
 use crate::plugin::request::PluginRequest;
 use crate::plugin::response::PluginResponse;
+use crate::settings::{ghost_voices, set_ghost_voices};
 use crate::variables::*;
-use ghost_speaker_common::{Command, GhostVoiceInfo, Response, SyncState};
+use ghost_speaker_common::{
+  find_speak_action, Command, GhostVoiceInfo, Response, SpeakAction, SyncState,
+  DEFAULT_SYNC_POLL_INTERVAL,
+};
+
+/// 最初のセグメントを待っている間、OnSyncSpeechContinue の Reference1 に付ける印
+const SYNC_FIRST_SEGMENT: &str = "first";
 
 pub(crate) fn on_other_ghost_talk(req: &PluginRequest) -> PluginResponse {
   let refs = get_references(req);
@@ -28,19 +35,38 @@ pub(crate) fn on_other_ghost_talk(req: &PluginRequest) -> PluginResponse {
   let event_id = refs[3].to_string();
   let msg = refs[4].to_string();
 
+  // シェルの切り替えは OnShellChanged の Reference0 で知る。
+  // 元のイベントの Reference は Reference5 に \x01 区切りで入っている
+  if event_id == "OnShellChanged" {
+    if let Some(shell_name) = refs.get(5).and_then(|r| r.split('\u{1}').next()) {
+      set_current_shell(&ghost_name, shell_name);
+    }
+  }
+
   if msg.is_empty() || flags.contains("plugin-script") {
     return new_response_nocontent();
   }
 
-  let sync_enabled = match GHOSTS_VOICES.read() {
-    Ok(gv) => gv
-      .get(&ghost_name)
-      .map(|info| info.sync_speech_to_balloon)
-      .unwrap_or(false),
-    Err(e) => {
-      error!("Failed to read GHOSTS_VOICES: {}", e);
-      false
-    }
+  let action = resolve_speak_action(&ghost_name, &event_id);
+  if action == SpeakAction::Mute {
+    debug!("muted by speak rule: {}", event_id);
+    return new_response_nocontent();
+  }
+
+  let sync_enabled = match action {
+    SpeakAction::Sync => true,
+    SpeakAction::AsyncOnly => false,
+    _ => match SETTINGS.read() {
+      Ok(s) => s
+        .ghosts_voices
+        .get(&ghost_name)
+        .map(|info| info.sync_speech_to_balloon)
+        .unwrap_or(false),
+      Err(e) => {
+        error!("Failed to read SETTINGS: {}", e);
+        false
+      }
+    },
   };
 
   if !sync_enabled || SYNC_EXCLUDED_EVENTS.contains(&event_id.as_str()) {
@@ -70,14 +96,29 @@ pub(crate) fn on_other_ghost_talk(req: &PluginRequest) -> PluginResponse {
       let tag = scope_to_tag(seg.scope);
       let script = if has_more {
         format!(
-          "{}{}\\![raiseplugin,{},OnSyncSpeechContinue,{}]",
-          tag, seg.raw_text, PLUGIN_UUID, ghost_name,
+          "{}{}{}\\![raiseplugin,{},OnSyncSpeechContinue,{}]",
+          tag,
+          seg.raw_text,
+          wait_tag(seg.duration_ms),
+          PLUGIN_UUID,
+          ghost_name,
         )
       } else {
         format!("{}{}", tag, seg.raw_text)
       };
       new_response_with_script(script, false)
     }
+    Response::SyncStarted {
+      first_segment: None,
+      has_more: true,
+    } => {
+      // 最初のセグメントは合成が終わりしだい OnSyncSpeechContinue で受け取る
+      let script = format!(
+        "\\C\\![raiseplugin,{},OnSyncSpeechContinue,{},{}]",
+        PLUGIN_UUID, ghost_name, SYNC_FIRST_SEGMENT,
+      );
+      new_response_with_script(script, false)
+    }
     Response::SyncStarted {
       first_segment: None,
       ..
@@ -89,9 +130,32 @@ pub(crate) fn on_other_ghost_talk(req: &PluginRequest) -> PluginResponse {
   }
 }
 
+/// 読み上げルールを ゴースト別 → 共通 の順に評価する。どれにもマッチしなければ通常の読み上げ
+fn resolve_speak_action(ghost_name: &str, event_id: &str) -> SpeakAction {
+  match SETTINGS.read() {
+    Ok(s) => s
+      .ghosts_voices
+      .get(ghost_name)
+      .and_then(|info| find_speak_action(&info.speak_rules, event_id))
+      .or_else(|| find_speak_action(&s.speak_rules, event_id))
+      .unwrap_or(SpeakAction::Speak),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      SpeakAction::Speak
+    }
+  }
+}
+
 pub(crate) fn on_sync_speech_continue(req: &PluginRequest) -> PluginResponse {
   let refs = get_references(req);
   let ghost_name = refs[0].to_string();
+  let waiting_first = refs.get(1).is_some_and(|r| *r == SYNC_FIRST_SEGMENT);
+  // 最初のセグメントを待っている間は印を引き継ぐ
+  let marker = if waiting_first {
+    format!(",{}", SYNC_FIRST_SEGMENT)
+  } else {
+    String::new()
+  };
 
   let resp = match send_command_logged(&Command::SyncPoll) {
     Some(r) => r,
@@ -100,11 +164,31 @@ pub(crate) fn on_sync_speech_continue(req: &PluginRequest) -> PluginResponse {
 
   match resp {
     Response::SyncStatus { state } => match state {
-      SyncState::Playing | SyncState::Waiting => {
-        // まだ再生中 or 合成待ち → 200ms後にリトライ
+      SyncState::Playing { remaining_ms } => {
+        // まだ再生中 → 再生終了見込みまで待ってからリトライ
+        // (見込みを過ぎても再生中の場合はポーリング間隔で待つ)
+        let wait = if remaining_ms > 0 {
+          remaining_ms
+        } else {
+          sync_poll_interval()
+        };
+        let script = format!(
+          "\\C{}\\![raiseplugin,{},OnSyncSpeechContinue,{}{}]",
+          wait_tag(wait),
+          PLUGIN_UUID,
+          ghost_name,
+          marker,
+        );
+        new_response_with_script(script, false)
+      }
+      SyncState::Waiting => {
+        // 合成待ち → ポーリング間隔後にリトライ
         let script = format!(
-          "\\C\\_w[200]\\![raiseplugin,{},OnSyncSpeechContinue,{}]",
-          PLUGIN_UUID, ghost_name,
+          "\\C{}\\![raiseplugin,{},OnSyncSpeechContinue,{}{}]",
+          wait_tag(sync_poll_interval()),
+          PLUGIN_UUID,
+          ghost_name,
+          marker,
         );
         new_response_with_script(script, false)
       }
@@ -113,13 +197,20 @@ pub(crate) fn on_sync_speech_continue(req: &PluginRequest) -> PluginResponse {
         has_more,
       } => {
         let tag = scope_to_tag(seg.scope);
+        // 最初のセグメントはバルーンを新しく開く
+        let clear = if waiting_first { "" } else { "\\C" };
         let script = if has_more {
           format!(
-            "\\C{}{}\\![raiseplugin,{},OnSyncSpeechContinue,{}]",
-            tag, seg.raw_text, PLUGIN_UUID, ghost_name,
+            "{}{}{}{}\\![raiseplugin,{},OnSyncSpeechContinue,{}]",
+            clear,
+            tag,
+            seg.raw_text,
+            wait_tag(seg.duration_ms),
+            PLUGIN_UUID,
+            ghost_name,
           )
         } else {
-          format!("\\C{}{}", tag, seg.raw_text)
+          format!("{}{}{}", clear, tag, seg.raw_text)
         };
         new_response_with_script(script, false)
       }
@@ -132,28 +223,139 @@ pub(crate) fn on_sync_speech_continue(req: &PluginRequest) -> PluginResponse {
   }
 }
 
+/// OnSyncSpeechContinue を呼ぶ前の待機タグを返す。
+/// \__w はスクリプト開始からの経過時間で待つため、バルーン表示と音声再生が並行して進む。
+fn wait_tag(ms: u64) -> String {
+  if ms == 0 {
+    String::new()
+  } else {
+    format!("\\__w[{}]", ms)
+  }
+}
+
+// sync_poll_interval の下限。0 だと待たずに OnSyncSpeechContinue を繰り返し呼んでしまう
+const MIN_SYNC_POLL_INTERVAL: u64 = 50;
+
+fn sync_poll_interval() -> u64 {
+  match SETTINGS.read() {
+    Ok(s) => s.sync_poll_interval.max(MIN_SYNC_POLL_INTERVAL),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      DEFAULT_SYNC_POLL_INTERVAL
+    }
+  }
+}
+
+/// ゴーストのスクリプトから録音を切り替える。
+/// \\![raiseplugin,GhostSpeaker,OnRecordingChange,ゴースト名,start|stop]（省略すると反転）
+pub(crate) fn on_recording_change(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let ghost_name = match refs.first() {
+    Some(name) => name.to_string(),
+    None => {
+      error!("Missing ghost name in references");
+      return new_response_nocontent();
+    }
+  };
+  let enabled = match refs.get(1).copied() {
+    Some("start") => Some(true),
+    Some("stop") => Some(false),
+    _ => None,
+  };
+  crate::events::menu::set_recording(&ghost_name, enabled);
+  new_response_nocontent()
+}
+
+/// ゴーストのスクリプトから、直前のトーク（または履歴の id のトーク）を再生し直す
+pub(crate) fn on_speech_replay(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  let ghost_name = match refs.first() {
+    Some(name) => name.to_string(),
+    None => {
+      error!("Missing ghost name in references");
+      return new_response_nocontent();
+    }
+  };
+  let id = refs.get(1).and_then(|id| id.parse::<u64>().ok());
+  crate::events::menu::replay_speech(&ghost_name, id);
+  new_response_nocontent()
+}
+
+/// ゴーストの現在のシェルを覚え、変わっていればワーカーに通知する
+pub(crate) fn set_current_shell(ghost_name: &str, shell_name: &str) {
+  if shell_name.is_empty() {
+    return;
+  }
+  let changed = match CURRENT_SHELLS.write() {
+    Ok(mut cs) => {
+      cs.insert(ghost_name.to_string(), shell_name.to_string())
+        .as_deref()
+        != Some(shell_name)
+    }
+    Err(e) => {
+      error!("Failed to write CURRENT_SHELLS: {}", e);
+      return;
+    }
+  };
+  if changed {
+    send_command_logged(&Command::UpdateShell {
+      ghost_name: ghost_name.to_string(),
+      shell_name: shell_name.to_string(),
+    });
+  }
+}
+
+pub(crate) fn current_shell(ghost_name: &str) -> Option<String> {
+  match CURRENT_SHELLS.read() {
+    Ok(cs) => cs.get(ghost_name).cloned(),
+    Err(e) => {
+      error!("Failed to read CURRENT_SHELLS: {}", e);
+      None
+    }
+  }
+}
+
+/// シェルの切り替えなどでゴーストの情報が変わった（Reference は OnGhostBoot と同じ）
+pub(crate) fn on_ghost_info_update(req: &PluginRequest) -> PluginResponse {
+  let refs = get_references(req);
+  if let (Some(ghost_name), Some(shell_name)) = (refs.get(1), refs.get(2)) {
+    set_current_shell(ghost_name, shell_name);
+  }
+  new_response_nocontent()
+}
+
 pub(crate) fn on_ghost_boot(req: &PluginRequest) -> PluginResponse {
   let refs = get_references(req);
   let ghost_name = refs[1].to_string();
   let path = refs[4].to_string();
-  let description = load_descript(path);
+  if let Some(shell_name) = refs.get(2) {
+    set_current_shell(&ghost_name, shell_name);
+  }
+  let description = load_descript(path.clone());
+  // ghostspeaker.txt の提案は descript.txt より優先する
+  let mut hints = description.clone();
+  hints.extend(load_ghostspeaker_txt(&path));
   let characters = count_characters(description);
 
-  let mut ghosts_voices = match GHOSTS_VOICES.write() {
-    Ok(gv) => gv,
-    Err(e) => {
-      error!("Failed to write GHOSTS_VOICES: {}", e);
-      return new_response_nocontent();
+  match ghost_voices(&ghost_name) {
+    None => {
+      let mut info = GhostVoiceInfo::new(characters.len());
+      info.character_names = characters;
+      info.apply_suggested_voices(&hints);
+      set_ghost_voices(&ghost_name, &info);
+    }
+    Some(mut info) => {
+      // キャラクター名は録音のファイル名などに使うので、変わっていれば更新する
+      let renamed = info.character_names != characters;
+      if renamed {
+        info.character_names = characters;
+      }
+      // まだ声質を選んでいないキャラクターには、後から追加された提案も使う
+      let suggested = info.apply_suggested_voices(&hints);
+      if renamed || suggested {
+        set_ghost_voices(&ghost_name, &info);
+      }
     }
-  };
-  if ghosts_voices.get(&ghost_name).is_none() {
-    let info = GhostVoiceInfo::new(characters.len());
-    // ワーカーにも新ゴストの情報を通知
-    send_command_logged(&Command::UpdateGhostVoices {
-      ghost_name: ghost_name.clone(),
-      info: info.clone(),
-    });
-    ghosts_voices.insert(ghost_name, info);
   }
 
   new_response_nocontent()
@@ -1,9 +1,8 @@
 use crate::events::common::*;
-use crate::ipc::send_command_logged;
+use crate::ipc::ensure_worker;
 use crate::plugin::request::PluginRequest;
 use crate::plugin::response::PluginResponse;
-use crate::variables::{LOG_INIT_SUCCESS, PLUGIN_NAME};
-use ghost_speaker_common::{Command, Response};
+use crate::variables::{LOG_INIT_SUCCESS, PLUGIN_NAME, WORKER_DIALOGS, WORKER_ERROR};
 use std::sync::atomic::{AtomicBool, Ordering};
 
 pub(crate) fn on_second_change(_req: &PluginRequest) -> PluginResponse {
@@ -12,12 +11,17 @@ pub(crate) fn on_second_change(_req: &PluginRequest) -> PluginResponse {
 
   let mut lines: Vec<String> = Vec::new();
 
-  // ワーカーから接続ダイアログをポーリング
-  if let Some(Response::Dialog {
-    message: Some(msg), ..
-  }) = send_command_logged(&Command::PopDialog)
-  {
-    lines.push(msg);
+  // ワーカーが異常終了していれば再起動する。起動と Init は別スレッドで行うので待たない
+  ensure_worker();
+
+  // ワーカーから通知されたメッセージ（エンジンの接続・切断など）
+  match WORKER_DIALOGS.write() {
+    Ok(mut dialogs) => {
+      if !dialogs.is_empty() {
+        lines.push(dialogs.remove(0));
+      }
+    }
+    Err(e) => error!("Failed to write WORKER_DIALOGS: {}", e),
   }
 
   let update = if !UPDATE_CHECKED.swap(true, Ordering::Relaxed) {
@@ -26,6 +30,16 @@ pub(crate) fn on_second_change(_req: &PluginRequest) -> PluginResponse {
     String::new()
   };
 
+  // ワーカーの初期化エラーは一度だけ通知する
+  match WORKER_ERROR.write() {
+    Ok(mut we) => {
+      if let Some(msg) = we.take() {
+        lines.push(msg);
+      }
+    }
+    Err(e) => error!("Failed to write WORKER_ERROR: {}", e),
+  }
+
   if !LOG_INIT_CHECKED.swap(true, Ordering::Relaxed) {
     let log_init_success = match LOG_INIT_SUCCESS.read() {
       Ok(lis) => *lis,
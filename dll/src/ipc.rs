@@ -1,16 +1,54 @@
-use ghost_speaker_common::{Command, Response};
-use log::{debug, error};
+use crate::settings::{fetch_settings, store_settings};
+use crate::variables::{DLL_DIR, WORKER_DIALOGS, WORKER_ERROR};
+use ghost_speaker_common::{
+  features, missing_features, Command, Request, Response, WorkerConfig, WorkerEvent, WorkerMessage,
+  PROTOCOL_VERSION,
+};
+use log::{debug, error, info};
+use std::collections::HashMap;
 use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::process::{Child, Stdio};
-use std::sync::Mutex;
+use std::process::{Child, ChildStdout, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// 応答待ちのリクエスト（id → 応答の送り先）
+type PendingReplies = Arc<Mutex<HashMap<u64, Sender<Response>>>>;
 
 struct WorkerConnection {
   child: Child,
   writer: BufWriter<std::process::ChildStdin>,
-  reader: BufReader<std::process::ChildStdout>,
+  pending: PendingReplies,
+  /// 再起動前の読み取りスレッドが新しい接続を消さないよう、接続ごとに振る番号
+  generation: u64,
+  reader: JoinHandle<()>,
+  /// 読み取りスレッドが終了するときに通知される
+  reader_done: Receiver<()>,
 }
 
 static WORKER: Mutex<Option<WorkerConnection>> = Mutex::new(None);
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// バージョン不一致や異常終了の繰り返しでワーカーを使えない状態。再起動しても直らないため再試行しない
+static WORKER_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// バックグラウンドでワーカーを起動し直している間は true。その間は Init 以外のコマンドを送らない
+static RESPAWNING: AtomicBool = AtomicBool::new(false);
+static RESPAWN_THREAD: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+/// 異常終了から再起動までの待ち時間の上限
+const MAX_RESPAWN_BACKOFF: Duration = Duration::from_secs(30);
+/// この期間内に CRASH_LOOP_LIMIT 回異常終了したら再起動をあきらめる
+const CRASH_WINDOW: Duration = Duration::from_secs(300);
+const CRASH_LOOP_LIMIT: usize = 5;
+
+/// ワーカーが異常終了した時刻
+static CRASH_TIMES: Mutex<Vec<Instant>> = Mutex::new(Vec::new());
+/// 次に再起動を試みてよい時刻
+static NEXT_RESPAWN: Mutex<Option<Instant>> = Mutex::new(None);
 
 pub(crate) fn spawn_worker(dll_dir: &str) -> Result<(), String> {
   let worker_path = std::path::Path::new(dll_dir).join("ghost_speaker_worker.exe");
@@ -48,7 +86,20 @@ pub(crate) fn spawn_worker(dll_dir: &str) -> Result<(), String> {
   let stdout = child.stdout.take().ok_or("Failed to take worker stdout")?;
 
   let writer = BufWriter::new(stdin);
-  let reader = BufReader::new(stdout);
+  let pending: PendingReplies = Arc::new(Mutex::new(HashMap::new()));
+  let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+  let (done_tx, reader_done) = mpsc::channel();
+
+  let reader = {
+    let pending = pending.clone();
+    std::thread::Builder::new()
+      .name("ghost-speaker-ipc".to_string())
+      .spawn(move || {
+        read_messages(BufReader::new(stdout), pending, generation);
+        let _ = done_tx.send(());
+      })
+      .map_err(|e| format!("Failed to spawn reader thread: {}", e))?
+  };
 
   let mut guard = WORKER
     .lock()
@@ -56,62 +107,255 @@ pub(crate) fn spawn_worker(dll_dir: &str) -> Result<(), String> {
   *guard = Some(WorkerConnection {
     child,
     writer,
+    pending,
+    generation,
     reader,
+    reader_done,
   });
 
   debug!("Worker spawned successfully");
   Ok(())
 }
 
-pub(crate) fn send_command(cmd: &Command) -> Result<Response, String> {
-  let mut guard = WORKER
-    .lock()
-    .map_err(|e| format!("Failed to lock WORKER: {}", e))?;
-
-  let conn = guard
-    .as_mut()
-    .ok_or_else(|| "Worker not running".to_string())?;
+/// ワーカーからのメッセージを読み続け、応答は待っているリクエストへ、通知は handle_event へ渡す
+fn read_messages(reader: BufReader<ChildStdout>, pending: PendingReplies, generation: u64) {
+  let mut closed = false;
+  let mut received = false;
+  for line in reader.lines() {
+    let line = match line {
+      Ok(l) => l,
+      Err(e) => {
+        error!("Failed to read from worker: {}", e);
+        break;
+      }
+    };
+    if line.is_empty() {
+      continue;
+    }
+    received = true;
+    match serde_json::from_str::<WorkerMessage>(&line) {
+      Ok(WorkerMessage::Reply { id, response }) => {
+        let sender = match pending.lock() {
+          Ok(mut p) => p.remove(&id),
+          Err(e) => {
+            error!("Failed to lock pending replies: {}", e);
+            None
+          }
+        };
+        match sender {
+          Some(tx) => {
+            let _ = tx.send(response);
+          }
+          // タイムアウトで待つのをやめたリクエストへの応答
+          None => debug!("Discarding reply for request {}", id),
+        }
+      }
+      Ok(WorkerMessage::Event { event }) => handle_event(event),
+      Ok(WorkerMessage::Closed) => {
+        debug!("Worker closed its output");
+        closed = true;
+        break;
+      }
+      Ok(WorkerMessage::Unknown) => debug!("Unknown message from worker: {}", line),
+      Err(e) => error!("Failed to parse worker message: {}: {}", e, line),
+    }
+  }
 
-  // Check if worker is still alive
-  match conn.child.try_wait() {
-    Ok(Some(status)) => {
-      error!("Worker process exited with status: {}", status);
+  // 応答を待っているリクエストを打ち切る
+  match pending.lock() {
+    Ok(mut p) => p.clear(),
+    Err(e) => error!("Failed to lock pending replies: {}", e),
+  }
+  // 何も送らずに終了した場合は Init の失敗として initialize_worker が扱う
+  if !received {
+    debug!("Worker reader thread finished without any message");
+    return;
+  }
+  if let Ok(mut guard) = WORKER.lock() {
+    if guard.as_ref().is_some_and(|c| c.generation == generation) {
       *guard = None;
-      return Err(format!("Worker process exited: {}", status));
+      if !closed {
+        record_crash("Worker closed its output unexpectedly");
+      }
     }
-    Ok(None) => {} // still running
-    Err(e) => {
-      error!("Failed to check worker status: {}", e);
+  }
+  debug!("Worker reader thread finished");
+}
+
+fn handle_event(event: WorkerEvent) {
+  match event {
+    WorkerEvent::Dialog { message } => push_dialog(message),
+    WorkerEvent::SettingsChanged { settings } => store_settings(*settings),
+    WorkerEvent::Unknown => debug!("Unknown event from worker"),
+  }
+}
+
+/// コマンドごとの応答待ちの上限
+fn command_timeout(cmd: &Command) -> Duration {
+  match cmd {
+    Command::Init { .. } => Duration::from_secs(10),
+    Command::BootEngine { .. } | Command::GracefulShutdown | Command::Shutdown => {
+      Duration::from_secs(5)
     }
+    _ => Duration::from_secs(3),
   }
+}
 
-  let json = serde_json::to_string(cmd).map_err(|e| format!("Serialize error: {}", e))?;
+pub(crate) fn send_command(cmd: &Command) -> Result<Response, String> {
+  if WORKER_DISABLED.load(Ordering::Acquire) {
+    return Err("Worker is incompatible".to_string());
+  }
+  if RESPAWNING.load(Ordering::Acquire) && !matches!(cmd, Command::Init { .. }) {
+    return Err("Worker is restarting".to_string());
+  }
+  let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+  let (tx, rx) = mpsc::channel();
 
-  conn
-    .writer
-    .write_all(json.as_bytes())
-    .map_err(|e| format!("Write error: {}", e))?;
-  conn
-    .writer
-    .write_all(b"\n")
-    .map_err(|e| format!("Write newline error: {}", e))?;
-  conn
-    .writer
-    .flush()
-    .map_err(|e| format!("Flush error: {}", e))?;
+  // 書き込みの間だけ WORKER をロックし、応答はロックを解放してから待つ
+  let pending = {
+    let mut guard = WORKER
+      .lock()
+      .map_err(|e| format!("Failed to lock WORKER: {}", e))?;
 
-  let mut line = String::new();
-  conn
-    .reader
-    .read_line(&mut line)
-    .map_err(|e| format!("Read error: {}", e))?;
+    let conn = guard
+      .as_mut()
+      .ok_or_else(|| "Worker not running".to_string())?;
 
-  if line.is_empty() {
+    // Check if worker is still alive
+    match conn.child.try_wait() {
+      Ok(Some(status)) => {
+        *guard = None;
+        record_crash(&format!("Worker process exited with status: {}", status));
+        return Err(format!("Worker process exited: {}", status));
+      }
+      Ok(None) => {} // still running
+      Err(e) => {
+        error!("Failed to check worker status: {}", e);
+      }
+    }
+
+    let json = serde_json::to_string(&Request {
+      id,
+      command: cmd.clone(),
+    })
+    .map_err(|e| format!("Serialize error: {}", e))?;
+
+    conn
+      .pending
+      .lock()
+      .map_err(|e| format!("Failed to lock pending replies: {}", e))?
+      .insert(id, tx);
+
+    let written = conn
+      .writer
+      .write_all(json.as_bytes())
+      .and_then(|_| conn.writer.write_all(b"\n"))
+      .and_then(|_| conn.writer.flush());
+    if let Err(e) = written {
+      if let Ok(mut p) = conn.pending.lock() {
+        p.remove(&id);
+      }
+      return Err(format!("Write error: {}", e));
+    }
+    conn.pending.clone()
+  };
+
+  let timeout = command_timeout(cmd);
+  match rx.recv_timeout(timeout) {
+    Ok(resp) => Ok(resp),
+    Err(RecvTimeoutError::Timeout) => {
+      if let Ok(mut p) = pending.lock() {
+        p.remove(&id);
+      }
+      Err(format!(
+        "Timed out waiting for response ({} ms)",
+        timeout.as_millis()
+      ))
+    }
+    Err(RecvTimeoutError::Disconnected) => Err("Worker closed connection".to_string()),
+  }
+}
+
+/// ワーカーが起動していて、終了していないか
+fn is_worker_running() -> bool {
+  let mut guard = match WORKER.lock() {
+    Ok(g) => g,
+    Err(_) => return false,
+  };
+  let exited = match guard.as_mut() {
+    Some(conn) => !matches!(conn.child.try_wait(), Ok(None)),
+    None => return false,
+  };
+  if exited {
     *guard = None;
-    return Err("Worker closed connection".to_string());
+    record_crash("Worker process exited");
+  }
+  !exited
+}
+
+/// ワーカーの異常終了を記録し、次の再起動までの待ち時間を決める。
+/// 短い間に何度も落ちる場合はプラグインを停止する
+fn record_crash(reason: &str) {
+  error!("{}", reason);
+  if crate::SHUTTING_DOWN.load(Ordering::Acquire) {
+    return;
+  }
+  let now = Instant::now();
+  let crashes = match CRASH_TIMES.lock() {
+    Ok(mut times) => {
+      times.retain(|t| now.duration_since(*t) < CRASH_WINDOW);
+      times.push(now);
+      times.len()
+    }
+    Err(e) => {
+      error!("Failed to lock CRASH_TIMES: {}", e);
+      1
+    }
+  };
+
+  if crashes >= CRASH_LOOP_LIMIT {
+    error!("Worker crashed {} times, giving up", crashes);
+    WORKER_DISABLED.store(true, Ordering::Release);
+    report_worker_error(
+      "ワーカーが繰り返し異常終了したため、読み上げを停止しました。ゴーストを再起動すると再開します"
+        .to_string(),
+    );
+    return;
+  }
+
+  // 1秒, 2秒, 4秒… と待ち時間を延ばす
+  let backoff = Duration::from_secs(1 << (crashes - 1).min(5)).min(MAX_RESPAWN_BACKOFF);
+  match NEXT_RESPAWN.lock() {
+    Ok(mut next) => *next = Some(now + backoff),
+    Err(e) => error!("Failed to lock NEXT_RESPAWN: {}", e),
+  }
+  push_dialog("ワーカーが異常終了しました。再起動します".to_string());
+}
+
+fn push_dialog(message: String) {
+  match WORKER_DIALOGS.write() {
+    Ok(mut dialogs) => dialogs.push(message),
+    Err(e) => error!("Failed to write WORKER_DIALOGS: {}", e),
+  }
+}
+
+/// OnSecondChange でユーザーに通知するエラーを設定する
+fn report_worker_error(message: String) {
+  match WORKER_ERROR.write() {
+    Ok(mut we) => *we = Some(message),
+    Err(e) => error!("Failed to write WORKER_ERROR: {}", e),
   }
+}
 
-  serde_json::from_str(line.trim()).map_err(|e| format!("Deserialize error: {}", e))
+/// 読み込みのたびに、前回の異常終了の記録や停止状態をリセットする
+pub(crate) fn reset_supervision() {
+  WORKER_DISABLED.store(false, Ordering::Release);
+  if let Ok(mut times) = CRASH_TIMES.lock() {
+    times.clear();
+  }
+  if let Ok(mut next) = NEXT_RESPAWN.lock() {
+    *next = None;
+  }
 }
 
 /// Send a command without caring about errors (fire-and-forget style logging)
@@ -119,39 +363,237 @@ pub(crate) fn send_command_logged(cmd: &Command) -> Option<Response> {
   match send_command(cmd) {
     Ok(resp) => Some(resp),
     Err(e) => {
+      if WORKER_DISABLED.load(Ordering::Acquire) {
+        return None;
+      }
       error!("IPC command failed: {}", e);
-      // Try to respawn worker once
-      try_respawn_worker();
+      ensure_worker();
       None
     }
   }
 }
 
-fn try_respawn_worker() {
-  let dll_dir = match crate::variables::DLL_DIR.read() {
+/// ワーカーが止まっていれば、待ち時間を過ぎたあとに起動し直して Init をやり直す。
+/// Init の応答は最大10秒待つため、ベースウェアを止めないよう別スレッドで行う
+pub(crate) fn ensure_worker() {
+  if WORKER_DISABLED.load(Ordering::Acquire)
+    || crate::SHUTTING_DOWN.load(Ordering::Acquire)
+    || RESPAWNING.load(Ordering::Acquire)
+  {
+    return;
+  }
+  // タイムアウトしただけでワーカーが動いている場合は再起動しない
+  if is_worker_running() {
+    return;
+  }
+  match NEXT_RESPAWN.lock() {
+    Ok(next) => {
+      if next.is_some_and(|t| Instant::now() < t) {
+        return;
+      }
+    }
+    Err(e) => error!("Failed to lock NEXT_RESPAWN: {}", e),
+  }
+  let dll_dir = match DLL_DIR.read() {
     Ok(d) => d.clone(),
     Err(_) => return,
   };
   if dll_dir.is_empty() {
     return;
   }
+  if RESPAWNING.swap(true, Ordering::AcqRel) {
+    return;
+  }
+
+  let spawned = std::thread::Builder::new()
+    .name("ghost-speaker-respawn".to_string())
+    .spawn(move || respawn_worker(&dll_dir));
+  match spawned {
+    Ok(handle) => match RESPAWN_THREAD.lock() {
+      Ok(mut thread) => *thread = Some(handle),
+      Err(e) => error!("Failed to lock RESPAWN_THREAD: {}", e),
+    },
+    Err(e) => {
+      RESPAWNING.store(false, Ordering::Release);
+      error!("Failed to spawn respawn thread: {}", e);
+    }
+  }
+}
+
+fn respawn_worker(dll_dir: &str) {
+  if crate::SHUTTING_DOWN.load(Ordering::Acquire) {
+    RESPAWNING.store(false, Ordering::Release);
+    return;
+  }
   debug!("Attempting to respawn worker...");
-  if let Err(e) = spawn_worker(&dll_dir) {
-    error!("Worker respawn failed: {}", e);
+  if let Err(e) = spawn_worker(dll_dir) {
+    RESPAWNING.store(false, Ordering::Release);
+    record_crash(&format!("Worker respawn failed: {}", e));
+    return;
+  }
+  // Init をやり直す。設定はワーカーが vars.yaml から読み込み直す
+  let result = initialize_worker(dll_dir, crate::build_worker_config());
+  RESPAWNING.store(false, Ordering::Release);
+  match result {
+    Ok(()) => {
+      info!("Worker recovered");
+      fetch_settings();
+      push_dialog("ワーカーを再起動しました".to_string());
+    }
+    Err(message) => error!("{}", message),
+  }
+}
+
+/// Init を送り、プロトコルバージョンと対応機能を確認する。
+/// 失敗した場合はワーカーを停止し、ユーザーに表示するメッセージを返す。
+/// バージョンが一致しない場合だけプラグインを停止し、それ以外は異常終了として再起動に任せる
+pub(crate) fn initialize_worker(dll_dir: &str, config: WorkerConfig) -> Result<(), String> {
+  let resp = send_command(&Command::Init {
+    dll_dir: dll_dir.to_string(),
+    config: Box::new(config),
+    protocol_version: PROTOCOL_VERSION,
+    features: features(),
+  });
+  let replied = resp.is_ok();
+  let (message, incompatible) = match resp {
+    Ok(Response::Initialized {
+      protocol_version,
+      features,
+    }) => {
+      if protocol_version == PROTOCOL_VERSION {
+        let missing = missing_features(&features);
+        if !missing.is_empty() {
+          info!("Features not supported by worker: {}", missing.join(", "));
+        }
+        return Ok(());
+      }
+      (
+        format!(
+          "DLLとワーカーのバージョンが一致しません(DLL: v{} / ワーカー: v{})。プラグインを入れ直してください",
+          PROTOCOL_VERSION, protocol_version
+        ),
+        true,
+      )
+    }
+    Ok(Response::Error { message }) => {
+      error!("Worker Init failed: {}", message);
+      (
+        format!("ワーカーの初期化に失敗しました: {}", message),
+        false,
+      )
+    }
+    Ok(other) => {
+      error!("Unexpected Init response: {:?}", other);
+      (
+        "DLLとワーカーのバージョンが一致しません。プラグインを入れ直してください".to_string(),
+        true,
+      )
+    }
+    Err(e) => {
+      error!("Failed to send Init command: {}", e);
+      (format!("ワーカーの初期化に応答がありません: {}", e), false)
+    }
+  };
+  // 先に WORKER から外しておけば、読み取りスレッドが異常終了として数えることはない
+  let (stopped, exited) = match WORKER.lock() {
+    Ok(mut guard) => match guard.take() {
+      Some(mut conn) => {
+        let exited = !replied && has_exited(&mut conn.child, Duration::from_millis(500));
+        let _ = conn.child.kill();
+        (true, exited)
+      }
+      None => (false, false),
+    },
+    Err(e) => {
+      error!("Failed to lock WORKER: {}", e);
+      (false, false)
+    }
+  };
+  // 旧バージョンのワーカーは新しい形式の Init を読めず、何も返さずに終了する
+  let (message, incompatible) = if exited {
+    error!("Worker exited without replying to Init");
+    (
+      "DLLとワーカーのバージョンが一致しません。プラグインを入れ直してください".to_string(),
+      true,
+    )
+  } else {
+    (message, incompatible)
+  };
+  if incompatible {
+    // 起動し直しても直らないので、プラグインを停止する
+    WORKER_DISABLED.store(true, Ordering::Release);
+    report_worker_error(message.clone());
+  } else if stopped {
+    // タイムアウトなどは再起動の待ち時間と繰り返し回数の判定に任せる。
+    // ワーカーが既に終了していた場合は send_command か読み取りスレッドが記録済み
+    record_crash(&message);
+  }
+  Err(message)
+}
+
+/// 出力が閉じた直後はまだ終了していないことがあるので、timeout まで待って確かめる
+fn has_exited(child: &mut Child, timeout: Duration) -> bool {
+  let deadline = Instant::now() + timeout;
+  loop {
+    match child.try_wait() {
+      Ok(Some(_)) => return true,
+      Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+      _ => return false,
+    }
   }
 }
 
 pub(crate) fn shutdown_worker() -> Result<(), String> {
+  detach_worker()?;
+  // 再起動中のスレッドは、ワーカーの接続を閉じれば Init の応答待ちがすぐに終わる
+  let respawn = match RESPAWN_THREAD.lock() {
+    Ok(mut thread) => thread.take(),
+    Err(e) => {
+      error!("Failed to lock RESPAWN_THREAD: {}", e);
+      None
+    }
+  };
+  if let Some(handle) = respawn {
+    let _ = handle.join();
+    // 待つ間に起動されたワーカーも閉じる
+    detach_worker()?;
+  }
+  Ok(())
+}
+
+fn detach_worker() -> Result<(), String> {
   // GracefulShutdown コマンドを送信
   let _ = send_command(&Command::GracefulShutdown);
 
   // ワーカー接続をドロップ（パイプを閉じる）
   // ワーカーの終了を待たない → detach
-  let mut guard = WORKER
+  let conn = WORKER
     .lock()
-    .map_err(|e| format!("Failed to lock WORKER: {}", e))?;
-  if let Some(conn) = guard.take() {
-    drop(conn); // パイプクローズ、プロセスハンドル解放
+    .map_err(|e| format!("Failed to lock WORKER: {}", e))?
+    .take();
+  if let Some(conn) = conn {
+    let WorkerConnection {
+      mut child,
+      writer,
+      reader,
+      reader_done,
+      ..
+    } = conn;
+    drop(writer); // パイプクローズ
+    if RESPAWNING.load(Ordering::Acquire) {
+      // Init 前のワーカーは保存するものがないので、応答を待たずに止める
+      let _ = child.kill();
+    }
+    drop(child); // プロセスハンドル解放
+
+    // DLL がアンロードされる前に読み取りスレッドの終了を待つ。
+    // ワーカーは終了処理に入ると Closed を送るので、通常はすぐに終わる
+    match reader_done.recv_timeout(Duration::from_secs(2)) {
+      Ok(()) | Err(RecvTimeoutError::Disconnected) => {
+        let _ = reader.join();
+      }
+      Err(RecvTimeoutError::Timeout) => error!("Worker reader thread did not finish"),
+    }
     debug!("Worker detached for graceful shutdown");
   }
 
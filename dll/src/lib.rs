@@ -4,22 +4,16 @@ mod common;
 mod events;
 mod ipc;
 mod plugin;
+mod settings;
 mod variables;
 
-use crate::ipc::{send_command, shutdown_worker, spawn_worker};
+use crate::ipc::{initialize_worker, reset_supervision, shutdown_worker, spawn_worker};
 use crate::plugin::request::PluginRequest;
-use crate::variables::rawvariables::copy_from_raw;
-use crate::variables::rawvariables::save_variables;
-use crate::variables::rawvariables::RawGlobalVariables;
+use crate::settings::fetch_settings;
+use crate::variables::CURRENT_SHELLS;
 use crate::variables::DLL_DIR;
-use crate::variables::ENGINE_AUTO_START;
-use crate::variables::ENGINE_PATH;
-use crate::variables::GHOSTS_VOICES;
-use crate::variables::INITIAL_VOICE;
 use crate::variables::LOG_INIT_SUCCESS;
-use crate::variables::SPEAK_BY_PUNCTUATION;
-use crate::variables::VOLUME;
-use ghost_speaker_common::{Command, WorkerConfig};
+use ghost_speaker_common::{AudioOutput, WorkerConfig};
 use shiori_hglobal::*;
 use shiorust::message::Parser;
 use simplelog::*;
@@ -154,6 +148,7 @@ pub extern "cdecl" fn load(h: HGLOBAL, len: c_long) -> BOOL {
 fn common_load_process(dll_path: &str) -> Result<(), ()> {
   SHUTTING_DOWN.store(false, Ordering::Release);
   VEH_EXCEPTION_COUNT.store(0, Ordering::Relaxed);
+  reset_supervision();
 
   let log_path = PathBuf::from(dll_path).join("ghost-speaker.log");
   println!("log_path: {:?}", log_path);
@@ -177,10 +172,9 @@ fn common_load_process(dll_path: &str) -> Result<(), ()> {
     }
   };
 
-  debug!("logger initialized, loading variables");
+  debug!("logger initialized");
   log::logger().flush();
 
-  copy_from_raw(&RawGlobalVariables::new(dll_path));
   let mut dll_dir = match DLL_DIR.write() {
     Ok(d) => d,
     Err(_) => return Err(()),
@@ -188,7 +182,7 @@ fn common_load_process(dll_path: &str) -> Result<(), ()> {
   *dll_dir = dll_path.to_string();
   drop(dll_dir);
 
-  debug!("variables loaded, setting panic hook");
+  debug!("setting panic hook");
   log::logger().flush();
 
   panic::set_hook(Box::new(|panic_info| {
@@ -210,25 +204,14 @@ fn common_load_process(dll_path: &str) -> Result<(), ()> {
   log::logger().flush();
 
   let config = build_worker_config();
-  match send_command(&Command::Init {
-    dll_dir: dll_path.to_string(),
-    config,
-  }) {
-    Ok(ghost_speaker_common::Response::Ok) => {
+  match initialize_worker(dll_path, config) {
+    Ok(()) => {
       debug!("Worker initialized successfully");
+      // 設定はワーカーが vars.yaml から読み込んでいる
+      fetch_settings();
     }
-    Ok(ghost_speaker_common::Response::Error { message }) => {
-      error!("Worker Init failed: {}", message);
-      return Err(());
-    }
-    Ok(other) => {
-      error!("Unexpected Init response: {:?}", other);
-      return Err(());
-    }
-    Err(e) => {
-      error!("Failed to send Init command: {}", e);
-      return Err(());
-    }
+    // プラグイン自体は読み込み、OnSecondChange でエラーを通知する
+    Err(message) => error!("{}", message),
   }
 
   debug!("load complete");
@@ -237,30 +220,16 @@ fn common_load_process(dll_path: &str) -> Result<(), ()> {
   Ok(())
 }
 
-fn build_worker_config() -> WorkerConfig {
-  let volume = VOLUME.read().map(|v| *v).unwrap_or(1.0);
-  let speak_by_punctuation = SPEAK_BY_PUNCTUATION.read().map(|s| *s).unwrap_or(true);
-  let ghosts_voices = GHOSTS_VOICES
-    .read()
-    .map(|gv| gv.clone())
-    .unwrap_or_default();
-  let initial_voice = INITIAL_VOICE
+pub(crate) fn build_worker_config() -> WorkerConfig {
+  let current_shells = CURRENT_SHELLS
     .read()
-    .map(|iv| iv.clone())
+    .map(|cs| cs.clone())
     .unwrap_or_default();
-  let engine_auto_start = ENGINE_AUTO_START
-    .read()
-    .map(|ea| ea.clone())
-    .unwrap_or_default();
-  let engine_path = ENGINE_PATH.read().map(|ep| ep.clone()).unwrap_or_default();
 
   WorkerConfig {
-    volume,
-    speak_by_punctuation,
-    ghosts_voices,
-    initial_voice,
-    engine_auto_start,
-    engine_path,
+    audio_output: AudioOutput::Device,
+    current_shells,
+    persist_settings: true,
   }
 }
 
@@ -278,38 +247,11 @@ pub extern "cdecl" fn unload() -> BOOL {
       }
     }
 
-    // ワーカー停止前にエンジンステータスを同期
-    match send_command(&Command::GetEngineStatus) {
-      Ok(ghost_speaker_common::Response::EngineStatus {
-        engine_paths,
-        engine_auto_start,
-        ..
-      }) => {
-        if let Ok(mut ep) = ENGINE_PATH.write() {
-          *ep = engine_paths;
-        }
-        if let Ok(mut ea) = ENGINE_AUTO_START.write() {
-          *ea = engine_auto_start;
-        }
-      }
-      Ok(_) => {
-        debug!("Unexpected response from GetEngineStatus");
-      }
-      Err(e) => {
-        debug!("Failed to get engine status before shutdown: {}", e);
-      }
-    }
-
-    // ワーカーを停止
+    // ワーカーを停止。保存していない設定はワーカーが終了するときに保存する
     if let Err(e) = shutdown_worker() {
       error!("Failed to shutdown worker: {}", e);
     }
 
-    // 変数保存
-    if save_variables().is_err() {
-      error!("Failed to save variables");
-    }
-
     log::logger().flush();
 
     debug!("unload");
@@ -0,0 +1,71 @@
+//! ワーカーが持つ設定の写し
+//!
+//! 設定の保存はワーカーが行う。DLL は GetSettings や SettingsChanged で受け取った設定を
+//! 表示に使い、変更は PatchSettings でワーカーに送る
+use crate::ipc::send_command_logged;
+use crate::variables::SETTINGS;
+use ghost_speaker_common::{Command, GhostVoiceInfo, Response, SettingChange, Settings};
+use log::error;
+use serde::Serialize;
+
+/// ワーカーから設定を取得して写しを更新する
+pub(crate) fn fetch_settings() {
+  match send_command_logged(&Command::GetSettings) {
+    Some(Response::Settings { settings }) => store_settings(*settings),
+    Some(other) => error!("Unexpected GetSettings response: {:?}", other),
+    None => {}
+  }
+}
+
+pub(crate) fn store_settings(settings: Settings) {
+  match SETTINGS.write() {
+    Ok(mut s) => *s = settings,
+    Err(e) => error!("Failed to write SETTINGS: {}", e),
+  }
+}
+
+/// 設定の変更をワーカーに送り、変更後の設定で写しを更新する。失敗したら false
+pub(crate) fn patch_settings(changes: Vec<SettingChange>) -> bool {
+  match send_command_logged(&Command::PatchSettings { changes }) {
+    Some(Response::Settings { settings }) => {
+      store_settings(*settings);
+      true
+    }
+    Some(Response::Error { message }) => {
+      error!("Failed to patch settings: {}", message);
+      false
+    }
+    Some(other) => {
+      error!("Unexpected PatchSettings response: {:?}", other);
+      false
+    }
+    None => false,
+  }
+}
+
+/// path の位置の設定を value に変更する
+pub(crate) fn set_setting<T: Serialize>(path: &[&str], value: &T) -> bool {
+  match SettingChange::new(path, value) {
+    Ok(change) => patch_settings(vec![change]),
+    Err(e) => {
+      error!("Failed to serialize setting {}: {}", path.join("."), e);
+      false
+    }
+  }
+}
+
+/// ゴーストの声質の設定を info に変更する
+pub(crate) fn set_ghost_voices(ghost_name: &str, info: &GhostVoiceInfo) -> bool {
+  set_setting(&["ghosts_voices", ghost_name], info)
+}
+
+/// ゴーストの声質の設定の写し
+pub(crate) fn ghost_voices(ghost_name: &str) -> Option<GhostVoiceInfo> {
+  match SETTINGS.read() {
+    Ok(s) => s.ghosts_voices.get(ghost_name).cloned(),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      None
+    }
+  }
+}
@@ -1,28 +1,25 @@
-pub(crate) mod rawvariables;
-
-use ghost_speaker_common::{CharacterVoice, Engine, GhostVoiceInfo, SpeakerInfo};
+use ghost_speaker_common::{Engine, Settings, SpeakerInfo};
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::sync::RwLock;
 
 pub(crate) const PLUGIN_NAME: &str = "GhostSpeaker";
 pub(crate) const PLUGIN_UUID: &str = "1e1e0813-f16f-409e-b870-2c36b9084732";
-pub(crate) const VAR_PATH: &str = "vars.yaml";
+/// 声質の一覧に表示する、最近使った声質の数
+pub(crate) const RECENT_VOICES_LIMIT: usize = 5;
 
-pub(crate) static ENGINE_PATH: Lazy<RwLock<HashMap<Engine, String>>> =
-  Lazy::new(|| RwLock::new(HashMap::new()));
-pub(crate) static ENGINE_AUTO_START: Lazy<RwLock<HashMap<Engine, bool>>> =
-  Lazy::new(|| RwLock::new(HashMap::new()));
-pub(crate) static VOLUME: Lazy<RwLock<f32>> = Lazy::new(|| RwLock::new(1.0));
-pub(crate) static SPEAK_BY_PUNCTUATION: Lazy<RwLock<bool>> = Lazy::new(|| RwLock::new(true));
-pub(crate) static GHOSTS_VOICES: Lazy<RwLock<HashMap<String, GhostVoiceInfo>>> =
-  Lazy::new(|| RwLock::new(HashMap::new()));
-pub(crate) static INITIAL_VOICE: Lazy<RwLock<CharacterVoice>> =
-  Lazy::new(|| RwLock::new(CharacterVoice::no_voice()));
-pub(crate) static LAST_VERSION: Lazy<RwLock<String>> = Lazy::new(|| RwLock::new(String::new()));
+/// ワーカーが持つ設定の写し。変更は crate::settings を通してワーカーに送る
+pub(crate) static SETTINGS: Lazy<RwLock<Settings>> = Lazy::new(|| RwLock::new(Settings::default()));
 pub(crate) static DLL_DIR: Lazy<RwLock<String>> = Lazy::new(|| RwLock::new(String::new()));
 pub(crate) static SPEAKERS_INFO: Lazy<RwLock<HashMap<Engine, Vec<SpeakerInfo>>>> =
   Lazy::new(|| RwLock::new(HashMap::new()));
 pub(crate) static CURRENT_CONNECTION_STATUS: Lazy<RwLock<HashMap<Engine, bool>>> =
   Lazy::new(|| RwLock::new(HashMap::new()));
 pub(crate) static LOG_INIT_SUCCESS: Lazy<RwLock<bool>> = Lazy::new(|| RwLock::new(false));
+/// ゴーストごとの現在のシェル名（保存しない）
+pub(crate) static CURRENT_SHELLS: Lazy<RwLock<HashMap<String, String>>> =
+  Lazy::new(|| RwLock::new(HashMap::new()));
+/// ワーカーから通知された、ユーザーに表示するメッセージ
+pub(crate) static WORKER_DIALOGS: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(Vec::new()));
+/// ワーカーの初期化に失敗したときにユーザーへ通知するメッセージ
+pub(crate) static WORKER_ERROR: Lazy<RwLock<Option<String>>> = Lazy::new(|| RwLock::new(None));
@@ -0,0 +1,668 @@
+//! ghost_speaker_worker を SSP や DLL を介さずに動かすためのコマンドラインツール。
+//! エンジンの不具合の調査や、Linux 上での回帰確認に使う
+use ghost_speaker_common::{
+  engine_from_port, features, AudioOutput, CharacterVoice, Command, Engine, GhostVoiceInfo,
+  Request, Response, SegmentInfo, SettingChange, SpeakerInfo, SyncState, WorkerConfig, WorkerEvent,
+  WorkerMessage, ENGINE_LIST, PROTOCOL_VERSION,
+};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "\
+使い方: ghost_speaker_cli [オプション] [コマンド]
+
+コマンドを省略すると、標準入力から1行ずつコマンドを読み込む
+
+コマンド:
+  speak --text 文章 [--ghost 名前] [--voice 声]   非同期で読み上げる
+  sync --text 文章 [--ghost 名前] [--voice 声]    同期読み上げを最後まで進め、セグメントを表示する
+  engines                                      エンジンの接続状態を表示する
+  voices [--engine エンジン]                     話者とスタイルの一覧を表示する
+  stop                                         再生を止める
+  help                                         この説明を表示する
+  quit                                         終了する（対話モードのみ）
+
+オプション:
+  --worker パス     ワーカーの実行ファイル（既定: このツールと同じ場所）
+  --dir パス        ワーカーのログを書き出すディレクトリ（既定: 一時ディレクトリ）
+  --timeout 秒      エンジンの接続を待つ時間（既定: 10）
+  --output 出力先   音声の出力先。device（既定）・null（再生せず待つだけ）・file:ディレクトリ
+
+声の指定:
+  エンジン:話者UUID:スタイルID（例: VOICEVOX:7ffcb7ce-00ec-4bdc-82cd-45a8889e43ff:2）
+  エンジンは名前かポート番号で指定する。voices の出力をそのまま使える";
+
+const DEFAULT_GHOST: &str = "cli";
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+enum CliCommand {
+  Speak {
+    ghost: String,
+    text: String,
+    voice: Option<CharacterVoice>,
+  },
+  Sync {
+    ghost: String,
+    text: String,
+    voice: Option<CharacterVoice>,
+  },
+  Engines,
+  Voices {
+    engine: Option<Engine>,
+  },
+  Stop,
+  Help,
+  Quit,
+}
+
+struct Options {
+  worker: PathBuf,
+  dir: PathBuf,
+  timeout: Duration,
+  output: AudioOutput,
+}
+
+struct EngineStatus {
+  speakers_info: HashMap<Engine, Vec<SpeakerInfo>>,
+  connection_status: HashMap<Engine, bool>,
+}
+
+/// 起動したワーカーとの接続
+struct Worker {
+  child: Child,
+  writer: BufWriter<ChildStdin>,
+  reader: Lines<BufReader<ChildStdout>>,
+  next_id: u64,
+}
+
+impl Worker {
+  fn spawn(options: &Options) -> Result<Self, String> {
+    let mut child = std::process::Command::new(&options.worker)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::inherit())
+      .spawn()
+      .map_err(|e| format!("{} を起動できません: {}", options.worker.display(), e))?;
+    let stdin = child
+      .stdin
+      .take()
+      .ok_or("ワーカーの標準入力を取得できません")?;
+    let stdout = child
+      .stdout
+      .take()
+      .ok_or("ワーカーの標準出力を取得できません")?;
+    let mut worker = Worker {
+      child,
+      writer: BufWriter::new(stdin),
+      reader: BufReader::new(stdout).lines(),
+      next_id: 1,
+    };
+
+    // 設定は既定値から始め、vars.yaml には保存しない
+    let config = WorkerConfig {
+      audio_output: options.output.clone(),
+      ..Default::default()
+    };
+    match worker.send(Command::Init {
+      dll_dir: options.dir.to_string_lossy().to_string(),
+      config: Box::new(config),
+      protocol_version: PROTOCOL_VERSION,
+      features: features(),
+    })? {
+      Response::Initialized {
+        protocol_version, ..
+      } if protocol_version == PROTOCOL_VERSION => Ok(worker),
+      Response::Initialized {
+        protocol_version, ..
+      } => Err(format!(
+        "プロトコルのバージョンが一致しません（CLI: v{} / ワーカー: v{}）",
+        PROTOCOL_VERSION, protocol_version
+      )),
+      other => Err(format!("Init に失敗しました: {:?}", other)),
+    }
+  }
+
+  /// コマンドを送り、同じ id の応答が来るまで待つ。途中の通知は標準エラーに表示する
+  fn send(&mut self, command: Command) -> Result<Response, String> {
+    let id = self.next_id;
+    self.next_id += 1;
+    let json = serde_json::to_string(&Request { id, command })
+      .map_err(|e| format!("コマンドを変換できません: {}", e))?;
+    writeln!(self.writer, "{}", json)
+      .and_then(|_| self.writer.flush())
+      .map_err(|e| format!("ワーカーに書き込めません: {}", e))?;
+
+    loop {
+      let line = match self.reader.next() {
+        Some(Ok(line)) => line,
+        Some(Err(e)) => return Err(format!("ワーカーから読み込めません: {}", e)),
+        None => return Err("ワーカーが終了しました".to_string()),
+      };
+      match serde_json::from_str::<WorkerMessage>(&line) {
+        Ok(WorkerMessage::Reply {
+          id: reply_id,
+          response,
+        }) if reply_id == id => return Ok(response),
+        Ok(WorkerMessage::Reply { .. }) => {}
+        Ok(WorkerMessage::Event {
+          event: WorkerEvent::Dialog { message },
+        }) => eprintln!("[ワーカー] {}", message),
+        Ok(WorkerMessage::Event { .. }) | Ok(WorkerMessage::Unknown) => {}
+        Ok(WorkerMessage::Closed) => return Err("ワーカーが終了しました".to_string()),
+        Err(e) => eprintln!("ワーカーの出力を読めません: {}: {}", e, line),
+      }
+    }
+  }
+
+  fn engine_status(&mut self) -> Result<EngineStatus, String> {
+    match self.send(Command::GetEngineStatus)? {
+      Response::EngineStatus {
+        speakers_info,
+        connection_status,
+        ..
+      } => Ok(EngineStatus {
+        speakers_info,
+        connection_status,
+      }),
+      other => Err(format!("予期しない応答: {:?}", other)),
+    }
+  }
+
+  /// いずれかのエンジンが接続されるまで待つ。接続されなくても timeout 後に現在の状態を返す
+  fn wait_for_engine(&mut self, timeout: Duration) -> Result<EngineStatus, String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+      let status = self.engine_status()?;
+      if status.connection_status.values().any(|c| *c) || Instant::now() >= deadline {
+        return Ok(status);
+      }
+      std::thread::sleep(Duration::from_millis(500));
+    }
+  }
+
+  /// 読み上げに使うゴーストと声をワーカーに登録する
+  fn prepare_ghost(&mut self, ghost: &str, voice: &Option<CharacterVoice>) -> Result<(), String> {
+    let mut info = GhostVoiceInfo::new(2);
+    let mut changes = Vec::new();
+    if let Some(voice) = voice {
+      info.voices = vec![Some(voice.clone()); 2];
+      changes.push(SettingChange::new(&["initial_voice"], voice)?);
+    }
+    changes.push(SettingChange::new(&["ghosts_voices", ghost], &info)?);
+    match self.send(Command::PatchSettings { changes })? {
+      Response::Settings { .. } => Ok(()),
+      Response::Error { message } => Err(message),
+      other => Err(format!("予期しない応答: {:?}", other)),
+    }
+  }
+
+  /// 再生が終わるのを待ってから終了させる
+  fn shutdown(mut self) {
+    let _ = self.send(Command::GracefulShutdown);
+    drop(self.writer);
+    let _ = self.child.wait();
+  }
+}
+
+fn expect_ok(response: Response) -> Result<(), String> {
+  match response {
+    Response::Ok => Ok(()),
+    Response::Error { message } => Err(message),
+    other => Err(format!("予期しない応答: {:?}", other)),
+  }
+}
+
+fn main() {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let (options, rest) = match parse_options(&args) {
+    Ok(v) => v,
+    Err(e) => {
+      eprintln!("{}\n\n{}", e, USAGE);
+      std::process::exit(2);
+    }
+  };
+
+  let single = if rest.is_empty() {
+    None
+  } else {
+    match parse_command(&rest) {
+      Ok(CliCommand::Help) => {
+        println!("{}", USAGE);
+        return;
+      }
+      Ok(cmd) => Some(cmd),
+      Err(e) => {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(2);
+      }
+    }
+  };
+
+  let mut worker = match Worker::spawn(&options) {
+    Ok(w) => w,
+    Err(e) => {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    }
+  };
+
+  let mut failed = false;
+  match single {
+    Some(cmd) => {
+      if let Err(e) = run(&mut worker, &options, cmd) {
+        eprintln!("{}", e);
+        failed = true;
+      }
+    }
+    None => {
+      // 対話モード: 1行1コマンド
+      for line in std::io::stdin().lock().lines() {
+        let line = match line {
+          Ok(l) => l,
+          Err(_) => break,
+        };
+        let words = split_words(&line);
+        if words.is_empty() || words[0].starts_with('#') {
+          continue;
+        }
+        let result = match parse_command(&words) {
+          Ok(CliCommand::Quit) => break,
+          Ok(cmd) => run(&mut worker, &options, cmd),
+          Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+          eprintln!("{}", e);
+          failed = true;
+        }
+      }
+    }
+  }
+
+  worker.shutdown();
+  if failed {
+    std::process::exit(1);
+  }
+}
+
+fn run(worker: &mut Worker, options: &Options, cmd: CliCommand) -> Result<(), String> {
+  match cmd {
+    CliCommand::Speak { ghost, text, voice } => {
+      worker.wait_for_engine(options.timeout)?;
+      worker.prepare_ghost(&ghost, &voice)?;
+      expect_ok(worker.send(Command::SpeakAsync {
+        text,
+        ghost_name: ghost,
+      })?)
+    }
+    CliCommand::Sync { ghost, text, voice } => {
+      worker.wait_for_engine(options.timeout)?;
+      worker.prepare_ghost(&ghost, &voice)?;
+      run_sync(worker, ghost, text)
+    }
+    CliCommand::Engines => {
+      let EngineStatus {
+        speakers_info,
+        connection_status,
+      } = worker.wait_for_engine(options.timeout)?;
+      for engine in ENGINE_LIST {
+        let connected = connection_status.get(engine).copied().unwrap_or(false);
+        let speakers = speakers_info.get(engine).map(|s| s.len()).unwrap_or(0);
+        if connected {
+          println!(
+            "{} ({}): 接続中 / 話者 {}",
+            engine.name(),
+            engine.port(),
+            speakers
+          );
+        } else {
+          println!("{} ({}): 未接続", engine.name(), engine.port());
+        }
+      }
+      Ok(())
+    }
+    CliCommand::Voices { engine } => {
+      let speakers_info = worker.wait_for_engine(options.timeout)?.speakers_info;
+      for e in ENGINE_LIST {
+        if engine.is_some_and(|target| target != *e) {
+          continue;
+        }
+        for speaker in speakers_info.get(e).into_iter().flatten() {
+          for style in &speaker.styles {
+            let style_id = match style.style_id {
+              Some(id) => id,
+              None => continue,
+            };
+            println!(
+              "{}:{}:{}\t{} / {}",
+              e.name(),
+              speaker.speaker_uuid,
+              style_id,
+              speaker.speaker_name,
+              style.style_name.as_deref().unwrap_or("-")
+            );
+          }
+        }
+      }
+      Ok(())
+    }
+    CliCommand::Stop => expect_ok(worker.send(Command::ForceStopPlayback)?),
+    CliCommand::Help => {
+      println!("{}", USAGE);
+      Ok(())
+    }
+    CliCommand::Quit => Ok(()),
+  }
+}
+
+/// DLL の OnSyncSpeechContinue と同じ手順で同期読み上げを最後まで進める
+fn run_sync(worker: &mut Worker, ghost: String, text: String) -> Result<(), String> {
+  match worker.send(Command::SyncStart {
+    text,
+    ghost_name: ghost,
+  })? {
+    Response::SyncStarted {
+      first_segment: Some(segment),
+      ..
+    } => print_segment(&segment),
+    Response::SyncStarted {
+      first_segment: None,
+      has_more: false,
+    } => {
+      println!("(セグメントが1つ以下のため、非同期で読み上げます)");
+      return Ok(());
+    }
+    Response::SyncStarted { .. } => {}
+    other => return Err(format!("予期しない応答: {:?}", other)),
+  }
+
+  loop {
+    match worker.send(Command::SyncPoll)? {
+      Response::SyncStatus { state } => match state {
+        SyncState::Playing { remaining_ms } => {
+          std::thread::sleep(Duration::from_millis(remaining_ms).max(POLL_INTERVAL));
+        }
+        SyncState::Waiting => std::thread::sleep(POLL_INTERVAL),
+        SyncState::Ready { segment, .. } => print_segment(&segment),
+        SyncState::Complete => return Ok(()),
+      },
+      other => return Err(format!("予期しない応答: {:?}", other)),
+    }
+  }
+}
+
+fn print_segment(segment: &SegmentInfo) {
+  println!(
+    "[{}] {} ({} ms{})",
+    segment.scope,
+    segment.raw_text,
+    segment.duration_ms,
+    if segment.is_ellipsis {
+      ", 省略記号"
+    } else {
+      ""
+    }
+  );
+}
+
+fn parse_options(args: &[String]) -> Result<(Options, Vec<String>), String> {
+  let exe_dir = std::env::current_exe()
+    .ok()
+    .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+    .unwrap_or_default();
+  let mut options = Options {
+    worker: exe_dir.join(format!(
+      "ghost_speaker_worker{}",
+      std::env::consts::EXE_SUFFIX
+    )),
+    dir: std::env::temp_dir(),
+    timeout: Duration::from_secs(10),
+    output: AudioOutput::Device,
+  };
+
+  let mut i = 0;
+  while i < args.len() {
+    let value = || {
+      args
+        .get(i + 1)
+        .cloned()
+        .ok_or_else(|| format!("{} の値がありません", args[i]))
+    };
+    match args[i].as_str() {
+      "--worker" => options.worker = PathBuf::from(value()?),
+      "--dir" => options.dir = PathBuf::from(value()?),
+      "--timeout" => {
+        let secs = value()?
+          .parse::<u64>()
+          .map_err(|_| "--timeout には秒数を指定してください".to_string())?;
+        options.timeout = Duration::from_secs(secs);
+      }
+      "--output" => options.output = parse_output(&value()?)?,
+      _ => break,
+    }
+    i += 2;
+  }
+  Ok((options, args[i..].to_vec()))
+}
+
+fn parse_output(s: &str) -> Result<AudioOutput, String> {
+  match s {
+    "device" => Ok(AudioOutput::Device),
+    "null" => Ok(AudioOutput::Null),
+    _ => match s.strip_prefix("file:") {
+      Some(dir) if !dir.is_empty() => {
+        // ワーカーは相対パスを dll_dir から解決するため、ここで絶対パスにしておく
+        let dir = std::env::current_dir()
+          .map(|cwd| cwd.join(dir))
+          .unwrap_or_else(|_| PathBuf::from(dir));
+        Ok(AudioOutput::File {
+          dir: dir.to_string_lossy().to_string(),
+        })
+      }
+      _ => Err(format!("出力先を解釈できません: {}", s)),
+    },
+  }
+}
+
+fn parse_command(words: &[String]) -> Result<CliCommand, String> {
+  let (name, args) = words.split_first().ok_or("コマンドがありません")?;
+  let mut ghost = DEFAULT_GHOST.to_string();
+  let mut text = None;
+  let mut voice = None;
+  let mut engine = None;
+
+  let mut i = 0;
+  while i < args.len() {
+    let value = args
+      .get(i + 1)
+      .ok_or_else(|| format!("{} の値がありません", args[i]))?;
+    match args[i].as_str() {
+      "--ghost" => ghost = value.clone(),
+      "--text" => text = Some(value.clone()),
+      "--voice" => voice = Some(parse_voice(value)?),
+      "--engine" => {
+        engine = Some(parse_engine(value).ok_or_else(|| format!("不明なエンジンです: {}", value))?)
+      }
+      other => return Err(format!("不明なオプションです: {}", other)),
+    }
+    i += 2;
+  }
+
+  let text = || {
+    text
+      .clone()
+      .ok_or_else(|| "--text を指定してください".to_string())
+  };
+  match name.as_str() {
+    "speak" => Ok(CliCommand::Speak {
+      ghost: ghost.clone(),
+      text: text()?,
+      voice: voice.clone(),
+    }),
+    "sync" => Ok(CliCommand::Sync {
+      ghost: ghost.clone(),
+      text: text()?,
+      voice: voice.clone(),
+    }),
+    "engines" => Ok(CliCommand::Engines),
+    "voices" => Ok(CliCommand::Voices { engine }),
+    "stop" => Ok(CliCommand::Stop),
+    "help" | "--help" | "-h" => Ok(CliCommand::Help),
+    "quit" | "exit" => Ok(CliCommand::Quit),
+    other => Err(format!("不明なコマンドです: {}", other)),
+  }
+}
+
+/// エンジン名（大文字小文字を区別しない）またはポート番号からエンジンを得る
+fn parse_engine(s: &str) -> Option<Engine> {
+  if let Ok(port) = s.parse::<i32>() {
+    return engine_from_port(port);
+  }
+  ENGINE_LIST
+    .iter()
+    .find(|e| e.name().eq_ignore_ascii_case(s))
+    .copied()
+}
+
+/// "エンジン:話者UUID:スタイルID" 形式の声の指定を読む
+fn parse_voice(s: &str) -> Result<CharacterVoice, String> {
+  let parts: Vec<&str> = s.split(':').collect();
+  let [engine, uuid, style] = parts[..] else {
+    return Err(format!(
+      "声は エンジン:話者UUID:スタイルID の形式で指定してください: {}",
+      s
+    ));
+  };
+  let engine = parse_engine(engine).ok_or_else(|| format!("不明なエンジンです: {}", engine))?;
+  let style_id = style
+    .parse::<i32>()
+    .map_err(|_| format!("スタイルIDが数値ではありません: {}", style))?;
+  Ok(CharacterVoice {
+    port: engine.port(),
+    speaker_uuid: uuid.to_string(),
+    style_id,
+    voice_quality: Default::default(),
+  })
+}
+
+/// 空白で区切る。ダブルクォートで囲んだ部分は1語として扱い、\" と \\ はエスケープとする
+fn split_words(line: &str) -> Vec<String> {
+  let mut words = Vec::new();
+  let mut current = String::new();
+  let mut in_word = false;
+  let mut in_quote = false;
+  let mut chars = line.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '"' => {
+        in_quote = !in_quote;
+        in_word = true;
+      }
+      '\\' if in_quote => match chars.next() {
+        Some(next @ ('"' | '\\')) => current.push(next),
+        Some(next) => {
+          current.push('\\');
+          current.push(next);
+        }
+        None => current.push('\\'),
+      },
+      c if c.is_whitespace() && !in_quote => {
+        if in_word {
+          words.push(std::mem::take(&mut current));
+          in_word = false;
+        }
+      }
+      c => {
+        current.push(c);
+        in_word = true;
+      }
+    }
+  }
+  if in_word {
+    words.push(current);
+  }
+  words
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn words(s: &[&str]) -> Vec<String> {
+    s.iter().map(|w| w.to_string()).collect()
+  }
+
+  #[test]
+  fn split_words_handles_quotes() {
+    assert_eq!(
+      split_words(r#"speak --ghost "Emily 4" --text "こんにちは、\"世界\"""#),
+      words(&[
+        "speak",
+        "--ghost",
+        "Emily 4",
+        "--text",
+        "こんにちは、\"世界\""
+      ])
+    );
+    assert_eq!(
+      split_words(r#"  sync   --text "\h\s[0]" "#),
+      words(&["sync", "--text", r"\h\s[0]"])
+    );
+    assert_eq!(
+      split_words(r#"speak --text """#),
+      words(&["speak", "--text", ""])
+    );
+  }
+
+  #[test]
+  fn parse_voice_accepts_name_or_port() {
+    let voice = parse_voice("voicevox:abc:3").unwrap();
+    assert_eq!(voice.port, Engine::VoiceVox.port());
+    assert_eq!(voice.speaker_uuid, "abc");
+    assert_eq!(voice.style_id, 3);
+    let voice = parse_voice("50032:def:0").unwrap();
+    assert_eq!(voice.port, Engine::CoeiroInkV2.port());
+    assert!(parse_voice("VOICEVOX:abc").is_err());
+    assert!(parse_voice("unknown:abc:1").is_err());
+  }
+
+  #[test]
+  fn parse_output_table() {
+    assert_eq!(parse_output("null"), Ok(AudioOutput::Null));
+    assert_eq!(parse_output("device"), Ok(AudioOutput::Device));
+    match parse_output("file:/tmp/out") {
+      Ok(AudioOutput::File { dir }) => assert_eq!(PathBuf::from(dir), PathBuf::from("/tmp/out")),
+      other => panic!("unexpected output: {:?}", other),
+    }
+    assert!(parse_output("file:").is_err());
+    assert!(parse_output("speaker").is_err());
+  }
+
+  #[test]
+  fn parse_command_table() {
+    match parse_command(&words(&["speak", "--text", "hi"])) {
+      Ok(CliCommand::Speak { ghost, text, voice }) => {
+        assert_eq!(ghost, DEFAULT_GHOST);
+        assert_eq!(text, "hi");
+        assert!(voice.is_none());
+      }
+      other => panic!("unexpected: {:?}", other),
+    }
+    assert!(matches!(
+      parse_command(&words(&["voices", "--engine", "COEIROINKv2"])),
+      Ok(CliCommand::Voices {
+        engine: Some(Engine::CoeiroInkV2)
+      })
+    ));
+    assert!(matches!(
+      parse_command(&words(&["stop"])),
+      Ok(CliCommand::Stop)
+    ));
+    assert!(parse_command(&words(&["sync"])).is_err());
+    assert!(parse_command(&words(&["speak", "--text"])).is_err());
+    assert!(parse_command(&words(&["dance"])).is_err());
+  }
+}
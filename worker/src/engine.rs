@@ -1,5 +1,7 @@
 pub mod bouyomichan;
 pub mod coeiroink_v2;
+#[cfg(test)]
+pub mod mock;
 pub mod voicevox_family;
 
 use async_trait::async_trait;
@@ -8,9 +10,43 @@ use coeiroink_v2::speaker::CoeiroinkV2SpeakerGetter;
 use ghost_speaker_common::{Engine, SpeakerInfo, ENGINE_LIST};
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Mutex as StdMutex;
+use std::sync::RwLock;
 use voicevox_family::speaker::VoicevoxFamilySpeakerGetter;
 
+/// エンジンの接続先の差し替え（テストでモックサーバーに向けるため）
+static ENGINE_ADDRS: Lazy<RwLock<HashMap<Engine, SocketAddr>>> =
+  Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// エンジンのソケットアドレス。差し替えが無ければ 127.0.0.1 の既定ポート
+pub fn engine_addr(engine: Engine) -> SocketAddr {
+  ENGINE_ADDRS
+    .read()
+    .ok()
+    .and_then(|addrs| addrs.get(&engine).copied())
+    .unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), engine.port() as u16))
+}
+
+/// エンジンの HTTP API の URL（末尾の / を含む）
+pub fn engine_base_url(engine: Engine) -> String {
+  match ENGINE_ADDRS
+    .read()
+    .ok()
+    .and_then(|addrs| addrs.get(&engine).copied())
+  {
+    Some(addr) => format!("http://{}/", addr),
+    None => format!("http://localhost:{}/", engine.port()),
+  }
+}
+
+#[cfg(test)]
+pub fn set_engine_addr(engine: Engine, addr: SocketAddr) {
+  if let Ok(mut addrs) = ENGINE_ADDRS.write() {
+    addrs.insert(engine, addr);
+  }
+}
+
 pub static HTTP_CLIENT: Lazy<StdMutex<Option<reqwest::Client>>> = Lazy::new(|| StdMutex::new(None));
 
 /// HTTP_CLIENT を初期化する
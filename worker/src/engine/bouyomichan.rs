@@ -4,13 +4,10 @@ pub mod speaker;
 use ghost_speaker_common::Engine;
 use std::error::Error;
 use std::io::Write;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::net::TcpStream;
 
 pub fn connect() -> Result<TcpStream, Box<dyn Error>> {
-  let address = SocketAddr::new(
-    IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
-    Engine::BouyomiChan.port() as u16,
-  );
+  let address = crate::engine::engine_addr(Engine::BouyomiChan);
   let stream = TcpStream::connect_timeout(&address, std::time::Duration::from_secs(5))?;
 
   Ok(stream)
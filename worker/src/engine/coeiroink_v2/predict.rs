@@ -1,6 +1,6 @@
 use crate::engine::Predictor;
 use async_trait::async_trait;
-use ghost_speaker_common::VoiceQuality;
+use ghost_speaker_common::{Engine, VoiceQuality};
 use http::StatusCode;
 use serde::Serialize;
 
@@ -73,7 +73,10 @@ impl CoeiroinkV2Predictor {
 #[async_trait]
 impl Predictor for CoeiroinkV2Predictor {
   async fn predict(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    const URL: &str = "http://localhost:50032/v1/synthesis";
+    let url = format!(
+      "{}v1/synthesis",
+      crate::engine::engine_base_url(Engine::CoeiroInkV2)
+    );
 
     let req = PredictRequest {
       speaker_uuid: self.speaker_uuid.clone(),
@@ -101,7 +104,7 @@ impl Predictor for CoeiroinkV2Predictor {
 
     let wav: Vec<u8>;
     match client
-      .post(URL)
+      .post(url)
       .header("Content-Type", "application/json")
       .header("Accept", "audio/wav")
       .body(b)
@@ -1,6 +1,6 @@
 use crate::engine::SpeakerGetter;
 use async_trait::async_trait;
-use ghost_speaker_common::{SpeakerInfo, Style};
+use ghost_speaker_common::{Engine, SpeakerInfo, Style};
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
@@ -62,12 +62,15 @@ impl SpeakerGetter for CoeiroinkV2SpeakerGetter {
   async fn get_speakers_info(
     &self,
   ) -> Result<Vec<SpeakerInfo>, Box<dyn std::error::Error + Send + Sync>> {
-    const URL: &str = "http://localhost:50032/v1/speakers";
+    let url = format!(
+      "{}v1/speakers",
+      crate::engine::engine_base_url(Engine::CoeiroInkV2)
+    );
 
     log::debug!("getting speakers info");
     let client =
       crate::engine::get_http_client().ok_or_else(|| "HTTP client not initialized".to_string())?;
-    let body: String = match client.get(URL).send().await {
+    let body: String = match client.get(url).send().await {
       Ok(res) => {
         log::debug!("get_speakers_info success");
         res.text().await?
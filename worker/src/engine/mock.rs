@@ -0,0 +1,499 @@
+//! テスト用の音声合成エンジンのモック。
+//! VOICEVOX・COEIROINKv2 の HTTP API と棒読みちゃんのソケットを実装し、
+//! 入力から決まる WAV を返す
+use crate::engine::set_engine_addr;
+use ghost_speaker_common::Engine;
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::io::Read;
+use std::net::TcpListener as StdTcpListener;
+use std::sync::Mutex as StdMutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+pub const VOICEVOX_SPEAKER_UUID: &str = "7ffcb7ce-00ec-4bdc-82cd-45a8889e43ff";
+pub const VOICEVOX_SPEAKER_NAME: &str = "モック話者";
+pub const COEIROINK_SPEAKER_UUID: &str = "3c37646f-3881-5374-2a83-149267990abc";
+pub const COEIROINK_SPEAKER_NAME: &str = "モックつくよみ";
+
+const SAMPLE_RATE: u32 = 8000;
+/// 1文字あたりの再生時間（ミリ秒）
+const MS_PER_CHAR: u32 = 50;
+
+/// モックが受け取った合成要求
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynthesisRequest {
+  pub engine: Engine,
+  /// VOICEVOX はスタイルID、COEIROINK は "話者UUID:スタイルID"、棒読みちゃんは声質
+  pub voice: String,
+  pub text: String,
+}
+
+pub struct MockEngines {
+  requests: StdMutex<Vec<SynthesisRequest>>,
+}
+
+impl MockEngines {
+  /// これまでに受け取った合成要求のうち、text を含むもの
+  pub fn requests_containing(&self, text: &str) -> Vec<SynthesisRequest> {
+    self
+      .requests
+      .lock()
+      .map(|r| {
+        r.iter()
+          .filter(|req| req.text.contains(text))
+          .cloned()
+          .collect()
+      })
+      .unwrap_or_default()
+  }
+
+  fn record(&self, engine: Engine, voice: String, text: String) {
+    if let Ok(mut r) = self.requests.lock() {
+      r.push(SynthesisRequest {
+        engine,
+        voice,
+        text,
+      });
+    }
+  }
+}
+
+static MOCK: Lazy<MockEngines> = Lazy::new(|| {
+  let voicevox = bind();
+  let coeiroink = bind();
+  let bouyomichan = bind();
+  set_engine_addr(Engine::VoiceVox, voicevox.local_addr().unwrap());
+  set_engine_addr(Engine::CoeiroInkV2, coeiroink.local_addr().unwrap());
+  set_engine_addr(Engine::BouyomiChan, bouyomichan.local_addr().unwrap());
+
+  // テストごとのランタイムが終了しても動き続けるよう、専用のスレッドで動かす
+  std::thread::spawn(move || {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+      .enable_all()
+      .build()
+      .unwrap();
+    runtime.block_on(async move {
+      tokio::spawn(serve_http(voicevox, Engine::VoiceVox));
+      tokio::spawn(serve_http(coeiroink, Engine::CoeiroInkV2));
+      serve_bouyomichan(bouyomichan).await;
+    });
+  });
+
+  crate::engine::init_http_client();
+  MockEngines {
+    requests: StdMutex::new(Vec::new()),
+  }
+});
+
+/// モックを起動し（2回目以降は起動済みのものを返す）、各エンジンの接続先をモックに向ける
+pub fn start() -> &'static MockEngines {
+  &MOCK
+}
+
+fn bind() -> StdTcpListener {
+  let listener = StdTcpListener::bind("127.0.0.1:0").unwrap();
+  listener.set_nonblocking(true).unwrap();
+  listener
+}
+
+/// 入力から決まる 16bit モノラルの WAV。長さは文字数に比例する
+pub fn mock_wav(voice: &str, text: &str) -> Vec<u8> {
+  let chars = text.chars().count().max(1) as u32;
+  let samples = SAMPLE_RATE * MS_PER_CHAR / 1000 * chars;
+  // FNV-1a で種を作り、線形合同法で波形を作る
+  let mut seed = 0xcbf29ce484222325u64;
+  for b in voice.bytes().chain([0]).chain(text.bytes()) {
+    seed = (seed ^ b as u64).wrapping_mul(0x100000001b3);
+  }
+  let data_len = samples * 2;
+  let mut wav = Vec::with_capacity(44 + data_len as usize);
+  wav.extend_from_slice(b"RIFF");
+  wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+  wav.extend_from_slice(b"WAVEfmt ");
+  wav.extend_from_slice(&16u32.to_le_bytes());
+  wav.extend_from_slice(&1u16.to_le_bytes());
+  wav.extend_from_slice(&1u16.to_le_bytes());
+  wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+  wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+  wav.extend_from_slice(&2u16.to_le_bytes());
+  wav.extend_from_slice(&16u16.to_le_bytes());
+  wav.extend_from_slice(b"data");
+  wav.extend_from_slice(&data_len.to_le_bytes());
+  for _ in 0..samples {
+    seed = seed
+      .wrapping_mul(6364136223846793005)
+      .wrapping_add(1442695040888963407);
+    let sample = ((seed >> 48) as i16) / 16;
+    wav.extend_from_slice(&sample.to_le_bytes());
+  }
+  wav
+}
+
+struct HttpRequest {
+  method: String,
+  path: String,
+  query: Vec<(String, String)>,
+  body: Vec<u8>,
+}
+
+impl HttpRequest {
+  fn query(&self, key: &str) -> Option<&str> {
+    self
+      .query
+      .iter()
+      .find(|(k, _)| k == key)
+      .map(|(_, v)| v.as_str())
+  }
+}
+
+async fn serve_http(listener: StdTcpListener, engine: Engine) {
+  let listener = TcpListener::from_std(listener).unwrap();
+  loop {
+    let (stream, _) = match listener.accept().await {
+      Ok(s) => s,
+      Err(_) => continue,
+    };
+    tokio::spawn(async move {
+      let _ = handle_http(stream, engine).await;
+    });
+  }
+}
+
+async fn handle_http(mut stream: TcpStream, engine: Engine) -> std::io::Result<()> {
+  let req = match read_request(&mut stream).await? {
+    Some(r) => r,
+    None => return Ok(()),
+  };
+  let (status, content_type, body) = match engine {
+    Engine::CoeiroInkV2 => route_coeiroink(&req),
+    _ => route_voicevox(&req),
+  };
+  let head = format!(
+    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+    status,
+    content_type,
+    body.len()
+  );
+  stream.write_all(head.as_bytes()).await?;
+  stream.write_all(&body).await?;
+  stream.shutdown().await
+}
+
+type HttpResponse = (&'static str, &'static str, Vec<u8>);
+
+fn ok_json(value: Value) -> HttpResponse {
+  ("200 OK", "application/json", value.to_string().into_bytes())
+}
+
+fn ok_wav(wav: Vec<u8>) -> HttpResponse {
+  ("200 OK", "audio/wav", wav)
+}
+
+fn not_found() -> HttpResponse {
+  ("404 Not Found", "text/plain", b"not found".to_vec())
+}
+
+fn bad_request(message: &str) -> HttpResponse {
+  (
+    "422 Unprocessable Entity",
+    "text/plain",
+    message.as_bytes().to_vec(),
+  )
+}
+
+fn route_voicevox(req: &HttpRequest) -> HttpResponse {
+  match (req.method.as_str(), req.path.as_str()) {
+    ("GET", "/speakers") => ok_json(json!([{
+      "name": VOICEVOX_SPEAKER_NAME,
+      "speaker_uuid": VOICEVOX_SPEAKER_UUID,
+      "styles": [{ "name": "ノーマル", "id": 2 }, { "name": "あまあま", "id": 0 }],
+      "version": "0.0.0-mock",
+    }])),
+    ("POST", "/audio_query") => {
+      let (Some(speaker), Some(text)) = (req.query("speaker"), req.query("text")) else {
+        return bad_request("speaker and text are required");
+      };
+      // 実際の audio_query の一部と、合成時に使う読みを返す
+      ok_json(json!({
+        "accent_phrases": [],
+        "speedScale": 1.0,
+        "pitchScale": 0.0,
+        "intonationScale": 1.0,
+        "volumeScale": 1.0,
+        "outputSamplingRate": SAMPLE_RATE,
+        "outputStereo": false,
+        "kana": text,
+        "speaker": speaker,
+      }))
+    }
+    ("POST", "/synthesis") => {
+      let Some(speaker) = req.query("speaker") else {
+        return bad_request("speaker is required");
+      };
+      let query: Value = match serde_json::from_slice(&req.body) {
+        Ok(v) => v,
+        Err(e) => return bad_request(&e.to_string()),
+      };
+      let text = query["kana"].as_str().unwrap_or_default().to_string();
+      MOCK.record(Engine::VoiceVox, speaker.to_string(), text.clone());
+      ok_wav(mock_wav(speaker, &text))
+    }
+    _ => not_found(),
+  }
+}
+
+fn route_coeiroink(req: &HttpRequest) -> HttpResponse {
+  match (req.method.as_str(), req.path.as_str()) {
+    ("GET", "/v1/speakers") => ok_json(json!([{
+      "speakerName": COEIROINK_SPEAKER_NAME,
+      "speakerUuid": COEIROINK_SPEAKER_UUID,
+      "styles": [{
+        "styleName": "れいせい",
+        "styleId": 0,
+        "base64Icon": "",
+        "base64Portrait": "",
+      }],
+      "version": "0.0.0-mock",
+      "base64Portrait": "",
+    }])),
+    ("POST", "/v1/synthesis") => {
+      let body: Value = match serde_json::from_slice(&req.body) {
+        Ok(v) => v,
+        Err(e) => return bad_request(&e.to_string()),
+      };
+      let (Some(uuid), Some(style_id), Some(text)) = (
+        body["speakerUuid"].as_str(),
+        body["styleId"].as_i64(),
+        body["text"].as_str(),
+      ) else {
+        return bad_request("speakerUuid, styleId and text are required");
+      };
+      let voice = format!("{}:{}", uuid, style_id);
+      MOCK.record(Engine::CoeiroInkV2, voice.clone(), text.to_string());
+      ok_wav(mock_wav(&voice, text))
+    }
+    _ => not_found(),
+  }
+}
+
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<HttpRequest>> {
+  let mut buf = Vec::new();
+  let mut chunk = [0u8; 4096];
+  let head_end = loop {
+    let n = stream.read(&mut chunk).await?;
+    if n == 0 {
+      return Ok(None);
+    }
+    buf.extend_from_slice(&chunk[..n]);
+    if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+      break pos + 4;
+    }
+  };
+
+  let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+  let mut lines = head.split("\r\n");
+  let mut request_line = lines.next().unwrap_or_default().split(' ');
+  let method = request_line.next().unwrap_or_default().to_string();
+  let target = request_line.next().unwrap_or_default();
+  let content_length = lines
+    .filter_map(|l| l.split_once(':'))
+    .find(|(k, _)| k.trim().eq_ignore_ascii_case("content-length"))
+    .and_then(|(_, v)| v.trim().parse::<usize>().ok())
+    .unwrap_or(0);
+
+  let mut body = buf[head_end..].to_vec();
+  while body.len() < content_length {
+    let n = stream.read(&mut chunk).await?;
+    if n == 0 {
+      break;
+    }
+    body.extend_from_slice(&chunk[..n]);
+  }
+
+  let (path, query) = target.split_once('?').unwrap_or((target, ""));
+  Ok(Some(HttpRequest {
+    method,
+    path: path.to_string(),
+    query: parse_query(query),
+    body,
+  }))
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+  query
+    .split('&')
+    .filter(|kv| !kv.is_empty())
+    .map(|kv| {
+      let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+      (percent_decode(k), percent_decode(v))
+    })
+    .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+  let bytes = s.as_bytes();
+  let mut out = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'+' => out.push(b' '),
+      b'%' if i + 2 < bytes.len() => {
+        let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+        match u8::from_str_radix(hex, 16) {
+          Ok(b) => {
+            out.push(b);
+            i += 2;
+          }
+          Err(_) => out.push(b'%'),
+        }
+      }
+      b => out.push(b),
+    }
+    i += 1;
+  }
+  String::from_utf8_lossy(&out).to_string()
+}
+
+/// 棒読みちゃんのソケット: ヘッダ（コマンド・速度・音程・音量・声質・文字コード・長さ）と本文を受け取る
+async fn serve_bouyomichan(listener: StdTcpListener) {
+  let listener = TcpListener::from_std(listener).unwrap();
+  loop {
+    let (stream, _) = match listener.accept().await {
+      Ok(s) => s,
+      Err(_) => continue,
+    };
+    let Ok(stream) = stream.into_std() else {
+      continue;
+    };
+    tokio::task::spawn_blocking(move || {
+      let mut stream = stream;
+      let _ = stream.set_nonblocking(false);
+      let mut data = Vec::new();
+      if stream.read_to_end(&mut data).is_err() {
+        return;
+      }
+      // 長さのフィールドはクライアントの usize の幅に合わせる
+      let len_size = std::mem::size_of::<usize>();
+      if data.len() < 12 + len_size {
+        return;
+      }
+      let voice = i16::from_le_bytes([data[8], data[9]]);
+      let text = String::from_utf8_lossy(&data[12 + len_size..]).to_string();
+      MOCK.record(Engine::BouyomiChan, voice.to_string(), text);
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::bouyomichan::predict::BouyomichanPredictor;
+  use crate::engine::coeiroink_v2::predict::CoeiroinkV2Predictor;
+  use crate::engine::coeiroink_v2::speaker::CoeiroinkV2SpeakerGetter;
+  use crate::engine::voicevox_family::predict::VoicevoxFamilyPredictor;
+  use crate::engine::voicevox_family::speaker::VoicevoxFamilySpeakerGetter;
+  use crate::engine::{Predictor, SpeakerGetter};
+  use crate::player::wav_duration;
+  use ghost_speaker_common::VoiceQuality;
+  use std::time::Duration;
+
+  #[test]
+  fn mock_wav_is_deterministic() {
+    assert_eq!(mock_wav("2", "こんにちは"), mock_wav("2", "こんにちは"));
+    assert_ne!(mock_wav("2", "こんにちは"), mock_wav("0", "こんにちは"));
+    assert_eq!(
+      wav_duration(&mock_wav("2", "こんにちは")),
+      Some(Duration::from_millis(250))
+    );
+  }
+
+  #[test]
+  fn percent_decode_table() {
+    assert_eq!(percent_decode("a+b%20c"), "a b c");
+    assert_eq!(percent_decode("%E3%81%82"), "あ");
+    assert_eq!(percent_decode("100%"), "100%");
+  }
+
+  #[tokio::test]
+  async fn voicevox_speakers_and_synthesis() {
+    let mock = start();
+    let speakers = VoicevoxFamilySpeakerGetter {
+      engine: Engine::VoiceVox,
+    }
+    .get_speakers_info()
+    .await
+    .unwrap();
+    assert_eq!(speakers.len(), 1);
+    assert_eq!(speakers[0].speaker_uuid, VOICEVOX_SPEAKER_UUID);
+    assert_eq!(speakers[0].styles[0].style_id, Some(2));
+
+    let text = "ボイボ テスト&1";
+    let wav = VoicevoxFamilyPredictor::new(
+      Engine::VoiceVox,
+      text.to_string(),
+      2,
+      VoiceQuality::default(),
+    )
+    .predict()
+    .await
+    .unwrap();
+    assert_eq!(wav, mock_wav("2", text));
+    assert_eq!(
+      mock.requests_containing(text),
+      vec![SynthesisRequest {
+        engine: Engine::VoiceVox,
+        voice: "2".to_string(),
+        text: text.to_string(),
+      }]
+    );
+  }
+
+  #[tokio::test]
+  async fn coeiroink_speakers_and_synthesis() {
+    let mock = start();
+    let speakers = CoeiroinkV2SpeakerGetter.get_speakers_info().await.unwrap();
+    assert_eq!(speakers[0].speaker_name, COEIROINK_SPEAKER_NAME);
+
+    let text = "コエイロ テスト";
+    let wav = CoeiroinkV2Predictor::new(
+      text.to_string(),
+      COEIROINK_SPEAKER_UUID.to_string(),
+      0,
+      VoiceQuality::default(),
+    )
+    .predict()
+    .await
+    .unwrap();
+    let voice = format!("{}:0", COEIROINK_SPEAKER_UUID);
+    assert_eq!(wav, mock_wav(&voice, text));
+    assert_eq!(mock.requests_containing(text).len(), 1);
+  }
+
+  #[tokio::test]
+  async fn bouyomichan_receives_text() {
+    let mock = start();
+    let text = "棒読み テスト";
+    let wav = BouyomichanPredictor::new(text.to_string(), 3, 1.0)
+      .predict()
+      .await
+      .unwrap();
+    // 棒読みちゃんは自前で再生するため WAV を返さない
+    assert!(wav.is_empty());
+    // 受信は別スレッドなので少し待つ
+    for _ in 0..50 {
+      if !mock.requests_containing(text).is_empty() {
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(
+      mock.requests_containing(text),
+      vec![SynthesisRequest {
+        engine: Engine::BouyomiChan,
+        voice: "3".to_string(),
+        text: text.to_string(),
+      }]
+    );
+  }
+}
@@ -25,7 +25,7 @@ impl VoicevoxFamilyPredictor {
 #[async_trait]
 impl Predictor for VoicevoxFamilyPredictor {
   async fn predict(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let domain: String = format!("http://localhost:{}/", self.engine.port());
+    let domain: String = crate::engine::engine_base_url(self.engine);
 
     let client =
       crate::engine::get_http_client().ok_or_else(|| "HTTP client not initialized".to_string())?;
@@ -54,7 +54,7 @@ impl SpeakerGetter for VoicevoxFamilySpeakerGetter {
   async fn get_speakers_info(
     &self,
   ) -> Result<Vec<SpeakerInfo>, Box<dyn std::error::Error + Send + Sync>> {
-    let domain: String = format!("http://localhost:{}/", self.engine.port());
+    let domain: String = crate::engine::engine_base_url(self.engine);
 
     log::debug!("getting speakers info");
     let client =
@@ -0,0 +1,356 @@
+use ghost_speaker_common::SubstitutionRule;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+// 同梱の英単語辞書（英単語(小文字),読み）
+static DICTIONARY: Lazy<HashMap<String, String>> = Lazy::new(|| {
+  include_str!("../assets/english_katakana.txt")
+    .lines()
+    .map(|line| line.trim())
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .filter_map(|line| line.split_once(','))
+    .map(|(word, reading)| (word.trim().to_lowercase(), reading.trim().to_string()))
+    .collect()
+});
+// アルファベットの連続（アポストロフィを含む "don't" なども1語とする）
+static LATIN_RUN_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"[A-Za-z]+(?:['’][A-Za-z]+)*").unwrap());
+// キャメルケースの語の区切り（"GhostSpeaker" → "Ghost", "Speaker"）
+static CAMEL_CASE_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"[A-Z]?[a-z]+(?:['’][a-z]+)?|[A-Z]+(?:['’][A-Za-z]+)?").unwrap());
+
+const LETTERS: [&str; 26] = [
+  "エー",
+  "ビー",
+  "シー",
+  "ディー",
+  "イー",
+  "エフ",
+  "ジー",
+  "エイチ",
+  "アイ",
+  "ジェー",
+  "ケー",
+  "エル",
+  "エム",
+  "エヌ",
+  "オー",
+  "ピー",
+  "キュー",
+  "アール",
+  "エス",
+  "ティー",
+  "ユー",
+  "ブイ",
+  "ダブリュー",
+  "エックス",
+  "ワイ",
+  "ゼット",
+];
+
+// ローマ字 → カタカナ（長いものから順に照合する）
+const ROMAJI_TABLE: &[(&str, &str)] = &[
+  ("kya", "キャ"),
+  ("kyu", "キュ"),
+  ("kyo", "キョ"),
+  ("sha", "シャ"),
+  ("shi", "シ"),
+  ("shu", "シュ"),
+  ("she", "シェ"),
+  ("sho", "ショ"),
+  ("sya", "シャ"),
+  ("syu", "シュ"),
+  ("syo", "ショ"),
+  ("cha", "チャ"),
+  ("chi", "チ"),
+  ("chu", "チュ"),
+  ("che", "チェ"),
+  ("cho", "チョ"),
+  ("tya", "チャ"),
+  ("tyu", "チュ"),
+  ("tyo", "チョ"),
+  ("tsu", "ツ"),
+  ("nya", "ニャ"),
+  ("nyu", "ニュ"),
+  ("nyo", "ニョ"),
+  ("hya", "ヒャ"),
+  ("hyu", "ヒュ"),
+  ("hyo", "ヒョ"),
+  ("mya", "ミャ"),
+  ("myu", "ミュ"),
+  ("myo", "ミョ"),
+  ("rya", "リャ"),
+  ("ryu", "リュ"),
+  ("ryo", "リョ"),
+  ("gya", "ギャ"),
+  ("gyu", "ギュ"),
+  ("gyo", "ギョ"),
+  ("jya", "ジャ"),
+  ("jyu", "ジュ"),
+  ("jyo", "ジョ"),
+  ("zya", "ジャ"),
+  ("zyu", "ジュ"),
+  ("zyo", "ジョ"),
+  ("bya", "ビャ"),
+  ("byu", "ビュ"),
+  ("byo", "ビョ"),
+  ("pya", "ピャ"),
+  ("pyu", "ピュ"),
+  ("pyo", "ピョ"),
+  ("ka", "カ"),
+  ("ki", "キ"),
+  ("ku", "ク"),
+  ("ke", "ケ"),
+  ("ko", "コ"),
+  ("sa", "サ"),
+  ("si", "シ"),
+  ("su", "ス"),
+  ("se", "セ"),
+  ("so", "ソ"),
+  ("ta", "タ"),
+  ("ti", "チ"),
+  ("tu", "ツ"),
+  ("te", "テ"),
+  ("to", "ト"),
+  ("na", "ナ"),
+  ("ni", "ニ"),
+  ("nu", "ヌ"),
+  ("ne", "ネ"),
+  ("no", "ノ"),
+  ("ha", "ハ"),
+  ("hi", "ヒ"),
+  ("fu", "フ"),
+  ("hu", "フ"),
+  ("he", "ヘ"),
+  ("ho", "ホ"),
+  ("ma", "マ"),
+  ("mi", "ミ"),
+  ("mu", "ム"),
+  ("me", "メ"),
+  ("mo", "モ"),
+  ("ya", "ヤ"),
+  ("yu", "ユ"),
+  ("yo", "ヨ"),
+  ("ra", "ラ"),
+  ("ri", "リ"),
+  ("ru", "ル"),
+  ("re", "レ"),
+  ("ro", "ロ"),
+  ("wa", "ワ"),
+  ("wo", "ヲ"),
+  ("ga", "ガ"),
+  ("gi", "ギ"),
+  ("gu", "グ"),
+  ("ge", "ゲ"),
+  ("go", "ゴ"),
+  ("za", "ザ"),
+  ("ji", "ジ"),
+  ("zi", "ジ"),
+  ("zu", "ズ"),
+  ("ze", "ゼ"),
+  ("zo", "ゾ"),
+  ("ja", "ジャ"),
+  ("ju", "ジュ"),
+  ("je", "ジェ"),
+  ("jo", "ジョ"),
+  ("da", "ダ"),
+  ("di", "ヂ"),
+  ("du", "ヅ"),
+  ("de", "デ"),
+  ("do", "ド"),
+  ("ba", "バ"),
+  ("bi", "ビ"),
+  ("bu", "ブ"),
+  ("be", "ベ"),
+  ("bo", "ボ"),
+  ("pa", "パ"),
+  ("pi", "ピ"),
+  ("pu", "プ"),
+  ("pe", "ペ"),
+  ("po", "ポ"),
+  ("fa", "ファ"),
+  ("fi", "フィ"),
+  ("fe", "フェ"),
+  ("fo", "フォ"),
+  ("a", "ア"),
+  ("i", "イ"),
+  ("u", "ウ"),
+  ("e", "エ"),
+  ("o", "オ"),
+];
+
+/// テキスト中のアルファベットの連続をカタカナ読みに変換する。
+/// ユーザーの読み替え辞書 → 同梱の辞書 → ローマ字 → 1文字ずつの読み の順に試す
+pub fn english_to_katakana(text: &str, overrides: &[SubstitutionRule]) -> String {
+  let text = to_halfwidth_latin(text);
+  LATIN_RUN_RE
+    .replace_all(&text, |caps: &Captures| {
+      let run = &caps[0];
+      if let Some(reading) = lookup_override(run, overrides) {
+        return reading;
+      }
+      if let Some(reading) = lookup_dictionary(run) {
+        return reading;
+      }
+      // キャメルケースは語ごとに読む
+      CAMEL_CASE_RE
+        .find_iter(run)
+        .map(|m| word_to_katakana(m.as_str(), overrides))
+        .collect::<String>()
+    })
+    .to_string()
+}
+
+fn to_halfwidth_latin(text: &str) -> String {
+  text
+    .chars()
+    .map(|c| match c {
+      'Ａ'..='Ｚ' | 'ａ'..='ｚ' => {
+        char::from_u32(c as u32 - 'Ａ' as u32 + 'A' as u32).unwrap_or(c)
+      }
+      _ => c,
+    })
+    .collect()
+}
+
+fn normalize_apostrophe(word: &str) -> String {
+  word.replace('’', "'").to_lowercase()
+}
+
+fn lookup_override(word: &str, overrides: &[SubstitutionRule]) -> Option<String> {
+  let word = normalize_apostrophe(word);
+  overrides
+    .iter()
+    .find(|rule| normalize_apostrophe(&rule.from) == word)
+    .map(|rule| rule.to.clone())
+}
+
+fn lookup_dictionary(word: &str) -> Option<String> {
+  DICTIONARY.get(&normalize_apostrophe(word)).cloned()
+}
+
+fn word_to_katakana(word: &str, overrides: &[SubstitutionRule]) -> String {
+  if let Some(reading) = lookup_override(word, overrides) {
+    return reading;
+  }
+  if let Some(reading) = lookup_dictionary(word) {
+    return reading;
+  }
+  // 大文字だけの語は略語とみなしてローマ字読みしない
+  let is_acronym = word.len() > 1 && word.chars().all(|c| c.is_ascii_uppercase());
+  if !is_acronym {
+    if let Some(reading) = romaji_to_katakana(&word.to_lowercase()) {
+      return reading;
+    }
+  }
+  spell_letters(word)
+}
+
+fn spell_letters(word: &str) -> String {
+  word
+    .chars()
+    .filter(|c| c.is_ascii_alphabetic())
+    .map(|c| LETTERS[(c.to_ascii_lowercase() as u8 - b'a') as usize])
+    .collect()
+}
+
+fn is_vowel(c: u8) -> bool {
+  matches!(c, b'a' | b'i' | b'u' | b'e' | b'o')
+}
+
+/// ローマ字として読める語をカタカナに変換する。読めない場合は None
+fn romaji_to_katakana(word: &str) -> Option<String> {
+  let bytes = word.as_bytes();
+  let mut result = String::new();
+  let mut i = 0;
+  while i < bytes.len() {
+    let c = bytes[i];
+    let next = bytes.get(i + 1).copied();
+    // 撥音: 母音・y が続かない n
+    if c == b'n' && !next.is_some_and(|n| is_vowel(n) || n == b'y') {
+      result.push('ン');
+      // "nn" の表記も1文字の撥音とする
+      let after = bytes.get(i + 2).copied();
+      i += if next == Some(b'n') && !after.is_some_and(|a| is_vowel(a) || a == b'y') {
+        2
+      } else {
+        1
+      };
+      continue;
+    }
+    // 促音: 同じ子音の連続、または "tch"
+    if !is_vowel(c) && (next == Some(c) || (c == b't' && next == Some(b'c'))) {
+      result.push('ッ');
+      i += 1;
+      continue;
+    }
+    let rest = &word[i..];
+    let (romaji, kana) = ROMAJI_TABLE
+      .iter()
+      .find(|(romaji, _)| rest.starts_with(romaji))?;
+    result.push_str(kana);
+    i += romaji.len();
+  }
+  Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rule(from: &str, to: &str) -> SubstitutionRule {
+    SubstitutionRule {
+      from: from.to_string(),
+      to: to.to_string(),
+    }
+  }
+
+  #[test]
+  fn english_to_katakana_table() {
+    let cases = [
+      ("Hello", "ハロー"),
+      ("hello world", "ハロー ワールド"),
+      ("今日はgood morningです", "今日はグッド モーニングです"),
+      ("ＯＫ", "オーケー"),
+      ("I'm fine", "アイム ファイン"),
+      ("don’t", "ドント"),
+      ("GhostSpeaker", "ゴーストスピーカー"),
+      ("HTML", "エイチティーエムエル"),
+      ("NHK", "エヌエイチケー"),
+      ("sakura", "サクラ"),
+      ("konnichiwa", "コンニチワ"),
+      ("kitto", "キット"),
+      ("matcha", "マッチャ"),
+      ("shinbun", "シンブン"),
+      ("xyz", "エックスワイゼット"),
+      ("日本語だけ", "日本語だけ"),
+    ];
+    for (input, expected) in cases {
+      assert_eq!(
+        english_to_katakana(input, &[]),
+        expected,
+        "input: {}",
+        input
+      );
+    }
+  }
+
+  #[test]
+  fn overrides_take_precedence() {
+    let overrides = [rule("ghost", "ゴースツ"), rule("Ukagaka", "ウカガカ")];
+    assert_eq!(english_to_katakana("Ghost", &overrides), "ゴースツ");
+    assert_eq!(english_to_katakana("UKAGAKA", &overrides), "ウカガカ");
+    assert_eq!(
+      english_to_katakana("GhostSpeaker", &overrides),
+      "ゴースツスピーカー"
+    );
+  }
+
+  #[test]
+  fn romaji_rejects_non_romaji() {
+    assert_eq!(romaji_to_katakana("xyz"), None);
+    assert_eq!(romaji_to_katakana("kanji"), Some("カンジ".to_string()));
+    assert_eq!(romaji_to_katakana("kannon"), Some("カンノン".to_string()));
+  }
+}
@@ -0,0 +1,310 @@
+use ghost_speaker_common::{EmojiFilter, TextFilterSettings};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::ops::Range;
+
+const URL_PLACEHOLDER: &str = "ユーアールエル";
+const PATH_PLACEHOLDER: &str = "パス";
+
+// 同梱の絵文字辞書（絵文字,読み）
+static EMOJI_NAMES: Lazy<HashMap<char, String>> = Lazy::new(|| {
+  include_str!("../assets/emoji_ja.txt")
+    .lines()
+    .map(|line| line.trim())
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .filter_map(|line| line.split_once(','))
+    .filter_map(|(emoji, name)| Some((emoji.trim().chars().next()?, name.trim().to_string())))
+    .collect()
+});
+static URL_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(
+    r#"(?i)(?:https?|ftp|file)://[^\s\x00「」『』（）()<>"'、。]+|www\.[^\s\x00「」『』（）()<>"'、。]+"#,
+  )
+  .unwrap()
+});
+// Windows のパス（C:\... や \\server\...）と、区切りを2つ以上含む Unix 形式のパス
+static PATH_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(concat!(
+    r"(?:[A-Za-z]:|\\\\[\w.\-]+)(?:\\[^\\\s\x00/:*?<>|「」『』（）()、。]+)+\\?",
+    r"|(?:~|\.{1,2})?(?:/[\w.\-]+){2,}/?",
+  ))
+  .unwrap()
+});
+// 顔文字: 括弧の中身と、その両側に付く腕などの記号
+static KAOMOJI_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(concat!(
+    r"([ヽ＼\\٩σ〃ψｏoＯOm┐└]*)",
+    r"([（(][^（()）\n]{1,20}[)）])",
+    r"([ノﾉ／/۶σ☆彡ｏoＯOm┌┘ゞ]*)",
+  ))
+  .unwrap()
+});
+// 括弧を伴わない顔文字（^^ や orz など）
+static BARE_KAOMOJI_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"\^[_\-ｰ]?\^|[oO]rz|OTL|m\(_ _\)m").unwrap());
+// 顔文字の目や口に使われやすい文字
+const KAOMOJI_CHARS: &str = "´｀`・ω∀▽△ﾟ゜дД＾^◕‿≧≦εз∇Ｏ°▼ｰ_＿;；﹏ᴗ︶艸ﾛ口∩*＊〃•ㅂ○●◎≡～〜><＞＜";
+
+/// 読み上げに向かない文字列（URL・パス・絵文字・顔文字）を設定に従って除去・置換する
+pub fn filter_text(text: &str, settings: &TextFilterSettings) -> String {
+  let mut s = text.to_string();
+  if settings.url {
+    s = URL_RE.replace_all(&s, URL_PLACEHOLDER).to_string();
+  }
+  if settings.path {
+    s = replace_paths(&s);
+  }
+  // 顔文字の判定には絵文字を含めたまま行う
+  if settings.kaomoji {
+    s = remove_kaomoji(&s);
+  }
+  match settings.emoji {
+    EmojiFilter::Keep => s,
+    EmojiFilter::Drop => replace_emoji(&s, false),
+    EmojiFilter::Name => replace_emoji(&s, true),
+  }
+}
+
+/// 設定で置換の対象になる URL・パスの範囲（バイト位置）。
+/// 句読点で分割する前の文全体から求め、URL の途中の ? や ! で分割しないようにする
+pub fn link_ranges(text: &str, settings: &TextFilterSettings) -> Vec<Range<usize>> {
+  let mut ranges = Vec::new();
+  if settings.url {
+    ranges.extend(URL_RE.find_iter(text).map(|m| m.range()));
+  }
+  if settings.path {
+    ranges.extend(
+      PATH_RE
+        .find_iter(text)
+        .filter(|m| is_path_start(text, m.start()))
+        .map(|m| m.range()),
+    );
+  }
+  ranges
+}
+
+fn replace_paths(text: &str) -> String {
+  PATH_RE
+    .replace_all(text, |caps: &Captures| {
+      let start = caps.get(0).map(|m| m.start()).unwrap_or(0);
+      if is_path_start(text, start) {
+        PATH_PLACEHOLDER.to_string()
+      } else {
+        caps[0].to_string()
+      }
+    })
+    .to_string()
+}
+
+// "2026/10/17" のように語の途中から始まるものはパスとみなさない
+fn is_path_start(text: &str, start: usize) -> bool {
+  !text[..start]
+    .chars()
+    .last()
+    .is_some_and(|c| c.is_alphanumeric())
+}
+
+fn is_kaomoji(candidate: &str) -> bool {
+  let inner: String = candidate
+    .chars()
+    .skip_while(|c| *c != '(' && *c != '（')
+    .skip(1)
+    .take_while(|c| *c != ')' && *c != '）')
+    .collect();
+  let face_chars = inner.chars().filter(|c| KAOMOJI_CHARS.contains(*c)).count();
+  // ひらがな・漢字・数字や英単語を含むものは普通の括弧書きとみなす
+  let has_words = inner
+    .chars()
+    .any(|c| matches!(c, 'ぁ'..='ゖ' | '一'..='鿿' | '0'..='9'))
+    || inner
+      .split(|c: char| !c.is_ascii_alphabetic())
+      .any(|word| word.len() >= 2);
+  face_chars >= 2 && !has_words
+}
+
+fn remove_kaomoji(text: &str) -> String {
+  let s = KAOMOJI_RE
+    .replace_all(text, |caps: &Captures| {
+      let (whole, face) = (&caps[0], &caps[2]);
+      if !is_kaomoji(face) {
+        return whole.to_string();
+      }
+      // 英単語に続く o・O・m は腕ではなく単語の一部（"Tom(^^)" の "om" など）
+      let (start, end) = caps.get(0).map_or((0, 0), |m| (m.start(), m.end()));
+      let mut kept = String::new();
+      if ends_with_ascii_letter(&text[..start]) {
+        kept.extend(caps[1].chars().take_while(|c| c.is_ascii_alphabetic()));
+      }
+      if starts_with_ascii_letter(&text[end..]) {
+        let suffix: Vec<char> = caps[3]
+          .chars()
+          .rev()
+          .take_while(|c| c.is_ascii_alphabetic())
+          .collect();
+        kept.extend(suffix.into_iter().rev());
+      }
+      kept
+    })
+    .to_string();
+  BARE_KAOMOJI_RE
+    .replace_all(&s, |caps: &Captures| {
+      let m = caps.get(0).map_or(0..0, |m| m.range());
+      // "Borzoi" の "orz" のように英単語の途中にあるものは残す
+      if ends_with_ascii_letter(&s[..m.start]) || starts_with_ascii_letter(&s[m.end..]) {
+        caps[0].to_string()
+      } else {
+        String::new()
+      }
+    })
+    .to_string()
+}
+
+fn ends_with_ascii_letter(text: &str) -> bool {
+  text.chars().last().is_some_and(|c| c.is_ascii_alphabetic())
+}
+
+fn starts_with_ascii_letter(text: &str) -> bool {
+  text.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+}
+
+fn is_emoji(c: char) -> bool {
+  matches!(c as u32,
+    0x1F000..=0x1FAFF // 絵文字・記号・国旗
+    | 0x2700..=0x27BF // 装飾記号
+    | 0x2B50 | 0x2B55 | 0x2B1B | 0x2B1C
+    | 0x231A | 0x231B | 0x23E9..=0x23FA
+  )
+}
+
+/// 絵文字の見た目を変えるだけの結合文字（ZWJ・異体字セレクタ・肌の色など）
+fn is_emoji_modifier(c: char) -> bool {
+  matches!(c as u32, 0x200D | 0xFE0E | 0xFE0F | 0x20E3 | 0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F)
+}
+
+fn replace_emoji(text: &str, with_name: bool) -> String {
+  let chars: Vec<char> = text.chars().collect();
+  let mut result = String::with_capacity(text.len());
+  let mut i = 0;
+  while i < chars.len() {
+    let c = chars[i];
+    // ☀ などの記号は異体字セレクタが付いたときだけ絵文字とみなす
+    let is_symbol_emoji =
+      matches!(c as u32, 0x2600..=0x26FF) && chars.get(i + 1) == Some(&'\u{FE0F}');
+    if !is_emoji(c) && !is_symbol_emoji {
+      if !is_emoji_modifier(c) {
+        result.push(c);
+      }
+      i += 1;
+      continue;
+    }
+    // ZWJ でつながった絵文字の並びは1つの絵文字として扱う
+    let mut j = i + 1;
+    while j < chars.len() {
+      if chars[j] == '\u{200D}' && j + 1 < chars.len() {
+        j += 2;
+      } else if is_emoji_modifier(chars[j]) {
+        j += 1;
+      } else {
+        break;
+      }
+    }
+    // 国旗（地域指示記号の組）
+    if (0x1F1E6..=0x1F1FF).contains(&(c as u32))
+      && chars
+        .get(j)
+        .is_some_and(|n| (0x1F1E6..=0x1F1FF).contains(&(*n as u32)))
+    {
+      j += 1;
+    }
+    if with_name {
+      if let Some(name) = EMOJI_NAMES.get(&c) {
+        result.push_str(name);
+      }
+    }
+    i = j;
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn settings(emoji: EmojiFilter) -> TextFilterSettings {
+    TextFilterSettings {
+      emoji,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn filter_table() {
+    let cases = [
+      // URL
+      (
+        "詳しくは https://example.com/a?b=1 を見てね",
+        "詳しくは ユーアールエル を見てね",
+      ),
+      ("www.example.com です", "ユーアールエル です"),
+      ("「http://example.com」", "「ユーアールエル」"),
+      // パス
+      (r"C:\Users\foo\bar.txt を開いた", "パス を開いた"),
+      (r"\\server\share\file", "パス"),
+      ("/usr/local/bin を見て", "パス を見て"),
+      ("1/2 と A/B はそのまま", "1/2 と A/B はそのまま"),
+      ("2026/10/17 はそのまま", "2026/10/17 はそのまま"),
+      // 顔文字
+      ("こんにちは(´・ω・`)", "こんにちは"),
+      ("やった＼(^o^)／", "やった"),
+      ("よろしく(｀・ω・´)ゞ", "よろしく"),
+      ("ありがと ヽ(・∀・)ノ", "ありがと "),
+      ("すみません m(_ _)m", "すみません "),
+      ("痛い(>_<)", "痛い"),
+      ("(example)", "(example)"),
+      ("いいね^^", "いいね"),
+      ("もうだめ orz", "もうだめ "),
+      ("Boom(^_^)", "Boom"),
+      ("memo(・ω・)", "memo"),
+      ("Tom(^^)見て", "Tom見て"),
+      ("(^_^)oh yes", "oh yes"),
+      ("Borzoi", "Borzoi"),
+      ("ほんと(笑)", "ほんと(笑)"),
+      ("注意(1)", "注意(1)"),
+      ("(ただし例外あり)", "(ただし例外あり)"),
+      // 絵文字
+      ("すごい🎉", "すごい"),
+      ("はい👍🏻", "はい"),
+      ("家族👨‍👩‍👧です", "家族です"),
+      ("晴れ☀️", "晴れ"),
+      ("日本🇯🇵", "日本"),
+      ("星★と音符♪は残す", "星★と音符♪は残す"),
+    ];
+    let s = settings(EmojiFilter::Drop);
+    for (input, expected) in cases {
+      assert_eq!(filter_text(input, &s), expected, "input: {}", input);
+    }
+  }
+
+  #[test]
+  fn emoji_names() {
+    let s = settings(EmojiFilter::Name);
+    assert_eq!(filter_text("おめでとう🎉", &s), "おめでとうクラッカー");
+    assert_eq!(filter_text("いいね👍🏻", &s), "いいねサムズアップ");
+    assert_eq!(filter_text("好き❤️", &s), "好き赤いハート");
+    // 辞書にない絵文字は除去する
+    assert_eq!(filter_text("🦕", &s), "");
+  }
+
+  #[test]
+  fn categories_can_be_disabled() {
+    let s = TextFilterSettings {
+      url: false,
+      path: false,
+      emoji: EmojiFilter::Keep,
+      kaomoji: false,
+    };
+    let text = r"https://example.com C:\a\b (´・ω・`) 🎉";
+    assert_eq!(filter_text(text, &s), text);
+  }
+}
@@ -1,5 +1,7 @@
+use ghost_speaker_common::ChoiceReading;
 use once_cell::sync::Lazy;
 use regex::Regex;
+use std::ops::Range;
 
 static LINES_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(\\n(\[[^\]]+\])?)+").unwrap());
 static DELIMS_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"[！!?？。]").unwrap());
@@ -14,6 +16,22 @@ static SAKURA_SCRIPT_RE: Lazy<Regex> = Lazy::new(|| {
 });
 static QUICK_SECTION_TAG_RE: Lazy<Regex> =
   Lazy::new(|| Regex::new(r"(\\_q|\\!\[quicksection,(0|1|true|false)\])").unwrap());
+// 選択肢・アンカー領域の開始タグ（\__q[...] / \_a[...]）
+static CHOICE_START_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"\\(__q|_a)\[(\\\]|[^\]])*\]").unwrap());
+// 選択肢・アンカーを含むかの判定用（旧形式の \q[...] も含む）
+static CHOICE_ANY_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\\(__q|_a|q)\[").unwrap());
+// 分割しない範囲の中の区切り文字を、分割の間だけ置き換えておく私用領域の文字
+const PROTECTED_CHARS: [(char, char); 8] = [
+  ('！', '\u{F0000}'),
+  ('!', '\u{F0001}'),
+  ('?', '\u{F0002}'),
+  ('？', '\u{F0003}'),
+  ('。', '\u{F0004}'),
+  ('…', '\u{F0005}'),
+  ('・', '\u{F0006}'),
+  ('.', '\u{F0007}'),
+];
 
 pub struct Dialog {
   pub text: String,
@@ -21,7 +39,11 @@ pub struct Dialog {
   pub scope: usize,
 }
 
-pub fn split_dialog(src: String, devide_by_lines: bool) -> Vec<Dialog> {
+pub fn split_dialog(
+  src: String,
+  devide_by_lines: bool,
+  choice_reading: ChoiceReading,
+) -> Vec<Dialog> {
   let lines_re = &*LINES_RE;
 
   // raw_text 用: \_qタグだけ除去しテキスト内容は保持、。挿入前
@@ -29,6 +51,13 @@ pub fn split_dialog(src: String, devide_by_lines: bool) -> Vec<Dialog> {
 
   let mut s = delete_quick_section(src);
 
+  // 選択肢の除去はTTS用テキストのみに適用する（raw_textはバルーン表示用なので保持）
+  s = match choice_reading {
+    ChoiceReading::All => s,
+    ChoiceReading::SkipChoices => delete_choice_sections(s),
+    ChoiceReading::ProseOnly => delete_choice_lines(s),
+  };
+
   if devide_by_lines {
     // \0（null文字）を行区切りマーカーとして使用する。
     // 以前は「。」を挿入していたが、raw_textには「。」が存在しないため、
@@ -121,7 +150,7 @@ pub fn resplit_pairs_by_raw_ellipsis(pairs: Vec<(String, String)>) -> Vec<(Strin
     let rt_clean = clear_tags(rt.clone());
     if !t.is_empty() && !ELLIPSIS_RE.is_match(&t) && ELLIPSIS_RE.is_match(&rt_clean) {
       // \_q内の省略記号: raw-cleanベースで再分割
-      let new_pairs = split_by_punctuation_with_raw(rt_clean.clone(), rt);
+      let new_pairs = split_by_punctuation_with_raw(rt_clean.clone(), rt, &[]);
 
       // rt_cleanの各文字が元のtに含まれるかをマッピング
       // tはrt_cleanの部分列（quicksection内容除去後）なので、
@@ -163,7 +192,42 @@ pub fn resplit_pairs_by_raw_ellipsis(pairs: Vec<(String, String)>) -> Vec<(Strin
   result
 }
 
-pub fn split_by_punctuation(src: String) -> Vec<String> {
+/// 句読点と省略記号で分割する。keep の範囲（バイト位置）の中では分割しない
+pub fn split_by_punctuation(src: String, keep: &[Range<usize>]) -> Vec<String> {
+  if keep.is_empty() {
+    return split_by_delimiters(src);
+  }
+  let protected: String = src
+    .char_indices()
+    .map(|(i, c)| {
+      if keep.iter().any(|r| r.contains(&i)) {
+        protect_char(c)
+      } else {
+        c
+      }
+    })
+    .collect();
+  split_by_delimiters(protected)
+    .into_iter()
+    .map(|s| s.chars().map(restore_char).collect())
+    .collect()
+}
+
+fn protect_char(c: char) -> char {
+  PROTECTED_CHARS
+    .iter()
+    .find(|(orig, _)| *orig == c)
+    .map_or(c, |(_, protected)| *protected)
+}
+
+fn restore_char(c: char) -> char {
+  PROTECTED_CHARS
+    .iter()
+    .find(|(_, protected)| *protected == c)
+    .map_or(c, |(orig, _)| *orig)
+}
+
+fn split_by_delimiters(src: String) -> Vec<String> {
   let t = DELIMS_RE.replace_all(&src, "$0\u{0}").to_string();
   let mut result = Vec::new();
   for text in t.split('\u{0}') {
@@ -180,8 +244,12 @@ pub fn split_by_punctuation(src: String) -> Vec<String> {
   result
 }
 
-pub fn split_by_punctuation_with_raw(clean: String, raw: String) -> Vec<(String, String)> {
-  let clean_segments = split_by_punctuation(clean);
+pub fn split_by_punctuation_with_raw(
+  clean: String,
+  raw: String,
+  keep: &[Range<usize>],
+) -> Vec<(String, String)> {
+  let clean_segments = split_by_punctuation(clean, keep);
   if clean_segments.len() <= 1 {
     return vec![(clean_segments.into_iter().next().unwrap_or_default(), raw)];
   }
@@ -412,3 +480,141 @@ fn delete_quick_section(src: String) -> String {
 fn strip_quick_section_tags_only(src: String) -> String {
   QUICK_SECTION_TAG_RE.replace_all(&src, "").to_string()
 }
+
+/// 文字列中のスコープ切り替えタグだけを連結して返す。
+/// 除去する領域にスコープタグが含まれていても、raw_textとのスコープ対応を崩さないために使う
+fn scope_tags_in(src: &str) -> String {
+  CHANGE_SCOPE_RE
+    .find_iter(src)
+    .map(|m| m.as_str())
+    .collect::<Vec<_>>()
+    .join("")
+}
+
+/// 選択肢（\__q[...]～\__q）とアンカー（\_a[...]～\_a）の領域をタグごと削除する。
+/// 旧形式の \q[ラベル,ID] はラベルがタグ内にあるため clear_tags で除去される。
+fn delete_choice_sections(src: String) -> String {
+  let mut result = String::new();
+  let mut rest = src.as_str();
+  while let Some(cap) = CHOICE_START_RE.captures(rest) {
+    let (start, end) = match cap.get(0) {
+      Some(m) => (m.start(), m.end()),
+      None => break,
+    };
+    let closing = format!("\\{}", &cap[1]);
+    result.push_str(&rest[..start]);
+    let after = &rest[end..];
+    // 終了タグを探す（引数付きの同名タグは次の開始タグなので除外）
+    let mut search_from = 0;
+    let mut close_pos = None;
+    while let Some(i) = after[search_from..].find(&closing) {
+      let pos = search_from + i;
+      if after[pos + closing.len()..].starts_with('[') {
+        search_from = pos + closing.len();
+        continue;
+      }
+      close_pos = Some(pos);
+      break;
+    }
+    match close_pos {
+      Some(pos) => {
+        result.push_str(&scope_tags_in(&after[..pos]));
+        rest = &after[pos + closing.len()..];
+      }
+      None => {
+        // 終了タグがない場合は末尾まで選択肢とみなす
+        result.push_str(&scope_tags_in(after));
+        rest = "";
+      }
+    }
+  }
+  result.push_str(rest);
+  result
+}
+
+/// 選択肢・アンカーを含む行を削除し、地の文だけを残す
+fn delete_choice_lines(src: String) -> String {
+  let mut result = String::new();
+  let mut last_end = 0;
+  let push_line = |line: &str, result: &mut String| {
+    if CHOICE_ANY_RE.is_match(line) {
+      result.push_str(&scope_tags_in(line));
+    } else {
+      result.push_str(line);
+    }
+  };
+  for m in LINES_RE.find_iter(&src) {
+    push_line(&src[last_end..m.start()], &mut result);
+    result.push_str(m.as_str());
+    last_end = m.end();
+  }
+  push_line(&src[last_end..], &mut result);
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn texts(dialogs: &[Dialog]) -> Vec<String> {
+    dialogs.iter().map(|d| d.text.clone()).collect()
+  }
+
+  #[test]
+  fn choices_are_read_by_default() {
+    let src = "何する？\\n\\__q[OnTalk]話して\\__q".to_string();
+    let dialogs = split_dialog(src, false, ChoiceReading::All);
+    assert_eq!(texts(&dialogs), vec!["何する？話して"]);
+  }
+
+  #[test]
+  fn skip_choices_removes_labels() {
+    let src = "何する？\\n\\__q[OnTalk,a]話して\\__q \\q[おわり,OnEnd]".to_string();
+    let dialogs = split_dialog(src, false, ChoiceReading::SkipChoices);
+    assert_eq!(texts(&dialogs), vec!["何する？ "]);
+  }
+
+  #[test]
+  fn skip_choices_removes_anchor() {
+    let src = "これは\\_a[OnAnchor]リンク\\_aです".to_string();
+    assert_eq!(delete_choice_sections(src), "これはです");
+  }
+
+  #[test]
+  fn skip_choices_keeps_raw_text() {
+    let src = "選んで\\__q[OnTalk]話して\\__q".to_string();
+    let dialogs = split_dialog(src, false, ChoiceReading::SkipChoices);
+    assert_eq!(dialogs[0].text, "選んで");
+    assert_eq!(dialogs[0].raw_text, "選んで\\__q[OnTalk]話して\\__q");
+  }
+
+  #[test]
+  fn skip_choices_unclosed_section() {
+    let src = "選んで\\__q[OnTalk]話して".to_string();
+    assert_eq!(delete_choice_sections(src), "選んで");
+  }
+
+  #[test]
+  fn skip_choices_keeps_scope_tags() {
+    let src = "\\__q[OnTalk]あ\\1い\\__qう".to_string();
+    assert_eq!(delete_choice_sections(src), "\\1う");
+  }
+
+  #[test]
+  fn prose_only_removes_choice_lines() {
+    let src =
+      "何する？\\n\\![*]\\__q[OnTalk]話して\\__q\\n【\\q[おわり,OnEnd]】\\n以上".to_string();
+    let dialogs = split_dialog(src, false, ChoiceReading::ProseOnly);
+    assert_eq!(texts(&dialogs), vec!["何する？以上"]);
+  }
+
+  #[test]
+  fn prose_only_keeps_scope_tags() {
+    let src = "あ\\n\\1\\__q[OnTalk]い\\__q\\nう".to_string();
+    let dialogs = split_dialog(src, false, ChoiceReading::ProseOnly);
+    assert_eq!(dialogs.len(), 2);
+    assert_eq!(dialogs[0].text, "あ");
+    assert_eq!(dialogs[1].scope, 1);
+    assert_eq!(dialogs[1].text, "う");
+  }
+}
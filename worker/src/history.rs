@@ -0,0 +1,174 @@
+//! 読み上げ履歴: ゴーストごとに直近のトークを合成済みの音声ごと保持し、聞き直せるようにする
+use ghost_speaker_common::{CharacterVoice, SpeechHistoryEntry, SpeechHistoryLine};
+use log::error;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex as StdMutex;
+
+/// ゴーストごとに保持するトークの数
+pub const HISTORY_LIMIT: usize = 10;
+
+/// ゴーストごとのトーク。新しいものが先頭
+static HISTORY: Lazy<StdMutex<HashMap<String, VecDeque<Talk>>>> =
+  Lazy::new(|| StdMutex::new(HashMap::new()));
+static NEXT_TALK_ID: AtomicU64 = AtomicU64::new(1);
+
+/// 読み上げたセグメント。棒読みちゃんなど音声を返さないエンジンでは wav が空
+pub struct SpokenSegment {
+  pub scope: usize,
+  pub text: String,
+  pub voice: CharacterVoice,
+  pub wav: Vec<u8>,
+}
+
+struct Talk {
+  id: u64,
+  segments: Vec<SpokenSegment>,
+}
+
+impl Talk {
+  fn to_entry(&self) -> SpeechHistoryEntry {
+    // 同じキャラクターが続くセグメントは1つの台詞にまとめる
+    let mut lines: Vec<SpeechHistoryLine> = Vec::new();
+    for seg in &self.segments {
+      match lines.last_mut() {
+        Some(line) if line.scope == seg.scope => line.text.push_str(&seg.text),
+        _ => lines.push(SpeechHistoryLine {
+          scope: seg.scope,
+          text: seg.text.clone(),
+          voice: seg.voice.clone(),
+        }),
+      }
+    }
+    SpeechHistoryEntry {
+      id: self.id,
+      lines,
+      replayable: self.segments.iter().any(|seg| !seg.wav.is_empty()),
+    }
+  }
+}
+
+/// 読み上げたトークを履歴に加える
+pub fn push(ghost_name: &str, segments: Vec<SpokenSegment>) {
+  let segments: Vec<SpokenSegment> = segments
+    .into_iter()
+    .filter(|seg| !seg.text.is_empty())
+    .collect();
+  if segments.is_empty() {
+    return;
+  }
+  let talk = Talk {
+    id: NEXT_TALK_ID.fetch_add(1, Ordering::Relaxed),
+    segments,
+  };
+  match HISTORY.lock() {
+    Ok(mut history) => {
+      let talks = history.entry(ghost_name.to_string()).or_default();
+      talks.push_front(talk);
+      talks.truncate(HISTORY_LIMIT);
+    }
+    Err(e) => error!("Failed to lock HISTORY: {}", e),
+  }
+}
+
+/// ゴーストの履歴を新しい順に返す
+pub fn entries(ghost_name: &str) -> Vec<SpeechHistoryEntry> {
+  match HISTORY.lock() {
+    Ok(history) => history
+      .get(ghost_name)
+      .map(|talks| talks.iter().map(Talk::to_entry).collect())
+      .unwrap_or_default(),
+    Err(e) => {
+      error!("Failed to lock HISTORY: {}", e);
+      Vec::new()
+    }
+  }
+}
+
+/// 履歴のトークのキャッシュした音声を返す。id が None なら直前のトーク
+pub fn cached_wavs(ghost_name: &str, id: Option<u64>) -> Result<Vec<Vec<u8>>, String> {
+  let history = HISTORY
+    .lock()
+    .map_err(|e| format!("Failed to lock HISTORY: {}", e))?;
+  let talk = history
+    .get(ghost_name)
+    .and_then(|talks| match id {
+      Some(id) => talks.iter().find(|t| t.id == id),
+      None => talks.front(),
+    })
+    .ok_or_else(|| "履歴が見つかりません".to_string())?;
+  let wavs: Vec<Vec<u8>> = talk
+    .segments
+    .iter()
+    .filter(|seg| !seg.wav.is_empty())
+    .map(|seg| seg.wav.clone())
+    .collect();
+  if wavs.is_empty() {
+    return Err("音声がキャッシュされていません".to_string());
+  }
+  Ok(wavs)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn seg(scope: usize, text: &str, wav: &[u8]) -> SpokenSegment {
+    SpokenSegment {
+      scope,
+      text: text.to_string(),
+      voice: CharacterVoice::no_voice(),
+      wav: wav.to_vec(),
+    }
+  }
+
+  #[test]
+  fn keeps_recent_talks_and_replays_cached_audio() {
+    let ghost = "HistoryGhost";
+    for i in 0..HISTORY_LIMIT + 2 {
+      push(ghost, vec![seg(0, &format!("{}回目", i), &[i as u8])]);
+    }
+    let entries = entries(ghost);
+    assert_eq!(entries.len(), HISTORY_LIMIT);
+    assert_eq!(
+      entries[0].lines[0].text,
+      format!("{}回目", HISTORY_LIMIT + 1)
+    );
+    assert_eq!(
+      cached_wavs(ghost, None),
+      Ok(vec![vec![(HISTORY_LIMIT + 1) as u8]])
+    );
+    assert_eq!(
+      cached_wavs(ghost, Some(entries[3].id)),
+      Ok(vec![vec![(HISTORY_LIMIT - 2) as u8]])
+    );
+
+    // 棒読みちゃんのトークは表示できるが再生し直せない
+    push(ghost, vec![seg(0, "こんにちは", &[]), seg(0, "", &[1])]);
+    let entries = super::entries(ghost);
+    assert!(!entries[0].replayable);
+    assert!(cached_wavs(ghost, None).is_err());
+  }
+
+  #[test]
+  fn groups_lines_by_character() {
+    let ghost = "HistoryLinesGhost";
+    push(
+      ghost,
+      vec![
+        seg(0, "こんにちは。", &[1]),
+        seg(0, "元気？", &[2]),
+        seg(1, "はい。", &[3]),
+      ],
+    );
+    let entry = &entries(ghost)[0];
+    assert!(entry.replayable);
+    let lines: Vec<(usize, &str)> = entry
+      .lines
+      .iter()
+      .map(|l| (l.scope, l.text.as_str()))
+      .collect();
+    assert_eq!(lines, [(0, "こんにちは。元気？"), (1, "はい。")]);
+  }
+}
@@ -0,0 +1,28 @@
+use ghost_speaker_common::{Response, WorkerEvent, WorkerMessage};
+use log::error;
+use std::io::Write;
+
+/// DLL へ1行のメッセージを書き出す。
+/// 応答と通知が別スレッドから送られても行が混ざらないよう、1行ごとに stdout をロックする
+pub fn send_message(msg: &WorkerMessage) {
+  let json = match serde_json::to_string(msg) {
+    Ok(j) => j,
+    Err(e) => {
+      error!("Failed to serialize message: {}", e);
+      return;
+    }
+  };
+  let mut writer = std::io::stdout().lock();
+  if let Err(e) = writeln!(writer, "{}", json).and_then(|_| writer.flush()) {
+    error!("Failed to write message: {}", e);
+  }
+}
+
+pub fn send_reply(id: u64, response: Response) {
+  send_message(&WorkerMessage::Reply { id, response });
+}
+
+/// DLL へ通知を送る
+pub fn send_event(event: WorkerEvent) {
+  send_message(&WorkerMessage::Event { event });
+}
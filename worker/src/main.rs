@@ -1,24 +1,41 @@
 mod engine;
+mod english;
+mod filter;
 mod format;
+mod history;
+mod ipc;
+mod migration;
+mod normalize;
 mod player;
 mod queue;
+mod recorder;
+mod settings;
+mod subtitle;
 mod system;
 
-use ghost_speaker_common::{Command, Response, SegmentInfo, SyncState};
-use log::{debug, error, info};
+use ghost_speaker_common::{
+  features, missing_features, Command, Request, Response, SegmentInfo, SyncState, WorkerMessage,
+  PROTOCOL_VERSION,
+};
+use log::{debug, error, info, warn};
 use simplelog::{Config, LevelFilter, WriteLogger};
-use std::io::{BufRead, Write};
+use std::io::BufRead;
 use std::sync::atomic::Ordering;
 use std::time::Duration;
 
 use crate::format::is_ellipsis_segment;
 use crate::queue::{
   build_segments, cancel_sync_playback, is_sync_audio_done, pop_ready_segment, push_to_prediction,
-  spawn_sync_playback, spawn_sync_prediction, SyncSegment, CURRENT_CONNECTION_STATUS,
-  ENGINE_AUTO_START, ENGINE_PATH, GHOSTS_VOICES, INITIAL_VOICE, SHUTTING_DOWN, SPEAKERS_INFO,
-  SPEAK_BY_PUNCTUATION, SYNC_STATE, VOLUME,
+  spawn_sync_playback, spawn_sync_prediction, sync_audio_remaining, CURRENT_CONNECTION_STATUS,
+  SHUTTING_DOWN, SPEAKERS_INFO, SYNC_STATE,
 };
 
+/// パースできなかったリクエストから id だけを読み取る
+#[derive(serde::Deserialize)]
+struct RequestId {
+  id: u64,
+}
+
 /// ワーカーの状態を保持する構造体
 struct WorkerState {
   runtime_handle: tokio::runtime::Handle,
@@ -36,11 +53,9 @@ fn main() {
 
   let handle = runtime.handle().clone();
 
-  // stdin/stdout を取得
+  // stdin を取得（stdout への書き込みは ipc モジュールが行う）
   let stdin = std::io::stdin();
-  let stdout = std::io::stdout();
   let reader = stdin.lock();
-  let mut writer = stdout.lock();
 
   let mut lines = reader.lines();
 
@@ -57,24 +72,61 @@ fn main() {
     }
   };
 
-  let init_cmd: Command = match serde_json::from_str(&init_line) {
-    Ok(cmd) => cmd,
+  let init_req: Request = match serde_json::from_str(&init_line) {
+    Ok(req) => req,
     Err(e) => {
+      // 形式の異なる古いDLLからの Init はここで失敗する。
+      // 古い形式で自身のバージョンを返し、DLL 側でバージョン不一致として扱わせる
       eprintln!("Failed to parse init command: {}", e);
+      let resp = match serde_json::from_str::<Command>(&init_line) {
+        Ok(Command::Init { .. }) => Response::Initialized {
+          protocol_version: PROTOCOL_VERSION,
+          features: features(),
+        },
+        _ => Response::Error {
+          message: format!("Failed to parse Init command: {}", e),
+        },
+      };
+      if let Ok(json) = serde_json::to_string(&resp) {
+        println!("{}", json);
+      }
       std::process::exit(1);
     }
   };
-
-  let (dll_dir, config) = match init_cmd {
-    Command::Init { dll_dir, config } => (dll_dir, config),
+  let init_id = init_req.id;
+
+  let (dll_dir, config, dll_features) = match init_req.command {
+    Command::Init {
+      protocol_version, ..
+    } if protocol_version != PROTOCOL_VERSION => {
+      // バージョンが異なる場合は初期化せずに自身のバージョンだけを返して終了する
+      eprintln!(
+        "Protocol version mismatch: dll={}, worker={}",
+        protocol_version, PROTOCOL_VERSION
+      );
+      ipc::send_reply(
+        init_id,
+        Response::Initialized {
+          protocol_version: PROTOCOL_VERSION,
+          features: features(),
+        },
+      );
+      std::process::exit(1);
+    }
+    Command::Init {
+      dll_dir,
+      config,
+      features,
+      ..
+    } => (dll_dir, config, features),
     other => {
       // Init以外が来た場合、エラーを返す
-      let resp = Response::Error {
-        message: format!("Expected Init command, got: {:?}", other),
-      };
-      let _ = serde_json::to_writer(&mut writer, &resp);
-      let _ = writeln!(writer);
-      let _ = writer.flush();
+      ipc::send_reply(
+        init_id,
+        Response::Error {
+          message: format!("Expected Init command, got: {:?}", other),
+        },
+      );
       std::process::exit(1);
     }
   };
@@ -86,38 +138,17 @@ fn main() {
   }
 
   info!("Worker started, dll_dir={}", dll_dir);
-
-  // 設定をグローバル状態に反映
-  if let Ok(mut v) = VOLUME.write() {
-    *v = config.volume;
-  }
-  if let Ok(mut sbp) = SPEAK_BY_PUNCTUATION.write() {
-    *sbp = config.speak_by_punctuation;
+  let unsupported = missing_features(&dll_features);
+  if !unsupported.is_empty() {
+    info!("Features not supported by DLL: {}", unsupported.join(", "));
   }
-  if let Ok(mut gv) = GHOSTS_VOICES.write() {
-    *gv = config.ghosts_voices;
-  }
-  if let Ok(mut iv) = INITIAL_VOICE.write() {
-    *iv = config.initial_voice;
-  }
-  if let Ok(mut ea) = ENGINE_AUTO_START.write() {
-    *ea = config.engine_auto_start;
-  }
-  if let Ok(mut ep) = ENGINE_PATH.write() {
-    *ep = config.engine_path;
-    // Remove corrupted paths that point to the worker itself
-    if let Ok(current_exe) = std::env::current_exe() {
-      ep.retain(|engine, path| {
-        let is_self = std::path::Path::new(path) == current_exe.as_path();
-        if is_self {
-          log::warn!(
-            "Removing corrupted engine path for {}: points to worker itself",
-            engine.name()
-          );
-        }
-        !is_self
-      });
-    }
+
+  // 設定を読み込み、グローバル状態に反映
+  settings::init(&dll_dir, config.persist_settings);
+  player::set_audio_output(&config.audio_output, &dll_dir);
+  recorder::init(&dll_dir);
+  if let Ok(mut cs) = queue::CURRENT_SHELLS.write() {
+    *cs = config.current_shells;
   }
 
   // キューを初期化
@@ -125,12 +156,10 @@ fn main() {
 
   // エンジン自動起動
   {
-    let auto_start = ENGINE_AUTO_START.read().map(|ea| ea.clone()).unwrap_or_default();
-    let engine_path = ENGINE_PATH.read().map(|ep| ep.clone()).unwrap_or_default();
-
-    for (engine, should_start) in &auto_start {
+    let settings = settings::get();
+    for (engine, should_start) in &settings.engine_auto_start {
       if *should_start {
-        match system::boot_engine(*engine, &engine_path) {
+        match system::boot_engine(*engine, &settings.engine_path) {
           Ok(()) => info!("Auto-started engine: {}", engine.name()),
           Err(e) => error!("Failed to auto-start engine {}: {}", engine.name(), e),
         }
@@ -139,10 +168,13 @@ fn main() {
   }
 
   // Init の応答を返す
-  let resp = Response::Ok;
-  let _ = serde_json::to_writer(&mut writer, &resp);
-  let _ = writeln!(writer);
-  let _ = writer.flush();
+  ipc::send_reply(
+    init_id,
+    Response::Initialized {
+      protocol_version: PROTOCOL_VERSION,
+      features: features(),
+    },
+  );
 
   let mut state = WorkerState {
     runtime_handle: handle.clone(),
@@ -163,25 +195,25 @@ fn main() {
       continue;
     }
 
-    let cmd: Command = match serde_json::from_str(&line) {
-      Ok(c) => c,
+    let req: Request = match serde_json::from_str(&line) {
+      Ok(r) => r,
       Err(e) => {
         error!("Failed to parse command: {}", e);
-        let resp = Response::Error {
-          message: format!("Parse error: {}", e),
-        };
-        let _ = serde_json::to_writer(&mut writer, &resp);
-        let _ = writeln!(writer);
-        let _ = writer.flush();
+        // id だけでも読めれば、DLL がタイムアウトまで待たないようエラーを返す
+        if let Ok(RequestId { id }) = serde_json::from_str(&line) {
+          ipc::send_reply(
+            id,
+            Response::Error {
+              message: format!("Parse error: {}", e),
+            },
+          );
+        }
         continue;
       }
     };
 
-    let resp = handle_command(cmd, &mut state);
-
-    let _ = serde_json::to_writer(&mut writer, &resp);
-    let _ = writeln!(writer);
-    let _ = writer.flush();
+    let resp = handle_command(req.command, &mut state);
+    ipc::send_reply(req.id, resp);
 
     // Shutdown が処理された場合は終了
     if SHUTTING_DOWN.load(Ordering::Acquire) {
@@ -190,6 +222,10 @@ fn main() {
   }
 
   info!("Worker shutting down");
+  // 保存していない設定の変更を書き出す
+  settings::shutdown();
+  // DLL の読み取りスレッドを終了させる
+  ipc::send_message(&WorkerMessage::Closed);
 
   if queue::GRACEFUL_SHUTDOWN.load(Ordering::Acquire) {
     info!("Waiting for playback to drain...");
@@ -231,23 +267,6 @@ fn handle_command(cmd: Command, state: &mut WorkerState) -> Response {
       Response::Ok
     }
 
-    Command::PopDialog => {
-      let message = match queue::CONNECTION_DIALOGS.lock() {
-        Ok(mut dialogs) => {
-          if dialogs.is_empty() {
-            None
-          } else {
-            Some(dialogs.remove(0))
-          }
-        }
-        Err(e) => {
-          error!("Failed to lock CONNECTION_DIALOGS: {}", e);
-          None
-        }
-      };
-      Response::Dialog { message }
-    }
-
     Command::GetEngineStatus => {
       let speakers_info = SPEAKERS_INFO
         .read()
@@ -257,57 +276,44 @@ fn handle_command(cmd: Command, state: &mut WorkerState) -> Response {
         .read()
         .map(|cs| cs.clone())
         .unwrap_or_default();
-      let engine_paths = ENGINE_PATH.read().map(|ep| ep.clone()).unwrap_or_default();
-      let engine_auto_start = ENGINE_AUTO_START
-        .read()
-        .map(|ea| ea.clone())
-        .unwrap_or_default();
       Response::EngineStatus {
         speakers_info,
         connection_status,
-        engine_paths,
-        engine_auto_start,
-      }
-    }
-
-    Command::UpdateVolume { volume } => {
-      if let Ok(mut v) = VOLUME.write() {
-        *v = volume;
-      }
-      Response::Ok
-    }
-
-    Command::UpdateGhostVoices { ghost_name, info } => {
-      if let Ok(mut gv) = GHOSTS_VOICES.write() {
-        gv.insert(ghost_name, info);
-      }
-      Response::Ok
-    }
-
-    Command::UpdateInitialVoice { voice } => {
-      if let Ok(mut iv) = INITIAL_VOICE.write() {
-        *iv = voice;
-      }
-      Response::Ok
-    }
-
-    Command::UpdateSpeakByPunctuation { enabled } => {
-      if let Ok(mut sbp) = SPEAK_BY_PUNCTUATION.write() {
-        *sbp = enabled;
       }
-      Response::Ok
     }
 
-    Command::UpdateEngineAutoStart { engine, auto_start } => {
-      if let Ok(mut ea) = ENGINE_AUTO_START.write() {
-        ea.insert(engine, auto_start);
+    Command::GetSettings => Response::Settings {
+      settings: Box::new(settings::get()),
+    },
+
+    Command::PatchSettings { changes } => match settings::patch(&changes) {
+      Ok(settings) => Response::Settings {
+        settings: Box::new(settings),
+      },
+      Err(message) => Response::Error { message },
+    },
+
+    Command::GetSpeechHistory { ghost_name } => Response::SpeechHistory {
+      entries: history::entries(&ghost_name),
+    },
+
+    Command::ReplaySpeech { ghost_name, id } => match queue::replay_from_history(&ghost_name, id) {
+      Ok(()) => Response::Ok,
+      Err(message) => Response::Error { message },
+    },
+
+    Command::UpdateShell {
+      ghost_name,
+      shell_name,
+    } => {
+      if let Ok(mut cs) = queue::CURRENT_SHELLS.write() {
+        cs.insert(ghost_name, shell_name);
       }
       Response::Ok
     }
 
     Command::BootEngine { engine } => {
-      let engine_path = ENGINE_PATH.read().map(|ep| ep.clone()).unwrap_or_default();
-      match system::boot_engine(engine, &engine_path) {
+      match system::boot_engine(engine, &settings::get().engine_path) {
         Ok(()) => Response::Ok,
         Err(e) => Response::Error {
           message: format!("Failed to boot engine: {}", e),
@@ -315,6 +321,11 @@ fn handle_command(cmd: Command, state: &mut WorkerState) -> Response {
       }
     }
 
+    Command::PreviewVoice { voice, text } => {
+      queue::preview_voice(voice, text, &state.runtime_handle);
+      Response::Ok
+    }
+
     Command::ForceStopPlayback => {
       player::FORCE_STOP_SINK.store(true, Ordering::Release);
       cancel_sync_playback();
@@ -327,6 +338,13 @@ fn handle_command(cmd: Command, state: &mut WorkerState) -> Response {
       queue::graceful_stop_queues();
       Response::Ok
     }
+
+    Command::Unknown => {
+      warn!("Unknown command received");
+      Response::Error {
+        message: "Unknown command".to_string(),
+      }
+    }
   }
 }
 
@@ -366,57 +384,15 @@ fn handle_sync_start(text: String, ghost_name: String, state: &mut WorkerState)
     );
   }
 
-  // 最初のセグメントを分離
-  let mut segments_iter = segments.into_iter();
-  let first = segments_iter.next().unwrap();
-  let remaining: Vec<SyncSegment> = segments_iter.collect();
-  let has_more = !remaining.is_empty();
-
-  // 最初のセグメント情報
-  let first_info = SegmentInfo {
-    text: first.text.clone(),
-    raw_text: first.raw_text.clone(),
-    scope: first.scope,
-    is_ellipsis: is_ellipsis_segment(&first.text),
-  };
-
-  if !first_info.is_ellipsis && !first.text.is_empty() {
-    let wav_result: Result<Vec<u8>, String> = handle.block_on(async {
-      tokio::time::timeout(
-        std::time::Duration::from_secs(30),
-        first.predictor.predict(),
-      )
-      .await
-      .map_err(|_| "predict timed out".to_string())
-      .and_then(|r| r.map_err(|e| e.to_string()))
-    });
-
-    match wav_result {
-      Ok(wav) => {
-        // 最初のセグメントを再生開始
-        spawn_sync_playback(wav, first.volume, handle);
-      }
-      Err(e) => {
-        error!("First segment predict failed: {}", e);
-        // 空のwavで再生開始（すぐ完了する）
-        spawn_sync_playback(Vec::new(), first.volume, handle);
-      }
-    }
-  } else {
-    // 省略記号/空テキストセグメント: 空のwavで再生開始
-    spawn_sync_playback(Vec::new(), first.volume, handle);
-  }
-
-  // 残りのセグメントをバックグラウンドで合成
-  if has_more {
-    spawn_sync_prediction(remaining, ghost_name.clone(), handle);
-  }
+  // 最初のセグメントも含めてバックグラウンドで合成し、SyncPoll で順に受け渡す。
+  // 合成を待たずに応答するため、DLL 側はすぐに SHIORI リクエストへ戻れる
+  spawn_sync_prediction(segments, ghost_name.clone(), handle);
 
   state.sync_ghost_name = Some(ghost_name);
 
   Response::SyncStarted {
-    first_segment: Some(first_info),
-    has_more,
+    first_segment: None,
+    has_more: true,
   }
 }
 
@@ -435,7 +411,9 @@ fn handle_sync_poll(state: &mut WorkerState) -> Response {
   // 1. 現在のオーディオがまだ再生中か？
   if !is_sync_audio_done() {
     return Response::SyncStatus {
-      state: SyncState::Playing,
+      state: SyncState::Playing {
+        remaining_ms: sync_audio_remaining().as_millis() as u64,
+      },
     };
   }
 
@@ -444,11 +422,12 @@ fn handle_sync_poll(state: &mut WorkerState) -> Response {
 
   match ready_seg {
     Some(seg) => {
-      let segment_info = SegmentInfo {
+      let mut segment_info = SegmentInfo {
         text: seg.text.clone(),
         raw_text: seg.raw_text.clone(),
         scope: seg.scope,
         is_ellipsis: is_ellipsis_segment(&seg.text),
+        duration_ms: 0,
       };
 
       debug!(
@@ -458,7 +437,8 @@ fn handle_sync_poll(state: &mut WorkerState) -> Response {
 
       // 省略記号セグメントと空テキストセグメント（quicksection由来）は音声再生なし
       if !segment_info.is_ellipsis && !seg.text.is_empty() {
-        spawn_sync_playback(seg.wav, seg.volume, handle);
+        let duration = spawn_sync_playback(seg.wav, seg.volume, handle);
+        segment_info.duration_ms = duration.as_millis() as u64;
       }
 
       // 最後のセグメント → ステートクリア
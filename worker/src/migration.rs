@@ -0,0 +1,110 @@
+//! vars.yaml の形式の移行: 読み込んだ設定を、古い形式から順番に現在の形式へ書き換える
+use ghost_speaker_common::NO_VOICE_UUID;
+use log::{debug, error};
+use serde_yaml::{Mapping, Value};
+
+/// 現在の vars.yaml の形式のバージョン
+pub const SCHEMA_VERSION: u32 = 1;
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// MIGRATIONS[n] はバージョン n の設定をバージョン n + 1 に書き換える
+const MIGRATIONS: [fn(&mut Mapping); SCHEMA_VERSION as usize] = [unset_no_voice_placeholders];
+
+/// 設定の形式のバージョン。schema_version がない古いファイルは last_version から推定する
+fn schema_version(vars: &Mapping) -> u32 {
+  if let Some(v) = vars.get(SCHEMA_VERSION_KEY).and_then(Value::as_u64) {
+    return v as u32;
+  }
+  // 1.0 より前のファイルはバージョン 0、1.0 以降で schema_version がないものはバージョン 1
+  match vars.get("last_version").and_then(Value::as_str) {
+    Some(v) if !v.starts_with("0.") => 1,
+    _ => 0,
+  }
+}
+
+/// 設定を現在の形式に移行する
+pub fn migrate(vars: &mut Mapping) {
+  let from = schema_version(vars);
+  if from > SCHEMA_VERSION {
+    // 新しいバージョンで保存された設定: 分かる項目だけ読み込む
+    error!(
+      "vars.yaml has newer schema version {} (supported: {})",
+      from, SCHEMA_VERSION
+    );
+    return;
+  }
+  for (version, step) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+    debug!(
+      "Migrating variables from schema version {} to {}",
+      version,
+      version + 1
+    );
+    step(vars);
+  }
+  vars.insert(Value::from(SCHEMA_VERSION_KEY), Value::from(SCHEMA_VERSION));
+}
+
+/// 0 → 1: 1.0 より前は未設定の声質を NO_VOICE_UUID の声質として保存していたので、未設定に戻す
+fn unset_no_voice_placeholders(vars: &mut Mapping) {
+  let ghosts_voices = match vars
+    .get_mut("ghosts_voices")
+    .and_then(Value::as_mapping_mut)
+  {
+    Some(g) => g,
+    None => return,
+  };
+  for info in ghosts_voices.values_mut() {
+    if let Some(voices) = info.get_mut("voices").and_then(Value::as_sequence_mut) {
+      for voice in voices.iter_mut() {
+        if voice.get("speaker_uuid").and_then(Value::as_str) == Some(NO_VOICE_UUID) {
+          *voice = Value::Null;
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(yaml: &str) -> Mapping {
+    serde_yaml::from_str(yaml).unwrap()
+  }
+
+  const VOICES: &str = r#"
+ghosts_voices:
+  Ghost:
+    voices:
+    - speaker_uuid: dummy
+    - speaker_uuid: 7ffcb7ce-00ec-4bdc-82cd-45a8889e43ff
+"#;
+
+  fn voices(vars: &Mapping) -> Vec<Value> {
+    vars["ghosts_voices"]["Ghost"]["voices"]
+      .as_sequence()
+      .unwrap()
+      .clone()
+  }
+
+  #[test]
+  fn migrates_pre_1_0_placeholders_to_unset() {
+    let mut vars = parse(VOICES);
+    migrate(&mut vars);
+    let voices = voices(&vars);
+    assert!(voices[0].is_null());
+    assert!(!voices[1].is_null());
+    assert_eq!(schema_version(&vars), SCHEMA_VERSION);
+  }
+
+  #[test]
+  fn keeps_silent_voices_of_newer_files() {
+    // 1.0 以降の NO_VOICE_UUID は「無し」を選んだもの
+    for header in ["last_version: 2.0.2\n", "schema_version: 1\n"] {
+      let mut vars = parse(&format!("{}{}", header, VOICES));
+      migrate(&mut vars);
+      assert!(!voices(&vars)[0].is_null());
+      assert_eq!(schema_version(&vars), SCHEMA_VERSION);
+    }
+  }
+}
@@ -0,0 +1,480 @@
+use ghost_speaker_common::SubstitutionRule;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+// 数値とその前後の記号・単位をまとめて捉える
+// tail は単位の直後に英字が続く場合（"5mbps" など）に単位として扱わないための判定用
+static NUMERIC_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(concat!(
+    r"(?P<sign>[-−－])?",
+    r"(?P<cur>[¥￥$＄€£])?",
+    r"(?P<num>[0-9]+(?:[.．,，/／:：\-][0-9]+)*)",
+    r"(?P<suffix>[%％]|℃|°C|°|[（(][月火水木金土日][)）]",
+    r"|kHz|MHz|GHz|Hz|km/h|km|cm|mm|kg|mg|mL|ml|kW|KB|kB|MB|GB|TB|ms|m|g|L|W)?",
+    r"(?P<tail>[A-Za-z]?)",
+  ))
+  .unwrap()
+});
+// 数値の範囲・演算を表す記号
+static RANGE_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"([0-9])\s*[〜～~]\s*([-−－¥￥$＄€£]?[0-9])").unwrap());
+static ARITHMETIC_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"([0-9])\s*([+＋×÷=＝])\s*([0-9])").unwrap());
+static THOUSANDS_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[0-9]{1,3}(,[0-9]{3})+$").unwrap());
+static DECIMAL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([0-9]+)\.([0-9]+)$").unwrap());
+static DATE_YMD_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"^([0-9]{4})[/\-]([0-9]{1,2})[/\-]([0-9]{1,2})$").unwrap());
+static DATE_MD_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([0-9]{1,2})/([0-9]{1,2})$").unwrap());
+static TIME_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"^([0-9]{1,2}):([0-9]{2})(?::([0-9]{2}))?$").unwrap());
+// 月日の直後に続く時刻（"10/17 14:05" など）
+static TIME_AFTER_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"^\s*[0-9]{1,2}[:：][0-9]{2}").unwrap());
+
+const DIGITS: [&str; 10] = ["ゼロ", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+const BIG_UNITS: [&str; 5] = ["", "万", "億", "兆", "京"];
+// 連続したら1文字にまとめる文字
+const COLLAPSIBLE_CHARS: [char; 9] = ['ー', '～', '〜', '！', '!', '？', '?', 'っ', 'ッ'];
+
+/// ユーザーの読み替え辞書を上から順に適用する
+pub fn apply_substitutions(text: &str, rules: &[SubstitutionRule]) -> String {
+  let mut s = text.to_string();
+  for rule in rules.iter().filter(|r| !r.from.is_empty()) {
+    s = s.replace(&rule.from, &rule.to);
+  }
+  s
+}
+
+/// 読み上げ前にテキストを正規化する。
+/// 数値・日付・時刻・単位・通貨を漢数字による読みに展開し、連続する記号をまとめる。
+/// エンジンごとの読み方の差をなくすため、すべてのエンジンで共通に適用する
+pub fn normalize(text: &str) -> String {
+  let s = to_halfwidth_digits(text);
+  let s = RANGE_RE.replace_all(&s, "${1}から${2}").to_string();
+  let s = expand_arithmetic(s);
+  let s = expand_numerics(&s);
+  // 数値の範囲以外の波ダッシュは長音として読ませる
+  let s = s.replace(['〜', '～'], "ー");
+  collapse_repeats(&s)
+}
+
+fn expand_arithmetic(mut s: String) -> String {
+  // "1+1=2" のように数字を共有する演算は一度の置換で拾いきれないため繰り返す
+  while ARITHMETIC_RE.is_match(&s) {
+    s = ARITHMETIC_RE
+      .replace_all(&s, |caps: &Captures| {
+        let op = match &caps[2] {
+          "+" | "＋" => "プラス",
+          "×" => "かける",
+          "÷" => "わる",
+          _ => "イコール",
+        };
+        format!("{}{}{}", &caps[1], op, &caps[3])
+      })
+      .to_string();
+  }
+  s
+}
+
+fn to_halfwidth_digits(text: &str) -> String {
+  text
+    .chars()
+    .map(|c| match c {
+      '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+      _ => c,
+    })
+    .collect()
+}
+
+fn collapse_repeats(text: &str) -> String {
+  let mut result = String::with_capacity(text.len());
+  let mut prev: Option<char> = None;
+  for c in text.chars() {
+    if prev == Some(c) && COLLAPSIBLE_CHARS.contains(&c) {
+      continue;
+    }
+    result.push(c);
+    prev = Some(c);
+  }
+  result
+}
+
+fn expand_numerics(text: &str) -> String {
+  NUMERIC_RE
+    .replace_all(text, |caps: &Captures| {
+      let (start, end) = caps.get(0).map_or((0, 0), |m| (m.start(), m.end()));
+      let num = &caps["num"];
+      let tail = caps.name("tail").map(|m| m.as_str()).unwrap_or("");
+      let mut suffix = caps.name("suffix").map(|m| m.as_str()).unwrap_or("");
+
+      let mut result = String::new();
+      if let Some(sign) = caps.name("sign") {
+        // 直前が語の途中（"ver-2" など）ならハイフンとして残す
+        let is_minus = !text[..start]
+          .chars()
+          .last()
+          .is_some_and(|c| c.is_ascii_alphanumeric());
+        if is_minus {
+          result.push_str("マイナス");
+        } else {
+          result.push_str(sign.as_str());
+        }
+      }
+
+      // 単位の直後に英字が続く場合は単位とみなさない
+      let unit_reading = if tail.is_empty() {
+        read_suffix(suffix)
+      } else {
+        None
+      };
+      // "1/2の確率" のような分数と区別できないため、月日は曜日か時刻が続くときだけ日付として読む
+      let weekday = read_weekday(suffix);
+      let month_day = weekday.is_some() || TIME_AFTER_RE.is_match(&text[end..]);
+      let reading = match read_date(num, month_day) {
+        Some(date) => {
+          if let Some(weekday) = weekday {
+            suffix = "";
+            format!("{}{}", date, weekday)
+          } else {
+            date
+          }
+        }
+        None => read_numeric(num),
+      };
+      result.push_str(&reading);
+      match unit_reading {
+        Some(unit) if !suffix.is_empty() => result.push_str(unit),
+        _ => result.push_str(suffix),
+      }
+      if let Some(cur) = caps.name("cur") {
+        result.push_str(match cur.as_str() {
+          "¥" | "￥" => "円",
+          "$" | "＄" => "ドル",
+          "€" => "ユーロ",
+          _ => "ポンド",
+        });
+      }
+      result.push_str(tail);
+      result
+    })
+    .to_string()
+}
+
+fn read_suffix(suffix: &str) -> Option<&'static str> {
+  let reading = match suffix {
+    "%" | "％" => "パーセント",
+    "℃" | "°C" | "°" => "度",
+    "kHz" => "キロヘルツ",
+    "MHz" => "メガヘルツ",
+    "GHz" => "ギガヘルツ",
+    "Hz" => "ヘルツ",
+    "km/h" => "キロメートル毎時",
+    "km" => "キロメートル",
+    "cm" => "センチメートル",
+    "mm" => "ミリメートル",
+    "m" => "メートル",
+    "kg" => "キログラム",
+    "mg" => "ミリグラム",
+    "g" => "グラム",
+    "mL" | "ml" => "ミリリットル",
+    "L" => "リットル",
+    "kW" => "キロワット",
+    "W" => "ワット",
+    "KB" | "kB" => "キロバイト",
+    "MB" => "メガバイト",
+    "GB" => "ギガバイト",
+    "TB" => "テラバイト",
+    "ms" => "ミリ秒",
+    _ => return None,
+  };
+  Some(reading)
+}
+
+fn read_weekday(suffix: &str) -> Option<String> {
+  let day = suffix.chars().nth(1)?;
+  if "月火水木金土日".contains(day) {
+    Some(format!("{}曜日", day))
+  } else {
+    None
+  }
+}
+
+/// 全角の区切り記号を半角に揃える
+fn normalize_separators(num: &str) -> String {
+  num
+    .chars()
+    .map(|c| match c {
+      '．' => '.',
+      '，' => ',',
+      '／' => '/',
+      '：' => ':',
+      _ => c,
+    })
+    .collect()
+}
+
+fn read_date(num: &str, month_day: bool) -> Option<String> {
+  let num = normalize_separators(num);
+  if let Some(caps) = DATE_YMD_RE.captures(&num) {
+    let (month, day) = valid_month_day(&caps[2], &caps[3])?;
+    return Some(format!(
+      "{}年{}月{}日",
+      number_to_kanji(&caps[1]),
+      month,
+      day
+    ));
+  }
+  if let Some(caps) = DATE_MD_RE.captures(&num).filter(|_| month_day) {
+    let (month, day) = valid_month_day(&caps[1], &caps[2])?;
+    return Some(format!("{}月{}日", month, day));
+  }
+  None
+}
+
+fn valid_month_day(month: &str, day: &str) -> Option<(String, String)> {
+  let m: u32 = month.parse().ok()?;
+  let d: u32 = day.parse().ok()?;
+  if (1..=12).contains(&m) && (1..=31).contains(&d) {
+    Some((
+      number_to_kanji(&m.to_string()),
+      number_to_kanji(&d.to_string()),
+    ))
+  } else {
+    None
+  }
+}
+
+fn read_numeric(num: &str) -> String {
+  let num = normalize_separators(num);
+  if num.chars().all(|c| c.is_ascii_digit()) {
+    return number_to_kanji(&num);
+  }
+  if THOUSANDS_RE.is_match(&num) {
+    return number_to_kanji(&num.replace(',', ""));
+  }
+  if let Some(caps) = DECIMAL_RE.captures(&num) {
+    return format!(
+      "{}点{}",
+      number_to_kanji(&caps[1]),
+      digits_to_kanji(&caps[2])
+    );
+  }
+  if let Some(caps) = TIME_RE.captures(&num) {
+    if let Some(time) = read_time(&caps) {
+      return time;
+    }
+  }
+  // 分類できない数値（バージョン番号や分数など）は読みを決められないのでそのまま残す
+  num
+}
+
+fn read_time(caps: &Captures) -> Option<String> {
+  let hour: u32 = caps[1].parse().ok()?;
+  let minute: u32 = caps[2].parse().ok()?;
+  let second: Option<u32> = match caps.get(3) {
+    Some(s) => Some(s.as_str().parse().ok()?),
+    None => None,
+  };
+  if hour > 48 || minute > 59 || second.is_some_and(|s| s > 59) {
+    return None;
+  }
+  let mut result = format!("{}時", number_to_kanji(&hour.to_string()));
+  if minute > 0 {
+    result.push_str(&format!("{}分", number_to_kanji(&minute.to_string())));
+  }
+  if let Some(s) = second.filter(|s| *s > 0) {
+    result.push_str(&format!("{}秒", number_to_kanji(&s.to_string())));
+  }
+  Some(result)
+}
+
+/// 数字を1桁ずつ読む（小数部や先頭が0の番号など）
+fn digits_to_kanji(digits: &str) -> String {
+  digits
+    .chars()
+    .filter_map(|c| c.to_digit(10))
+    .map(|d| DIGITS[d as usize])
+    .collect()
+}
+
+/// 整数を漢数字の読みに変換する（例: "2026" → "二千二十六"）。
+/// 先頭が0の番号や、京を超える桁数のものは1桁ずつ読む
+pub fn number_to_kanji(digits: &str) -> String {
+  if digits.is_empty() {
+    return String::new();
+  }
+  if (digits.len() > 1 && digits.starts_with('0')) || digits.len() > BIG_UNITS.len() * 4 {
+    return digits_to_kanji(digits);
+  }
+  if digits.chars().all(|c| c == '0') {
+    return DIGITS[0].to_string();
+  }
+
+  let values: Vec<usize> = digits
+    .chars()
+    .filter_map(|c| c.to_digit(10))
+    .map(|d| d as usize)
+    .collect();
+  let mut result = String::new();
+  // 下から4桁ずつ区切り、上の区切りから読む
+  let group_count = values.len().div_ceil(4);
+  for g in (0..group_count).rev() {
+    let end = values.len() - g * 4;
+    let start = end.saturating_sub(4);
+    let group = &values[start..end];
+    let group_reading = four_digits_to_kanji(group);
+    if !group_reading.is_empty() {
+      result.push_str(&group_reading);
+      result.push_str(BIG_UNITS[g]);
+    }
+  }
+  result
+}
+
+fn four_digits_to_kanji(group: &[usize]) -> String {
+  const PLACES: [&str; 4] = ["", "十", "百", "千"];
+  let mut result = String::new();
+  for (i, d) in group.iter().enumerate() {
+    let place = group.len() - 1 - i;
+    match (*d, place) {
+      (0, _) => {}
+      (1, p) if p > 0 => result.push_str(PLACES[p]),
+      (d, p) => {
+        result.push_str(DIGITS[d]);
+        result.push_str(PLACES[p]);
+      }
+    }
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn substitutions_apply_in_order() {
+    let rules = [
+      SubstitutionRule {
+        from: "伺か".to_string(),
+        to: "うかがか".to_string(),
+      },
+      SubstitutionRule {
+        from: "うかがか".to_string(),
+        to: "ウカガカ".to_string(),
+      },
+      SubstitutionRule {
+        from: String::new(),
+        to: "無視".to_string(),
+      },
+    ];
+    assert_eq!(apply_substitutions("伺かの世界", &rules), "ウカガカの世界");
+  }
+
+  #[test]
+  fn number_to_kanji_table() {
+    let cases = [
+      ("0", "ゼロ"),
+      ("1", "一"),
+      ("10", "十"),
+      ("11", "十一"),
+      ("20", "二十"),
+      ("100", "百"),
+      ("101", "百一"),
+      ("110", "百十"),
+      ("999", "九百九十九"),
+      ("1000", "千"),
+      ("2026", "二千二十六"),
+      ("10000", "一万"),
+      ("10001", "一万一"),
+      ("12345", "一万二千三百四十五"),
+      ("100000000", "一億"),
+      ("100010000", "一億一万"),
+      ("1000000000000", "一兆"),
+      ("007", "ゼロゼロ七"),
+      ("000", "ゼロゼロゼロ"),
+      (
+        "123456789012345678901",
+        "一二三四五六七八九ゼロ一二三四五六七八九ゼロ一",
+      ),
+    ];
+    for (input, expected) in cases {
+      assert_eq!(number_to_kanji(input), expected, "input: {}", input);
+    }
+  }
+
+  #[test]
+  fn normalize_table() {
+    let cases = [
+      // 数値
+      ("りんごが3個", "りんごが三個"),
+      ("１２３円", "百二十三円"),
+      ("1,000人", "千人"),
+      ("１，０００人", "千人"),
+      ("1,2,3", "1,2,3"),
+      ("3.14", "三点一四"),
+      ("0.05", "ゼロ点ゼロ五"),
+      ("気温は-5℃", "気温はマイナス五度"),
+      ("ver-2", "ver-二"),
+      ("v1.2.3", "v1.2.3"),
+      ("ver1.2.3", "ver1.2.3"),
+      // 日付
+      ("2026/10/17", "二千二十六年十月十七日"),
+      ("2026-10-17", "二千二十六年十月十七日"),
+      ("10/17(土)", "十月十七日土曜日"),
+      ("10/17（土）", "十月十七日土曜日"),
+      ("13/40", "13/40"),
+      ("1/2の確率", "1/2の確率"),
+      ("2026/13/01", "2026/13/01"),
+      // 時刻
+      ("14:05", "十四時五分"),
+      ("14:00", "十四時"),
+      ("25:30", "二十五時三十分"),
+      ("14:00:30", "十四時三十秒"),
+      ("14:05:00", "十四時五分"),
+      ("14：05", "十四時五分"),
+      ("2026/10/17 14:05", "二千二十六年十月十七日 十四時五分"),
+      ("1:99", "1:99"),
+      ("10/17 14:05", "十月十七日 十四時五分"),
+      // 単位
+      ("3.5kg", "三点五キログラム"),
+      ("100g", "百グラム"),
+      ("42.195km", "四十二点一九五キロメートル"),
+      ("60km/h", "六十キロメートル毎時"),
+      ("500mL", "五百ミリリットル"),
+      ("2L", "二リットル"),
+      ("16GB", "十六ギガバイト"),
+      ("100ms", "百ミリ秒"),
+      ("440Hz", "四百四十ヘルツ"),
+      ("36.5°C", "三十六点五度"),
+      ("5mbps", "五mbps"),
+      // パーセント・通貨
+      ("100%", "百パーセント"),
+      ("５０％", "五十パーセント"),
+      ("¥1,000", "千円"),
+      ("￥500", "五百円"),
+      ("$5", "五ドル"),
+      ("€20", "二十ユーロ"),
+      // 範囲・演算
+      ("10〜20", "十から二十"),
+      ("10～20人", "十から二十人"),
+      ("1+1=2", "一プラス一イコール二"),
+      ("3×4", "三かける四"),
+      ("8÷2", "八わる二"),
+      // 記号の連続
+      ("すごーーーーい", "すごーい"),
+      ("やった！！！！", "やった！"),
+      ("え？？", "え？"),
+      ("よろしく〜", "よろしくー"),
+      ("よろしく〜〜〜", "よろしくー"),
+      ("あっっっ", "あっ"),
+      ("……", "……"),
+      // 変換対象なし
+      ("こんにちは", "こんにちは"),
+      ("", ""),
+    ];
+    for (input, expected) in cases {
+      assert_eq!(normalize(input), expected, "input: {}", input);
+    }
+  }
+}
@@ -1,46 +1,217 @@
-use log::error;
-use rodio::{Decoder, OutputStream, Sink};
+use ghost_speaker_common::AudioOutput;
+use log::{error, info};
+use once_cell::sync::Lazy;
+use rodio::{Decoder, OutputStream, Sink, Source};
 use std::io::BufReader;
 use std::io::Cursor;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 // 音声再生の最大時間（60秒）
 const MAX_AUDIO_PLAY_TIME: Duration = Duration::from_secs(60);
 
 pub static FORCE_STOP_SINK: AtomicBool = AtomicBool::new(false);
 
-pub fn play_wav(
-  wav: Vec<u8>,
-  volume: f32,
-) -> Result<(), Box<dyn std::error::Error>> {
-  let (_stream, handle) = OutputStream::try_default()?;
-  let sink = Sink::try_new(&handle)?;
-  sink.set_volume(volume);
-  let file = BufReader::new(Cursor::new(wav));
-  match Decoder::new(file) {
-    Ok(source) => {
-      sink.append(source);
-    }
-    Err(e) => return Err(Box::new(e)),
+/// 音声の出力先
+pub trait AudioSink: Send + Sync {
+  /// wav を出力し、再生が終わる（または停止される）まで待つ
+  fn play(&self, wav: Vec<u8>, volume: f32) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+static AUDIO_SINK: Lazy<RwLock<Arc<dyn AudioSink>>> =
+  Lazy::new(|| RwLock::new(Arc::new(DeviceSink)));
+
+/// 出力先を設定に従って切り替える
+pub fn set_audio_output(output: &AudioOutput, dll_dir: &str) {
+  let sink: Arc<dyn AudioSink> = match output {
+    AudioOutput::Device => Arc::new(DeviceSink),
+    AudioOutput::Null => Arc::new(NullSink),
+    AudioOutput::File { dir } => Arc::new(FileSink::new(Path::new(dll_dir).join(dir))),
+  };
+  info!("audio output: {:?}", output);
+  match AUDIO_SINK.write() {
+    Ok(mut s) => *s = sink,
+    Err(e) => error!("Failed to write AUDIO_SINK: {}", e),
+  }
+}
+
+/// wavデータをデコードして再生時間を求める。デコードできない場合は None
+pub fn wav_duration(wav: &[u8]) -> Option<Duration> {
+  if wav.is_empty() {
+    return None;
+  }
+  let source = Decoder::new(BufReader::new(Cursor::new(wav.to_vec()))).ok()?;
+  source.total_duration()
+}
+
+/// 現在の出力先で wav を再生する
+pub fn play_wav(wav: Vec<u8>, volume: f32) -> Result<(), Box<dyn std::error::Error>> {
+  let sink = match AUDIO_SINK.read() {
+    Ok(s) => s.clone(),
+    Err(e) => return Err(format!("Failed to read AUDIO_SINK: {}", e).into()),
   };
+  sink.play(wav, volume)
+}
+
+/// finished が true を返すまで待つ。タイムアウトまたは強制停止で中断した場合は true を返す
+fn wait_playback(mut finished: impl FnMut() -> bool) -> bool {
   let start_time = Instant::now();
-  while !sink.empty() {
+  while !finished() {
     // タイムアウトチェック
     if start_time.elapsed() >= MAX_AUDIO_PLAY_TIME {
       error!("Audio playback timeout exceeded, stopping playback");
-      sink.pause();
-      sink.stop();
-      return Ok(());
+      return true;
     }
 
     std::thread::sleep(std::time::Duration::from_millis(50));
     if FORCE_STOP_SINK.load(Ordering::Acquire) {
+      FORCE_STOP_SINK.store(false, Ordering::Release);
+      return true;
+    }
+  }
+  false
+}
+
+/// 再生時間だけ待つ
+fn wait_duration(wav: &[u8]) {
+  let duration = wav_duration(wav).unwrap_or_default();
+  let start_time = Instant::now();
+  wait_playback(|| start_time.elapsed() >= duration);
+}
+
+/// サウンドデバイスで再生する
+pub struct DeviceSink;
+
+impl AudioSink for DeviceSink {
+  fn play(&self, wav: Vec<u8>, volume: f32) -> Result<(), Box<dyn std::error::Error>> {
+    let (_stream, handle) = OutputStream::try_default()?;
+    let sink = Sink::try_new(&handle)?;
+    sink.set_volume(volume);
+    let file = BufReader::new(Cursor::new(wav));
+    match Decoder::new(file) {
+      Ok(source) => {
+        sink.append(source);
+      }
+      Err(e) => return Err(Box::new(e)),
+    };
+    if wait_playback(|| sink.empty()) {
       sink.pause();
       sink.stop();
-      FORCE_STOP_SINK.store(false, Ordering::Release);
-      return Ok(());
     }
+    Ok(())
+  }
+}
+
+/// 再生せず、再生時間だけ待つ
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+  fn play(&self, wav: Vec<u8>, _volume: f32) -> Result<(), Box<dyn std::error::Error>> {
+    wait_duration(&wav);
+    Ok(())
+  }
+}
+
+/// 1回の再生ごとに wav をファイルへ書き出し、再生時間だけ待つ。音量は反映しない
+pub struct FileSink {
+  dir: PathBuf,
+  /// 前回の起動で書き出したファイルを上書きしないよう、ファイル名の先頭に付ける
+  session: u64,
+  counter: AtomicU64,
+}
+
+impl FileSink {
+  pub fn new(dir: PathBuf) -> Self {
+    let session = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or_default();
+    FileSink {
+      dir,
+      session,
+      counter: AtomicU64::new(0),
+    }
+  }
+}
+
+impl AudioSink for FileSink {
+  fn play(&self, wav: Vec<u8>, _volume: f32) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(&self.dir)?;
+    let n = self.counter.fetch_add(1, Ordering::SeqCst) + 1;
+    let path = self.dir.join(format!("{}_{:04}.wav", self.session, n));
+    std::fs::write(&path, &wav)?;
+    wait_duration(&wav);
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// テスト用: 16bitモノラルの無音wavを生成する
+  fn make_wav(sample_rate: u32, samples: u32) -> Vec<u8> {
+    let data_len = samples * 2;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(wav.len() + data_len as usize, 0);
+    wav
+  }
+
+  #[test]
+  fn wav_duration_from_samples() {
+    let wav = make_wav(8000, 4000);
+    assert_eq!(wav_duration(&wav), Some(Duration::from_millis(500)));
+  }
+
+  #[test]
+  fn wav_duration_empty() {
+    assert_eq!(wav_duration(&[]), None);
+  }
+
+  #[test]
+  fn wav_duration_invalid() {
+    assert_eq!(wav_duration(b"not a wav"), None);
+  }
+
+  #[test]
+  fn null_sink_waits_for_duration() {
+    let start = Instant::now();
+    NullSink.play(make_wav(8000, 800), 1.0).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(100));
+  }
+
+  #[test]
+  fn file_sink_writes_each_clip() {
+    let dir = std::env::temp_dir().join(format!("ghost_speaker_file_sink_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let sink = FileSink::new(dir.clone());
+    let first = make_wav(8000, 80);
+    let second = make_wav(8000, 160);
+    sink.play(first.clone(), 0.5).unwrap();
+    sink.play(second.clone(), 0.5).unwrap();
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+      .unwrap()
+      .map(|e| e.unwrap().path())
+      .collect();
+    files.sort();
+    assert_eq!(files.len(), 2);
+    assert_eq!(std::fs::read(&files[0]).unwrap(), first);
+    assert_eq!(std::fs::read(&files[1]).unwrap(), second);
+    let _ = std::fs::remove_dir_all(&dir);
   }
-  Ok(())
 }
@@ -2,17 +2,24 @@ use crate::engine::bouyomichan::predict::BouyomichanPredictor;
 use crate::engine::coeiroink_v2::predict::CoeiroinkV2Predictor;
 use crate::engine::voicevox_family::predict::VoicevoxFamilyPredictor;
 use crate::engine::{get_speaker_getters, NoOpPredictor, Predictor};
+use crate::english::english_to_katakana;
+use crate::filter::{filter_text, link_ranges};
 use crate::format::{
   is_ellipsis_segment, resplit_pairs_by_raw_ellipsis, split_by_punctuation_with_raw, split_dialog,
 };
-use crate::player::play_wav;
+use crate::history::{self, SpokenSegment};
+use crate::ipc::send_event;
+use crate::normalize::{apply_substitutions, normalize};
+use crate::player::{play_wav, wav_duration};
+use crate::recorder::{self, RecordedSegment};
+use crate::settings::{self, SETTINGS};
 use crate::system::get_port_opener_path;
 use ghost_speaker_common::{
-  engine_from_port, CharacterVoice, Engine, GhostVoiceInfo, SpeakerInfo, NO_VOICE_UUID,
+  engine_from_port, CharacterVoice, Engine, SpeakerInfo, WorkerEvent, NO_VOICE_UUID,
 };
 use log::{debug, error, warn};
 use once_cell::sync::Lazy;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Mutex as StdMutex;
 use std::sync::RwLock;
@@ -23,31 +30,19 @@ const QUEUE_POLL_TIMEOUT: Duration = Duration::from_millis(100);
 
 // --- グローバル状態 ---
 
-pub static CONNECTION_DIALOGS: Lazy<StdMutex<Vec<String>>> =
-  Lazy::new(|| StdMutex::new(Vec::new()));
-
 pub static SPEAKERS_INFO: Lazy<RwLock<HashMap<Engine, Vec<SpeakerInfo>>>> =
   Lazy::new(|| RwLock::new(HashMap::new()));
 
 pub static CURRENT_CONNECTION_STATUS: Lazy<RwLock<HashMap<Engine, bool>>> =
   Lazy::new(|| RwLock::new(HashMap::new()));
 
-pub static ENGINE_PATH: Lazy<RwLock<HashMap<Engine, String>>> =
-  Lazy::new(|| RwLock::new(HashMap::new()));
-
-pub static ENGINE_AUTO_START: Lazy<RwLock<HashMap<Engine, bool>>> =
-  Lazy::new(|| RwLock::new(HashMap::new()));
-
-pub static VOLUME: Lazy<RwLock<f32>> = Lazy::new(|| RwLock::new(1.0));
-
-pub static SPEAK_BY_PUNCTUATION: Lazy<RwLock<bool>> = Lazy::new(|| RwLock::new(true));
-
-pub static GHOSTS_VOICES: Lazy<RwLock<HashMap<String, GhostVoiceInfo>>> =
+/// ゴーストごとの現在のシェル名
+pub static CURRENT_SHELLS: Lazy<RwLock<HashMap<String, String>>> =
   Lazy::new(|| RwLock::new(HashMap::new()));
 
-pub static INITIAL_VOICE: Lazy<RwLock<CharacterVoice>> =
-  Lazy::new(|| RwLock::new(CharacterVoice::no_voice()));
-
+/// 通知済みの (ゴースト名, スコープ, 声質)
+static FALLBACK_NOTIFIED: Lazy<StdMutex<HashSet<(String, usize, String)>>> =
+  Lazy::new(|| StdMutex::new(HashSet::new()));
 pub static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
 pub static GRACEFUL_SHUTDOWN: AtomicBool = AtomicBool::new(false);
 
@@ -72,6 +67,8 @@ pub struct SyncSegment {
   pub text: String,
   pub raw_text: String,
   pub scope: usize,
+  /// 読み上げに使う声質（履歴の表示用）
+  pub voice: CharacterVoice,
   pub predictor: Box<dyn Predictor + Send + Sync>,
   pub volume: f32,
 }
@@ -97,6 +94,9 @@ pub static SYNC_STATE: Lazy<StdMutex<Option<SyncPlaybackState>>> =
 static SYNC_AUDIO_GENERATION: AtomicU64 = AtomicU64::new(0);
 static SYNC_AUDIO_DONE_GEN: AtomicU64 = AtomicU64::new(0);
 
+// 同期再生中のオーディオの終了予定時刻（wavの長さから算出）
+static SYNC_AUDIO_DEADLINE: Lazy<StdMutex<Option<Instant>>> = Lazy::new(|| StdMutex::new(None));
+
 static SYNC_PREDICTION_HANDLER: Lazy<StdMutex<Option<tokio::task::JoinHandle<()>>>> =
   Lazy::new(|| StdMutex::new(None));
 
@@ -139,26 +139,13 @@ fn init_speak_queue(handle: &tokio::runtime::Handle) {
                 cs.get(&engine).is_none() || cs.get(&engine).is_some_and(|v| !*v)
               };
               if was_disconnected {
-                if let Ok(mut dialogs) = CONNECTION_DIALOGS.lock() {
-                  dialogs.push(format!("{} が接続されました", engine.name()));
-                } else {
-                  error!("Failed to lock CONNECTION_DIALOGS for connection message");
-                }
-                if let Ok(mut ep) = ENGINE_PATH.write() {
-                  ep.insert(engine, port_opener_path);
-                } else {
-                  error!("Failed to lock ENGINE_PATH for engine: {}", engine.name());
-                }
-                if let Ok(mut auto_start) = ENGINE_AUTO_START.write() {
-                  if auto_start.get(&engine).is_none() {
-                    auto_start.insert(engine, false);
-                  }
-                } else {
-                  error!(
-                    "Failed to lock ENGINE_AUTO_START for engine: {}",
-                    engine.name()
-                  );
-                }
+                send_event(WorkerEvent::Dialog {
+                  message: format!("{} が接続されました", engine.name()),
+                });
+                settings::update(|s| {
+                  s.engine_path.insert(engine, port_opener_path);
+                  s.engine_auto_start.entry(engine).or_insert(false);
+                });
               }
               if let Ok(mut cs) = CURRENT_CONNECTION_STATUS.write() {
                 cs.insert(engine, true);
@@ -196,11 +183,9 @@ fn init_speak_queue(handle: &tokio::runtime::Handle) {
                   cs.get(&engine).is_some_and(|v| *v)
                 };
                 if was_connected {
-                  if let Ok(mut dialogs) = CONNECTION_DIALOGS.lock() {
-                    dialogs.push(format!("{} が切断されました", engine.name()));
-                  } else {
-                    error!("Failed to lock CONNECTION_DIALOGS for disconnection message");
-                  }
+                  send_event(WorkerEvent::Dialog {
+                    message: format!("{} が切断されました", engine.name()),
+                  });
                 }
               }
               if let Ok(mut cs) = CURRENT_CONNECTION_STATUS.write() {
@@ -232,6 +217,15 @@ fn init_speak_queue(handle: &tokio::runtime::Handle) {
   }
 }
 
+/// 録音の書き出しは WAV の変換とファイルの書き込みに時間がかかるため、
+/// 非同期タスクのスレッドを止めないよう別スレッドで行う
+async fn record_talk(ghost_name: String, recorded: Vec<RecordedSegment>) {
+  match tokio::task::spawn_blocking(move || recorder::record_talk(&ghost_name, recorded)).await {
+    Ok(()) => {}
+    Err(e) => error!("Failed to record talk: {}", e),
+  }
+}
+
 fn init_predict_queue(handle: &tokio::runtime::Handle) {
   let handler = handle.spawn(async move {
     let mut last_activity = Instant::now();
@@ -267,19 +261,37 @@ fn init_predict_queue(handle: &tokio::runtime::Handle) {
         None => continue,
         Some(parg) => {
           last_activity = Instant::now();
-          match args_to_predictors(parg).await {
+          let ghost_name = parg.1.clone();
+          let recording = recorder::should_capture(&ghost_name);
+          match args_to_segments(parg).await {
             None => continue,
-            Some(predictors) => {
-              for (predictor, volume) in predictors {
+            Some(segments) => {
+              let mut recorded = Vec::new();
+              let mut spoken = Vec::new();
+              for seg in segments {
                 let wav_result: Result<Vec<u8>, String> =
-                  predictor.predict().await.map_err(|e| e.to_string());
+                  seg.predictor.predict().await.map_err(|e| e.to_string());
                 match wav_result {
                   Ok(res) => {
+                    spoken.push(SpokenSegment {
+                      scope: seg.scope,
+                      text: seg.text.clone(),
+                      voice: seg.voice,
+                      wav: res.clone(),
+                    });
+                    if recording {
+                      recorded.push(RecordedSegment {
+                        scope: seg.scope,
+                        text: seg.text,
+                        raw_text: seg.raw_text,
+                        wav: res.clone(),
+                      });
+                    }
                     debug!("pushing to play");
                     PLAY_QUEUE
                       .lock()
                       .unwrap_or_else(|e| e.into_inner())
-                      .push_back((res, volume));
+                      .push_back((res, seg.volume));
                     debug!("pushed to play");
                   }
                   Err(e) => {
@@ -287,6 +299,10 @@ fn init_predict_queue(handle: &tokio::runtime::Handle) {
                   }
                 }
               }
+              history::push(&ghost_name, spoken);
+              if recording {
+                record_talk(ghost_name, recorded).await;
+              }
             }
           }
         }
@@ -365,14 +381,13 @@ pub fn init_queues(handle: &tokio::runtime::Handle) {
     .unwrap_or_else(|e| e.into_inner())
     .clear();
   PLAY_QUEUE.lock().unwrap_or_else(|e| e.into_inner()).clear();
-  CONNECTION_DIALOGS
-    .lock()
-    .unwrap_or_else(|e| e.into_inner())
-    .clear();
 
   // 同期世代カウンタをリセット
   SYNC_AUDIO_GENERATION.store(0, Ordering::Release);
   SYNC_AUDIO_DONE_GEN.store(0, Ordering::Release);
+  if let Ok(mut d) = SYNC_AUDIO_DEADLINE.lock() {
+    *d = None;
+  }
   if let Ok(mut h) = SYNC_PREDICTION_HANDLER.lock() {
     *h = None;
   }
@@ -473,10 +488,6 @@ pub fn stop_queues() {
     .unwrap_or_else(|e| e.into_inner())
     .clear();
   PLAY_QUEUE.lock().unwrap_or_else(|e| e.into_inner()).clear();
-  CONNECTION_DIALOGS
-    .lock()
-    .unwrap_or_else(|e| e.into_inner())
-    .clear();
 
   crate::system::cleanup_system_cache();
 
@@ -566,10 +577,6 @@ pub fn wait_for_playback_drain(timeout: Duration) {
     .unwrap_or_else(|e| e.into_inner())
     .clear();
   PLAY_QUEUE.lock().unwrap_or_else(|e| e.into_inner()).clear();
-  CONNECTION_DIALOGS
-    .lock()
-    .unwrap_or_else(|e| e.into_inner())
-    .clear();
 
   crate::system::cleanup_system_cache();
 
@@ -591,9 +598,27 @@ pub fn push_to_prediction(text: String, ghost_name: String) {
     .push_back((text, ghost_name));
 }
 
-async fn args_to_predictors(
-  args: (String, String),
-) -> Option<VecDeque<(Box<dyn Predictor + Send + Sync>, f32)>> {
+/// 履歴のトークを、合成し直さずにキャッシュした音声で再生する
+pub fn replay_from_history(ghost_name: &str, id: Option<u64>) -> Result<(), String> {
+  if SHUTTING_DOWN.load(Ordering::Acquire) {
+    return Ok(());
+  }
+  let wavs = history::cached_wavs(ghost_name, id)?;
+  let volume = match SETTINGS.read() {
+    Ok(s) => s.volume,
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      1.0
+    }
+  };
+  let mut queue = PLAY_QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+  for wav in wavs {
+    queue.push_back((wav, volume));
+  }
+  Ok(())
+}
+
+async fn args_to_segments(args: (String, String)) -> Option<Vec<SyncSegment>> {
   let (text, ghost_name) = args;
   build_segments_async(text, ghost_name, false)
     .await
@@ -601,7 +626,6 @@ async fn args_to_predictors(
       segments
         .into_iter()
         .filter(|seg| !is_ellipsis_segment(&seg.text))
-        .map(|seg| (seg.predictor, seg.volume))
         .collect()
     })
 }
@@ -655,106 +679,123 @@ async fn build_segments_async(
 
   debug!("{}", format!("predicting: {}", text));
 
-  // GHOSTS_VOICES から必要なデータをクローンしてからガードをドロップ
-  let (devide_by_lines, speak_by_punctuation_val, speakers, initial_voice, volume) = {
-    let ghosts_voices = match GHOSTS_VOICES.read() {
-      Ok(gv) => gv,
+  // 設定から必要なデータをクローンしてからガードをドロップ
+  let (
+    devide_by_lines,
+    choice_reading,
+    normalize_text,
+    english_reading,
+    substitution_rules,
+    text_filter,
+    speak_by_punctuation_val,
+    speakers,
+    fallback_voices,
+    character_names,
+    initial_voice,
+    volume,
+  ) = {
+    let settings = match SETTINGS.read() {
+      Ok(s) => s,
       Err(e) => {
-        error!("Failed to read GHOSTS_VOICES: {}", e);
+        error!("Failed to read SETTINGS: {}", e);
         return None;
       }
     };
-    let ghost_info = match ghosts_voices.get(&ghost_name) {
+    let ghost_info = match settings.ghosts_voices.get(&ghost_name) {
       Some(info) => info,
       None => {
-        error!("Ghost not found in GHOSTS_VOICES: {}", ghost_name);
+        error!("Ghost not found in settings: {}", ghost_name);
         return None;
       }
     };
     let devide_by_lines = ghost_info.devide_by_lines;
-    let speakers = ghost_info.voices.clone();
-    let speak_by_punctuation_val = match SPEAK_BY_PUNCTUATION.read() {
-      Ok(sbp) => *sbp,
+    let choice_reading = ghost_info.choice_reading;
+    let normalize_text = ghost_info.normalize_text;
+    let english_reading = ghost_info.english_to_katakana;
+    let substitution_rules = settings.substitution_rules.clone();
+    let text_filter = settings.text_filter.clone();
+    let shell = match CURRENT_SHELLS.read() {
+      Ok(cs) => cs.get(&ghost_name).cloned(),
       Err(e) => {
-        error!("Failed to read SPEAK_BY_PUNCTUATION: {}", e);
-        true
-      }
-    };
-    let initial_voice = match INITIAL_VOICE.read() {
-      Ok(iv) => iv.clone(),
-      Err(e) => {
-        error!("Failed to read INITIAL_VOICE: {}", e);
-        return None;
-      }
-    };
-    let volume = match VOLUME.read() {
-      Ok(v) => *v,
-      Err(e) => {
-        error!("Failed to read VOLUME: {}", e);
-        1.0
+        error!("Failed to read CURRENT_SHELLS: {}", e);
+        None
       }
     };
+    let speakers = ghost_info.voices_for_shell(shell.as_deref());
+    let fallback_voices = ghost_info.fallback_voices.clone();
+    let character_names = ghost_info.character_names.clone();
+    let speak_by_punctuation_val = settings.speak_by_punctuation;
+    let initial_voice = settings.initial_voice.clone();
+    let volume = settings.volume;
     (
       devide_by_lines,
+      choice_reading,
+      normalize_text,
+      english_reading,
+      substitution_rules,
+      text_filter,
       speak_by_punctuation_val,
       speakers,
+      fallback_voices,
+      character_names,
       initial_voice,
       volume,
     )
   };
   // ここではすべてのstd::sync::RwLockガードがドロップ済み
 
-  for dialog in split_dialog(text, devide_by_lines) {
+  for dialog in split_dialog(text, devide_by_lines, choice_reading) {
     if dialog.text.is_empty() {
       continue;
     }
 
     debug!("selecting speaker: {}", dialog.scope);
-    let speaker = match speakers.get(dialog.scope) {
-      Some(speaker) => {
-        if let Some(sp) = speaker {
-          sp.clone()
-        } else {
-          initial_voice.clone()
-        }
-      }
-      None => initial_voice.clone(),
-    };
-
-    if speaker.speaker_uuid == NO_VOICE_UUID {
-      continue;
-    }
-    let play_volume = volume;
-    let voice_not_found = {
-      let engine = match engine_from_port(speaker.port) {
-        Some(e) => e,
-        None => continue,
-      };
-      match SPEAKERS_INFO.read() {
-        Ok(si) => {
-          if let Some(speakers_by_engine) = si.get(&engine) {
-            !speakers_by_engine
-              .iter()
-              .any(|s| s.speaker_uuid == speaker.speaker_uuid)
-          } else {
-            false
-          }
-        }
+    let assigned = speakers.get(dialog.scope).cloned().flatten();
+    let candidates = voice_candidates(
+      assigned.as_ref(),
+      fallback_voices
+        .get(dialog.scope)
+        .map(|v| v.as_slice())
+        .unwrap_or_default(),
+      &initial_voice,
+    );
+    let (position, speaker) = {
+      let speakers_info = match SPEAKERS_INFO.read() {
+        Ok(si) => si,
         Err(e) => {
           error!("Failed to read SPEAKERS_INFO: {}", e);
-          false
+          continue;
+        }
+      };
+      let selected = select_voice(&candidates, |voice| {
+        is_voice_available(voice, &connected_engines, &speakers_info)
+      });
+      match selected {
+        Some((position, voice)) => (position, voice.clone()),
+        None => {
+          debug!("no available voice for scope {}", dialog.scope);
+          continue;
         }
       }
     };
-    if voice_not_found {
-      continue;
+    if position > 0 {
+      notify_fallback(
+        &ghost_name,
+        dialog.scope,
+        &character_names,
+        &candidates[0],
+        &speaker,
+      );
     }
+    let play_volume = volume;
     let engine = match engine_from_port(speaker.port) {
       Some(e) => e,
       None => continue,
     };
     let pairs = if (speak_by_punctuation_val || sync_mode) && engine != Engine::BouyomiChan {
-      let p = split_by_punctuation_with_raw(dialog.text.clone(), dialog.raw_text.clone());
+      // URL やパスは途中の ? や ! で分割せず、ひとまとまりのままフィルタに渡す
+      let links = link_ranges(&dialog.text, &text_filter);
+      let p = split_by_punctuation_with_raw(dialog.text.clone(), dialog.raw_text.clone(), &links);
       // 同期モード: \_q内の省略記号をraw_textベースで再分割
       if sync_mode {
         resplit_pairs_by_raw_ellipsis(p)
@@ -775,6 +816,7 @@ async fn build_segments_async(
             text: t,
             raw_text: rt,
             scope: dialog.scope,
+            voice: speaker.clone(),
             predictor: Box::new(NoOpPredictor),
             volume: play_volume,
           });
@@ -786,39 +828,42 @@ async fn build_segments_async(
           text: t,
           raw_text: rt,
           scope: dialog.scope,
+          voice: speaker.clone(),
           predictor: Box::new(NoOpPredictor),
           volume: play_volume,
         });
         continue;
       }
-      let predictor: Box<dyn Predictor + Send + Sync> = match engine {
-        Engine::CoeiroInkV2 => Box::new(CoeiroinkV2Predictor::new(
-          t.clone(),
-          speaker.speaker_uuid.clone(),
-          speaker.style_id,
-          speaker.voice_quality.clone(),
-        )),
-        Engine::BouyomiChan => Box::new(BouyomichanPredictor::new(
-          t.clone(),
-          speaker.style_id,
-          volume,
-        )),
-        Engine::CoeiroInkV1
-        | Engine::VoiceVox
-        | Engine::Lmroid
-        | Engine::ShareVox
-        | Engine::ItVoice
-        | Engine::AivisSpeech => Box::new(VoicevoxFamilyPredictor::new(
-          engine,
-          t.clone(),
-          speaker.style_id,
-          speaker.voice_quality.clone(),
-        )),
-      };
+      // 読み替えや正規化はTTSに渡すテキストのみに適用する（省略記号判定などは元のテキストで行う）
+      let mut tts_text = apply_substitutions(&t, &substitution_rules);
+      tts_text = filter_text(&tts_text, &text_filter);
+      if normalize_text {
+        tts_text = normalize(&tts_text);
+      }
+      // 棒読みちゃんは英単語を独自の辞書で読めるため変換しない
+      if english_reading && engine != Engine::BouyomiChan {
+        tts_text = english_to_katakana(&tts_text, &substitution_rules);
+      }
+      // URLや絵文字だけのセグメントは除去後に読むものがなくなる
+      if tts_text.trim().is_empty() {
+        if sync_mode {
+          segments.push(SyncSegment {
+            text: t,
+            raw_text: rt,
+            scope: dialog.scope,
+            voice: speaker.clone(),
+            predictor: Box::new(NoOpPredictor),
+            volume: play_volume,
+          });
+        }
+        continue;
+      }
+      let predictor = new_predictor(engine, tts_text, &speaker, volume);
       segments.push(SyncSegment {
         text: t,
         raw_text: rt,
         scope: dialog.scope,
+        voice: speaker.clone(),
         predictor,
         volume: play_volume,
       });
@@ -831,6 +876,211 @@ async fn build_segments_async(
   Some(segments)
 }
 
+/// キャラクターの声質の候補: 割り当てた声質（未設定ならデフォルト声質）→ 予備の声質 → デフォルト声質。
+/// 「無し」を割り当てたキャラクターは読み上げない
+fn voice_candidates(
+  assigned: Option<&CharacterVoice>,
+  fallbacks: &[CharacterVoice],
+  initial_voice: &CharacterVoice,
+) -> Vec<CharacterVoice> {
+  let primary = assigned.unwrap_or(initial_voice);
+  if primary.speaker_uuid == NO_VOICE_UUID {
+    return Vec::new();
+  }
+  std::iter::once(primary)
+    .chain(fallbacks)
+    .chain(std::iter::once(initial_voice))
+    .cloned()
+    .collect()
+}
+
+/// 候補を順に調べ、使える最初の声質とその位置（0 なら割り当てどおり）を返す
+fn select_voice(
+  candidates: &[CharacterVoice],
+  is_available: impl Fn(&CharacterVoice) -> bool,
+) -> Option<(usize, &CharacterVoice)> {
+  candidates
+    .iter()
+    .enumerate()
+    .find(|(_, voice)| voice.speaker_uuid != NO_VOICE_UUID && is_available(voice))
+}
+
+/// 声質のエンジンが起動していて、話者が一覧にあるか
+fn is_voice_available(
+  voice: &CharacterVoice,
+  connected_engines: &[Engine],
+  speakers_info: &HashMap<Engine, Vec<SpeakerInfo>>,
+) -> bool {
+  let engine = match engine_from_port(voice.port) {
+    Some(e) => e,
+    None => return false,
+  };
+  if !connected_engines.contains(&engine) {
+    return false;
+  }
+  match speakers_info.get(&engine) {
+    Some(speakers) => speakers
+      .iter()
+      .any(|s| s.speaker_uuid == voice.speaker_uuid),
+    None => true,
+  }
+}
+
+/// 予備の声質を使ったことを、キャラクターと声質の組み合わせごとに一度だけ通知する
+fn notify_fallback(
+  ghost_name: &str,
+  scope: usize,
+  character_names: &[String],
+  primary: &CharacterVoice,
+  used: &CharacterVoice,
+) {
+  let enabled = match SETTINGS.read() {
+    Ok(s) => s.fallback_notice,
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      false
+    }
+  };
+  if !enabled {
+    return;
+  }
+  let key = (
+    ghost_name.to_string(),
+    scope,
+    format!("{}:{}:{}", used.port, used.speaker_uuid, used.style_id),
+  );
+  match FALLBACK_NOTIFIED.lock() {
+    Ok(mut notified) => {
+      if !notified.insert(key) {
+        return;
+      }
+    }
+    Err(e) => {
+      error!("Failed to lock FALLBACK_NOTIFIED: {}", e);
+      return;
+    }
+  }
+  let character = match character_names.get(scope) {
+    Some(name) if !name.is_empty() => name.clone(),
+    _ => format!("キャラクター{}", scope),
+  };
+  let primary_engine = engine_from_port(primary.port).map_or("不明なエンジン", |e| e.name());
+  send_event(WorkerEvent::Dialog {
+    message: format!(
+      "{}の{}は{}が使えないため、{}で読み上げます",
+      ghost_name,
+      character,
+      primary_engine,
+      voice_label(used)
+    ),
+  });
+}
+
+/// 通知用の声質の名前（エンジン 話者 - スタイル）
+fn voice_label(voice: &CharacterVoice) -> String {
+  let engine = match engine_from_port(voice.port) {
+    Some(e) => e,
+    None => return "不明な声質".to_string(),
+  };
+  let speakers_info = match SPEAKERS_INFO.read() {
+    Ok(si) => si,
+    Err(e) => {
+      error!("Failed to read SPEAKERS_INFO: {}", e);
+      return engine.name().to_string();
+    }
+  };
+  let speaker = speakers_info.get(&engine).and_then(|speakers| {
+    speakers
+      .iter()
+      .find(|s| s.speaker_uuid == voice.speaker_uuid)
+  });
+  match speaker {
+    Some(speaker) => {
+      let style = speaker
+        .styles
+        .iter()
+        .find(|s| s.style_id == Some(voice.style_id))
+        .and_then(|s| s.style_name.clone())
+        .unwrap_or_default();
+      format!("{} {} - {}", engine.name(), speaker.speaker_name, style)
+    }
+    None => engine.name().to_string(),
+  }
+}
+
+/// 声質に対応するエンジンの Predictor を作る
+fn new_predictor(
+  engine: Engine,
+  tts_text: String,
+  speaker: &CharacterVoice,
+  volume: f32,
+) -> Box<dyn Predictor + Send + Sync> {
+  match engine {
+    Engine::CoeiroInkV2 => Box::new(CoeiroinkV2Predictor::new(
+      tts_text,
+      speaker.speaker_uuid.clone(),
+      speaker.style_id,
+      speaker.voice_quality.clone(),
+    )),
+    Engine::BouyomiChan => Box::new(BouyomichanPredictor::new(
+      tts_text,
+      speaker.style_id,
+      volume,
+    )),
+    Engine::CoeiroInkV1
+    | Engine::VoiceVox
+    | Engine::Lmroid
+    | Engine::ShareVox
+    | Engine::ItVoice
+    | Engine::AivisSpeech => Box::new(VoicevoxFamilyPredictor::new(
+      engine,
+      tts_text,
+      speaker.style_id,
+      speaker.voice_quality.clone(),
+    )),
+  }
+}
+
+/// 試聴: 声質の割り当てを変えずに、指定した声質で文章を合成する
+async fn synthesize_preview(
+  voice: &CharacterVoice,
+  text: String,
+  volume: f32,
+) -> Result<Vec<u8>, String> {
+  let engine = engine_from_port(voice.port).ok_or_else(|| "Unknown engine".to_string())?;
+  new_predictor(engine, text, voice, volume)
+    .predict()
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// 試聴の音声を合成し、再生キューに入れる。合成は待たずに戻る
+pub fn preview_voice(voice: CharacterVoice, text: String, handle: &tokio::runtime::Handle) {
+  if SHUTTING_DOWN.load(Ordering::Acquire) {
+    return;
+  }
+  let volume = match SETTINGS.read() {
+    Ok(s) => s.volume,
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      1.0
+    }
+  };
+  handle.spawn(async move {
+    match synthesize_preview(&voice, text, volume).await {
+      // 棒読みちゃんはエンジン側で再生されるため空になる
+      Ok(wav) if wav.is_empty() => (),
+      Ok(wav) => {
+        PLAY_QUEUE
+          .lock()
+          .unwrap_or_else(|e| e.into_inner())
+          .push_back((wav, volume));
+      }
+      Err(e) => debug!("preview failed: {}", e),
+    }
+  });
+}
+
 /// sync ラッパー
 pub fn build_segments(
   text: String,
@@ -843,9 +1093,15 @@ pub fn build_segments(
 
 // --- 同期再生 ---
 
-pub fn spawn_sync_playback(wav: Vec<u8>, volume: f32, handle: &tokio::runtime::Handle) {
+/// 同期再生を開始し、wavの再生時間を返す
+pub fn spawn_sync_playback(wav: Vec<u8>, volume: f32, handle: &tokio::runtime::Handle) -> Duration {
   if SHUTTING_DOWN.load(Ordering::Acquire) {
-    return;
+    return Duration::ZERO;
+  }
+  let duration = wav_duration(&wav).unwrap_or_default();
+  match SYNC_AUDIO_DEADLINE.lock() {
+    Ok(mut d) => *d = Some(Instant::now() + duration),
+    Err(e) => error!("Failed to lock SYNC_AUDIO_DEADLINE: {}", e),
   }
   let gen = SYNC_AUDIO_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
   // cancel_sync_playback で設定された FORCE_STOP_SINK をリセット
@@ -868,12 +1124,26 @@ pub fn spawn_sync_playback(wav: Vec<u8>, volume: f32, handle: &tokio::runtime::H
   if let Ok(mut h) = SYNC_PLAYBACK_HANDLER.lock() {
     *h = Some(task_handle);
   }
+  duration
 }
 
 pub fn is_sync_audio_done() -> bool {
   SYNC_AUDIO_DONE_GEN.load(Ordering::SeqCst) >= SYNC_AUDIO_GENERATION.load(Ordering::SeqCst)
 }
 
+/// 同期再生中のオーディオが終了するまでの見込み時間
+pub fn sync_audio_remaining() -> Duration {
+  match SYNC_AUDIO_DEADLINE.lock() {
+    Ok(d) => d
+      .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+      .unwrap_or_default(),
+    Err(e) => {
+      error!("Failed to lock SYNC_AUDIO_DEADLINE: {}", e);
+      Duration::ZERO
+    }
+  }
+}
+
 /// 同期再生をキャンセル
 pub fn cancel_sync_playback() {
   match SYNC_STATE.lock() {
@@ -888,6 +1158,9 @@ pub fn cancel_sync_playback() {
       }
     }
   }
+  if let Ok(mut d) = SYNC_AUDIO_DEADLINE.lock() {
+    *d = None;
+  }
   crate::player::FORCE_STOP_SINK.store(true, Ordering::Release);
 }
 
@@ -901,6 +1174,9 @@ pub fn spawn_sync_prediction(
     return;
   }
 
+  let recording = recorder::should_capture(&ghost_name);
+  let record_ghost_name = ghost_name.clone();
+
   // SYNC_STATE を初期化（空の ready_queue）
   match SYNC_STATE.lock() {
     Ok(mut s) => {
@@ -917,6 +1193,8 @@ pub fn spawn_sync_prediction(
   }
 
   let task_handle = handle.spawn(async move {
+    let mut recorded = Vec::new();
+    let mut spoken = Vec::new();
     for segment in segments {
       // シャットダウンチェック
       if SHUTTING_DOWN.load(Ordering::Acquire) {
@@ -954,6 +1232,21 @@ pub fn spawn_sync_prediction(
         }
       };
 
+      spoken.push(SpokenSegment {
+        scope: segment.scope,
+        text: segment.text.clone(),
+        voice: segment.voice.clone(),
+        wav: wav.clone(),
+      });
+      if recording {
+        recorded.push(RecordedSegment {
+          scope: segment.scope,
+          text: segment.text.clone(),
+          raw_text: segment.raw_text.clone(),
+          wav: wav.clone(),
+        });
+      }
+
       // 合成結果をプールに追加
       {
         match SYNC_STATE.lock() {
@@ -987,6 +1280,11 @@ pub fn spawn_sync_prediction(
       }
       Err(e) => error!("Failed to lock SYNC_STATE for completion flag: {}", e),
     }
+
+    history::push(&record_ghost_name, spoken);
+    if recording {
+      record_talk(record_ghost_name, recorded).await;
+    }
   });
 
   match SYNC_PREDICTION_HANDLER.lock() {
@@ -1016,7 +1314,9 @@ pub fn pop_ready_segment(ghost_name: &str) -> (Option<SyncReadySegment>, bool) {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::engine::mock::{self, mock_wav, COEIROINK_SPEAKER_UUID, VOICEVOX_SPEAKER_UUID};
   use crate::engine::NoOpPredictor;
+  use ghost_speaker_common::GhostVoiceInfo;
 
   /// テスト用SyncSegmentを簡易作成
   fn make_seg(text: &str, raw_text: &str, scope: usize) -> SyncSegment {
@@ -1024,6 +1324,7 @@ mod tests {
       text: text.to_string(),
       raw_text: raw_text.to_string(),
       scope,
+      voice: CharacterVoice::no_voice(),
       predictor: Box::new(NoOpPredictor),
       volume: 1.0,
     }
@@ -1121,4 +1422,229 @@ mod tests {
     assert_eq!(result.len(), 1);
     assert_eq!(result[0].text, "テスト・・");
   }
+
+  /// モックエンジンにつながった状態で、0番を VOICEVOX・1番を COEIROINK に割り当てたゴーストを登録する
+  fn register_mock_ghost(ghost_name: &str) {
+    mock::start();
+    if let Ok(mut cs) = CURRENT_CONNECTION_STATUS.write() {
+      cs.insert(Engine::VoiceVox, true);
+      cs.insert(Engine::CoeiroInkV2, true);
+    }
+    let mut info = GhostVoiceInfo::default();
+    info.voices[0] = Some(CharacterVoice {
+      port: Engine::VoiceVox.port(),
+      speaker_uuid: VOICEVOX_SPEAKER_UUID.to_string(),
+      style_id: 2,
+      voice_quality: Default::default(),
+    });
+    info.voices[1] = Some(CharacterVoice {
+      port: Engine::CoeiroInkV2.port(),
+      speaker_uuid: COEIROINK_SPEAKER_UUID.to_string(),
+      style_id: 0,
+      voice_quality: Default::default(),
+    });
+    if let Ok(mut s) = SETTINGS.write() {
+      s.ghosts_voices.insert(ghost_name.to_string(), info);
+    }
+  }
+
+  #[tokio::test]
+  async fn segments_are_synthesized_by_each_characters_engine() {
+    let ghost = "MockGhostSegments";
+    register_mock_ghost(ghost);
+    let segments = build_segments_async(
+      "\\0こんにちは。元気？\\1はいはい。".to_string(),
+      ghost.to_string(),
+      false,
+    )
+    .await
+    .unwrap();
+    let mut results = Vec::new();
+    for seg in segments {
+      let wav = seg.predictor.predict().await.unwrap();
+      results.push((seg.scope, seg.text, wav));
+    }
+    let voice1 = format!("{}:0", COEIROINK_SPEAKER_UUID);
+    let expected = [
+      (0, "こんにちは。", "2"),
+      (0, "元気？", "2"),
+      (1, "はいはい。", voice1.as_str()),
+    ];
+    assert_eq!(results.len(), expected.len());
+    for ((scope, text, wav), (e_scope, e_text, e_voice)) in results.iter().zip(expected) {
+      assert_eq!((*scope, text.as_str()), (e_scope, e_text));
+      assert_eq!(*wav, mock_wav(e_voice, e_text));
+    }
+  }
+
+  #[tokio::test]
+  async fn urls_are_not_split_by_punctuation() {
+    let ghost = "MockGhostUrl";
+    register_mock_ghost(ghost);
+    let segments = build_segments_async(
+      "\\0詳しくは https://example.com/a?b=1!c を見てね！\\w9次です。".to_string(),
+      ghost.to_string(),
+      false,
+    )
+    .await
+    .unwrap();
+    let mut results = Vec::new();
+    for seg in segments {
+      let wav = seg.predictor.predict().await.unwrap();
+      results.push((seg.text, seg.raw_text, wav));
+    }
+    let expected = [
+      (
+        "詳しくは https://example.com/a?b=1!c を見てね！",
+        "詳しくは ユーアールエル を見てね！",
+      ),
+      ("次です。", "次です。"),
+    ];
+    assert_eq!(results.len(), expected.len());
+    for ((text, _, wav), (e_text, e_spoken)) in results.iter().zip(expected) {
+      assert_eq!(text, e_text);
+      assert_eq!(*wav, mock_wav("2", e_spoken));
+    }
+    // バルーン表示用の raw_text も URL の途中で切れない
+    assert!(results[0]
+      .1
+      .ends_with("https://example.com/a?b=1!c を見てね！\\w9"));
+    assert_eq!(results[1].1, "次です。");
+  }
+
+  #[tokio::test]
+  async fn preview_uses_given_voice() {
+    mock::start();
+    let voice = CharacterVoice {
+      port: Engine::VoiceVox.port(),
+      speaker_uuid: VOICEVOX_SPEAKER_UUID.to_string(),
+      style_id: 3,
+      voice_quality: Default::default(),
+    };
+    let wav = synthesize_preview(&voice, "試聴のテスト".to_string(), 1.0)
+      .await
+      .unwrap();
+    assert_eq!(wav, mock_wav("3", "試聴のテスト"));
+  }
+
+  #[tokio::test]
+  async fn shell_profile_overrides_ghost_voices() {
+    let ghost = "MockGhostShell";
+    register_mock_ghost(ghost);
+    if let Ok(mut s) = SETTINGS.write() {
+      let info = s.ghosts_voices.get_mut(ghost).unwrap();
+      let mut child = info.voices[0].clone().unwrap();
+      child.style_id = 3;
+      info
+        .shell_voices
+        .insert("child".to_string(), vec![Some(child)]);
+    }
+    if let Ok(mut cs) = CURRENT_SHELLS.write() {
+      cs.insert(ghost.to_string(), "child".to_string());
+    }
+    let segments = build_segments_async(
+      "\\0こんにちは。\\1はい。".to_string(),
+      ghost.to_string(),
+      false,
+    )
+    .await
+    .unwrap();
+    let styles: Vec<(usize, i32)> = segments
+      .iter()
+      .map(|seg| (seg.scope, seg.voice.style_id))
+      .collect();
+    // シェル別の声質が無いキャラクターはゴースト共通の声質
+    assert_eq!(styles, [(0, 3), (1, 0)]);
+  }
+
+  #[test]
+  fn falls_back_to_voice_on_running_engine() {
+    let voice = |engine: Engine, uuid: &str| CharacterVoice {
+      port: engine.port(),
+      speaker_uuid: uuid.to_string(),
+      style_id: 0,
+      voice_quality: Default::default(),
+    };
+    let primary = voice(Engine::CoeiroInkV2, "coeiroink");
+    let removed = voice(Engine::VoiceVox, "removed");
+    let alternate = voice(Engine::VoiceVox, "voicevox");
+    let initial = voice(Engine::AivisSpeech, "aivis");
+    let candidates = voice_candidates(
+      Some(&primary),
+      &[removed.clone(), alternate.clone()],
+      &initial,
+    );
+    assert_eq!(candidates.len(), 4);
+
+    let speakers_info = HashMap::from([(
+      Engine::VoiceVox,
+      vec![SpeakerInfo {
+        speaker_name: "VOICEVOX".to_string(),
+        speaker_uuid: "voicevox".to_string(),
+        styles: Vec::new(),
+      }],
+    )]);
+    let select = |connected: &[Engine]| {
+      select_voice(&candidates, |v| {
+        is_voice_available(v, connected, &speakers_info)
+      })
+      .map(|(i, v)| (i, v.speaker_uuid.clone()))
+    };
+    // 割り当てた声質のエンジンが起動していれば、そのまま使う
+    assert_eq!(
+      select(&[Engine::CoeiroInkV2, Engine::VoiceVox]),
+      Some((0, "coeiroink".to_string()))
+    );
+    // 一覧にない話者は飛ばして、次の予備の声質を使う
+    assert_eq!(
+      select(&[Engine::VoiceVox]),
+      Some((2, "voicevox".to_string()))
+    );
+    assert_eq!(
+      select(&[Engine::AivisSpeech]),
+      Some((3, "aivis".to_string()))
+    );
+    assert_eq!(select(&[]), None);
+
+    // 未設定ならデフォルト声質から、「無し」なら読み上げない
+    let candidates = voice_candidates(None, &[alternate], &initial);
+    assert_eq!(candidates[0].speaker_uuid, "aivis");
+    let no_voice = CharacterVoice::no_voice();
+    assert!(voice_candidates(Some(&no_voice), &[primary], &initial).is_empty());
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn sync_prediction_fills_ready_queue_in_order() {
+    let ghost = "MockGhostSync";
+    register_mock_ghost(ghost);
+    let handle = tokio::runtime::Handle::current();
+    let segments = build_segments_async(
+      "\\0同期です。\\1はい……".to_string(),
+      ghost.to_string(),
+      true,
+    )
+    .await
+    .unwrap();
+    spawn_sync_prediction(segments, ghost.to_string(), &handle);
+
+    let mut ready = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+      let (segment, has_more) = pop_ready_segment(ghost);
+      if let Some(seg) = segment {
+        ready.push(seg);
+      } else if !has_more || Instant::now() > deadline {
+        break;
+      } else {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+      }
+    }
+    let texts: Vec<&str> = ready.iter().map(|s| s.text.as_str()).collect();
+    assert_eq!(texts, ["同期です。", "はい……"]);
+    assert_eq!(
+      wav_duration(&ready[0].wav),
+      Some(Duration::from_millis(250))
+    );
+    assert!(!ready[1].wav.is_empty());
+  }
 }
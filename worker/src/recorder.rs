@@ -0,0 +1,669 @@
+//! 録音モード: 読み上げた音声をキャラクターの台詞ごとに WAV ファイルへ書き出す。
+//! トークごとにセグメントの一覧（サイドカー JSON）を書き、
+//! 設定に応じてセッション中の全トークをつなげた WAV と字幕も書き出す
+use crate::settings::SETTINGS;
+use crate::subtitle::{self, Cue};
+use ghost_speaker_common::SubtitleFormat;
+use log::{debug, error};
+use once_cell::sync::Lazy;
+use rodio::source::UniformSourceIterator;
+use rodio::{Decoder, Source};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex as StdMutex;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const RECORD_DIR_NAME: &str = "recordings";
+/// セッションファイルで、トークの間に挟む無音の長さ（ミリ秒）
+const SESSION_GAP_MS: u64 = 500;
+
+/// 書き出し先のディレクトリ（dll_dir/recordings）
+pub static RECORD_DIR: Lazy<RwLock<PathBuf>> = Lazy::new(|| RwLock::new(PathBuf::new()));
+static SESSIONS: Lazy<StdMutex<HashMap<String, Session>>> =
+  Lazy::new(|| StdMutex::new(HashMap::new()));
+/// 前回のトークの時刻。同じミリ秒のトークでファイル名が重ならないようにする
+static LAST_RECORDED_AT: AtomicU64 = AtomicU64::new(0);
+
+/// 合成済みのセグメント
+pub struct RecordedSegment {
+  pub scope: usize,
+  pub text: String,
+  pub raw_text: String,
+  pub wav: Vec<u8>,
+}
+
+/// サイドカー JSON の内容
+#[derive(Debug, Serialize)]
+struct TalkRecord {
+  ghost: String,
+  recorded_at: String,
+  /// セッションファイルと、その中でのトークの開始位置
+  session_file: Option<String>,
+  session_offset_ms: Option<u64>,
+  segments: Vec<SegmentRecord>,
+}
+
+#[derive(Debug, Serialize)]
+struct SegmentRecord {
+  /// 音声を含むファイル。棒読みちゃんなど音声を返さないエンジンでは None
+  file: Option<String>,
+  scope: usize,
+  character: String,
+  text: String,
+  raw_text: String,
+  /// トークの先頭からの位置
+  start_ms: u64,
+  duration_ms: u64,
+}
+
+struct Pcm {
+  channels: u16,
+  sample_rate: u32,
+  samples: Vec<i16>,
+}
+
+impl Pcm {
+  fn duration_ms(&self) -> u64 {
+    samples_to_ms(self.samples.len() as u64, self.channels, self.sample_rate)
+  }
+}
+
+/// セッション。ゴーストごとに全トークを1本の時間軸に並べ、音声と字幕を書き出す
+struct Session {
+  /// 拡張子を除いたファイル名: {stamp}_{ghost}_session
+  base_name: String,
+  audio: Option<SessionAudio>,
+  cues: Vec<Cue>,
+  /// 最後のトークの終わりの位置
+  end_ms: u64,
+  talks: usize,
+}
+
+/// セッションの音声ファイル。追記するたびにヘッダのサイズを書き直すので、途中で終了しても再生できる
+struct SessionAudio {
+  file: File,
+  file_name: String,
+  channels: u16,
+  sample_rate: u32,
+  data_len: u32,
+}
+
+impl SessionAudio {
+  fn create(path: &Path, channels: u16, sample_rate: u32) -> std::io::Result<Self> {
+    let mut file = File::create(path)?;
+    file.write_all(&wav_header(channels, sample_rate, 0))?;
+    Ok(SessionAudio {
+      file,
+      file_name: file_name_of(path),
+      channels,
+      sample_rate,
+      data_len: 0,
+    })
+  }
+
+  fn offset_ms(&self) -> u64 {
+    samples_to_ms((self.data_len / 2) as u64, self.channels, self.sample_rate)
+  }
+
+  fn append(&mut self, samples: &[i16]) -> std::io::Result<()> {
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    self.file.seek(SeekFrom::End(0))?;
+    self.file.write_all(&bytes)?;
+    self.data_len += bytes.len() as u32;
+    self.file.seek(SeekFrom::Start(0))?;
+    self
+      .file
+      .write_all(&wav_header(self.channels, self.sample_rate, self.data_len))?;
+    self.file.flush()
+  }
+
+  fn silence(&self, ms: u64) -> Vec<i16> {
+    let frames = self.sample_rate as u64 * ms / 1000;
+    vec![0; (frames * self.channels as u64) as usize]
+  }
+}
+
+/// 書き出し先を設定する
+pub fn init(dll_dir: &str) {
+  match RECORD_DIR.write() {
+    Ok(mut d) => *d = Path::new(dll_dir).join(RECORD_DIR_NAME),
+    Err(e) => error!("Failed to write RECORD_DIR: {}", e),
+  }
+}
+
+fn subtitle_format() -> SubtitleFormat {
+  match SETTINGS.read() {
+    Ok(s) => s.subtitle_format,
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      SubtitleFormat::Off
+    }
+  }
+}
+
+fn record_session() -> bool {
+  match SETTINGS.read() {
+    Ok(s) => s.record_session,
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      false
+    }
+  }
+}
+
+/// トークの音声を record_talk に渡す必要があるか。
+/// 録音モードでなくても、字幕を書き出す設定なら字幕だけを書き出す
+pub fn should_capture(ghost_name: &str) -> bool {
+  is_recording(ghost_name) || subtitle_format() != SubtitleFormat::Off
+}
+
+/// ゴーストが録音モードか
+pub fn is_recording(ghost_name: &str) -> bool {
+  match SETTINGS.read() {
+    Ok(s) => s
+      .ghosts_voices
+      .get(ghost_name)
+      .is_some_and(|info| info.record),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      false
+    }
+  }
+}
+
+/// ゴーストのセッションを終える。次に録音したトークからは新しいセッションファイルになる
+pub fn end_session(ghost_name: &str) {
+  if let Ok(mut sessions) = SESSIONS.lock() {
+    if sessions.remove(ghost_name).is_some() {
+      debug!("recording session ended: {}", ghost_name);
+    }
+  }
+}
+
+pub fn end_all_sessions() {
+  if let Ok(mut sessions) = SESSIONS.lock() {
+    sessions.clear();
+  }
+}
+
+/// 1回のトークを書き出す。同じキャラクターが続くセグメントは1つのファイルにまとめる
+pub fn record_talk(ghost_name: &str, segments: Vec<RecordedSegment>) {
+  if segments.is_empty() {
+    return;
+  }
+  let dir = match RECORD_DIR.read() {
+    Ok(d) => d.clone(),
+    Err(e) => {
+      error!("Failed to read RECORD_DIR: {}", e);
+      return;
+    }
+  };
+  if let Err(e) = std::fs::create_dir_all(&dir) {
+    error!("Failed to create {}: {}", dir.display(), e);
+    return;
+  }
+  let character_names = match SETTINGS.read() {
+    Ok(s) => s
+      .ghosts_voices
+      .get(ghost_name)
+      .map(|info| info.character_names.clone())
+      .unwrap_or_default(),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      Vec::new()
+    }
+  };
+  let now = unique_millis(unix_millis(SystemTime::now()));
+  let stamp = file_timestamp(now);
+  let ghost_part = sanitize_file_name(ghost_name);
+  let recording = is_recording(ghost_name);
+  let session_wavs: Vec<Vec<u8>> = if recording && record_session() {
+    segments.iter().map(|seg| seg.wav.clone()).collect()
+  } else {
+    Vec::new()
+  };
+
+  let mut records = Vec::new();
+  let mut start_ms = 0;
+  for (n, line) in group_by_scope(segments).into_iter().enumerate() {
+    let scope = line[0].scope;
+    let character = character_name(&character_names, scope);
+    let mut format = None;
+    let mut samples = Vec::new();
+    let mut line_records = Vec::new();
+    for seg in line {
+      let pcm = decode(&seg.wav, format);
+      let duration_ms = pcm.as_ref().map(|p| p.duration_ms()).unwrap_or(0);
+      if let Some(pcm) = pcm {
+        format.get_or_insert((pcm.channels, pcm.sample_rate));
+        samples.extend(pcm.samples);
+      }
+      line_records.push(SegmentRecord {
+        file: None,
+        scope,
+        character: character.clone(),
+        text: seg.text,
+        raw_text: seg.raw_text,
+        start_ms,
+        duration_ms,
+      });
+      start_ms += duration_ms;
+    }
+    let file = format
+      .filter(|_| recording)
+      .and_then(|(channels, sample_rate)| {
+        let name = format!(
+          "{}_{}_{:02}_{}.wav",
+          stamp,
+          ghost_part,
+          n + 1,
+          sanitize_file_name(&character)
+        );
+        match std::fs::write(dir.join(&name), encode_wav(channels, sample_rate, &samples)) {
+          Ok(()) => Some(name),
+          Err(e) => {
+            error!("Failed to write {}: {}", name, e);
+            None
+          }
+        }
+      });
+    for mut r in line_records {
+      r.file = file.clone();
+      records.push(r);
+    }
+  }
+
+  let session = add_to_session(
+    ghost_name,
+    &dir,
+    &stamp,
+    &ghost_part,
+    &session_wavs,
+    &records,
+  );
+  if !recording {
+    return;
+  }
+  let (session_file, session_offset_ms) = session.unzip();
+  let session_file = session_file.flatten();
+  let record = TalkRecord {
+    ghost: ghost_name.to_string(),
+    recorded_at: iso_timestamp(now),
+    session_file,
+    session_offset_ms,
+    segments: records,
+  };
+  let json_path = dir.join(format!("{}_{}.json", stamp, ghost_part));
+  let result = serde_json::to_vec_pretty(&record)
+    .map_err(|e| e.to_string())
+    .and_then(|json| std::fs::write(&json_path, json).map_err(|e| e.to_string()));
+  match result {
+    Ok(()) => debug!("recorded: {}", json_path.display()),
+    Err(e) => error!("Failed to write {}: {}", json_path.display(), e),
+  }
+}
+
+/// トークをセッションの時間軸に加えて音声と字幕を書き出し、
+/// セッションの音声ファイル名とトークの開始位置を返す
+fn add_to_session(
+  ghost_name: &str,
+  dir: &Path,
+  stamp: &str,
+  ghost_part: &str,
+  wavs: &[Vec<u8>],
+  records: &[SegmentRecord],
+) -> Option<(Option<String>, u64)> {
+  let format = subtitle_format();
+  if wavs.is_empty() && format == SubtitleFormat::Off {
+    return None;
+  }
+  let mut sessions = match SESSIONS.lock() {
+    Ok(s) => s,
+    Err(e) => {
+      error!("Failed to lock SESSIONS: {}", e);
+      return None;
+    }
+  };
+  let session = sessions
+    .entry(ghost_name.to_string())
+    .or_insert_with(|| Session {
+      base_name: format!("{}_{}_session", stamp, ghost_part),
+      audio: None,
+      cues: Vec::new(),
+      end_ms: 0,
+      talks: 0,
+    });
+  let offset_ms = if session.talks == 0 {
+    0
+  } else {
+    session.end_ms + SESSION_GAP_MS
+  };
+
+  if session.audio.is_none() {
+    // セッションの形式は最初に音声のあるセグメントに合わせる
+    if let Some(first) = wavs.iter().find_map(|wav| decode(wav, None)) {
+      let path = dir.join(format!("{}.wav", session.base_name));
+      match SessionAudio::create(&path, first.channels, first.sample_rate) {
+        Ok(audio) => session.audio = Some(audio),
+        Err(e) => error!("Failed to create {}: {}", path.display(), e),
+      }
+    }
+  }
+  if let Some(audio) = session.audio.as_mut().filter(|_| !wavs.is_empty()) {
+    // 字幕と位置がずれないよう、トークの開始位置まで無音で埋める
+    let mut samples = audio.silence(offset_ms.saturating_sub(audio.offset_ms()));
+    let format = Some((audio.channels, audio.sample_rate));
+    for wav in wavs {
+      if let Some(pcm) = decode(wav, format) {
+        samples.extend(pcm.samples);
+      }
+    }
+    if let Err(e) = audio.append(&samples) {
+      error!("Failed to append to {}: {}", audio.file_name, e);
+    }
+  }
+
+  // 棒読みちゃんなど音声の長さが分からないセグメントは字幕にしない
+  session
+    .cues
+    .extend(records.iter().filter(|r| r.duration_ms > 0).map(|r| Cue {
+      start_ms: offset_ms + r.start_ms,
+      end_ms: offset_ms + r.start_ms + r.duration_ms,
+      speaker: r.character.clone(),
+      text: r.text.clone(),
+    }));
+  let talk_ms = records
+    .iter()
+    .map(|r| r.start_ms + r.duration_ms)
+    .max()
+    .unwrap_or(0);
+  session.end_ms = offset_ms + talk_ms;
+  session.talks += 1;
+  for (ext, content) in subtitle::render(format, &session.cues) {
+    let path = dir.join(format!("{}.{}", session.base_name, ext));
+    if let Err(e) = std::fs::write(&path, content) {
+      error!("Failed to write {}: {}", path.display(), e);
+    }
+  }
+  Some((
+    session.audio.as_ref().map(|audio| audio.file_name.clone()),
+    offset_ms,
+  ))
+}
+
+fn group_by_scope(segments: Vec<RecordedSegment>) -> Vec<Vec<RecordedSegment>> {
+  let mut lines: Vec<Vec<RecordedSegment>> = Vec::new();
+  for seg in segments {
+    match lines.last_mut() {
+      Some(line) if line[0].scope == seg.scope => line.push(seg),
+      _ => lines.push(vec![seg]),
+    }
+  }
+  lines
+}
+
+fn character_name(names: &[String], scope: usize) -> String {
+  match names.get(scope) {
+    Some(name) if !name.is_empty() => name.clone(),
+    _ => format!("char{}", scope),
+  }
+}
+
+/// wav をデコードする。format を指定した場合はそのチャンネル数・サンプリングレートに変換する
+fn decode(wav: &[u8], format: Option<(u16, u32)>) -> Option<Pcm> {
+  if wav.is_empty() {
+    return None;
+  }
+  let decoder = Decoder::new(Cursor::new(wav.to_vec())).ok()?;
+  let (channels, sample_rate) = format.unwrap_or((decoder.channels(), decoder.sample_rate()));
+  let samples = UniformSourceIterator::<_, i16>::new(decoder, channels, sample_rate).collect();
+  Some(Pcm {
+    channels,
+    sample_rate,
+    samples,
+  })
+}
+
+fn samples_to_ms(samples: u64, channels: u16, sample_rate: u32) -> u64 {
+  if channels == 0 || sample_rate == 0 {
+    return 0;
+  }
+  samples / channels as u64 * 1000 / sample_rate as u64
+}
+
+fn wav_header(channels: u16, sample_rate: u32, data_len: u32) -> [u8; 44] {
+  let block_align = channels * 2;
+  let mut header = [0u8; 44];
+  header[0..4].copy_from_slice(b"RIFF");
+  header[4..8].copy_from_slice(&(36 + data_len).to_le_bytes());
+  header[8..16].copy_from_slice(b"WAVEfmt ");
+  header[16..20].copy_from_slice(&16u32.to_le_bytes());
+  header[20..22].copy_from_slice(&1u16.to_le_bytes());
+  header[22..24].copy_from_slice(&channels.to_le_bytes());
+  header[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+  header[28..32].copy_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+  header[32..34].copy_from_slice(&block_align.to_le_bytes());
+  header[34..36].copy_from_slice(&16u16.to_le_bytes());
+  header[36..40].copy_from_slice(b"data");
+  header[40..44].copy_from_slice(&data_len.to_le_bytes());
+  header
+}
+
+fn encode_wav(channels: u16, sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+  let mut wav = wav_header(channels, sample_rate, (samples.len() * 2) as u32).to_vec();
+  wav.extend(samples.iter().flat_map(|s| s.to_le_bytes()));
+  wav
+}
+
+/// ファイル名に使えない文字を _ に置き換える
+fn sanitize_file_name(s: &str) -> String {
+  let name: String = s
+    .chars()
+    .map(|c| {
+      if c.is_control() || "\\/:*?\"<>|".contains(c) {
+        '_'
+      } else {
+        c
+      }
+    })
+    .collect();
+  let name = name.trim().trim_end_matches('.').to_string();
+  if name.is_empty() {
+    "_".to_string()
+  } else {
+    name
+  }
+}
+
+fn file_name_of(path: &Path) -> String {
+  path
+    .file_name()
+    .map(|n| n.to_string_lossy().to_string())
+    .unwrap_or_default()
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+  time
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or_default()
+}
+
+/// 前回より後の時刻を返す
+fn unique_millis(now: u64) -> u64 {
+  let last = LAST_RECORDED_AT
+    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+      Some(now.max(last + 1))
+    })
+    .unwrap_or(now);
+  now.max(last + 1)
+}
+
+/// UNIX 時刻（ミリ秒）を UTC の (年, 月, 日, 時, 分, 秒, ミリ秒) に変換する
+fn civil_time(millis: u64) -> (i64, u32, u32, u32, u32, u32, u32) {
+  let secs = millis / 1000;
+  let days = (secs / 86400) as i64;
+  let rem = secs % 86400;
+  // 1970-01-01 からの日数を暦に変換する（Howard Hinnant の civil_from_days）
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z.rem_euclid(146097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  (
+    year,
+    month,
+    day,
+    (rem / 3600) as u32,
+    (rem % 3600 / 60) as u32,
+    (rem % 60) as u32,
+    (millis % 1000) as u32,
+  )
+}
+
+/// ファイル名用の時刻（UTC）: 20261018-153012-123
+fn file_timestamp(millis: u64) -> String {
+  let (y, mo, d, h, mi, s, ms) = civil_time(millis);
+  format!(
+    "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+    y, mo, d, h, mi, s, ms
+  )
+}
+
+/// JSON 用の時刻（UTC）: 2026-10-18T15:30:12.123Z
+fn iso_timestamp(millis: u64) -> String {
+  let (y, mo, d, h, mi, s, ms) = civil_time(millis);
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+    y, mo, d, h, mi, s, ms
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::mock::mock_wav;
+  use crate::player::wav_duration;
+  use ghost_speaker_common::GhostVoiceInfo;
+  use std::time::Duration;
+
+  fn seg(scope: usize, text: &str) -> RecordedSegment {
+    RecordedSegment {
+      scope,
+      text: text.to_string(),
+      raw_text: text.to_string(),
+      wav: mock_wav("2", text),
+    }
+  }
+
+  #[test]
+  fn timestamps() {
+    // 2026-10-18 15:30:12.345 UTC
+    let millis = 1_792_337_412_345;
+    assert_eq!(file_timestamp(millis), "20261018-153012-345");
+    assert_eq!(iso_timestamp(millis), "2026-10-18T15:30:12.345Z");
+    assert_eq!(file_timestamp(0), "19700101-000000-000");
+    // うるう日
+    assert_eq!(iso_timestamp(951_782_400_000), "2000-02-29T00:00:00.000Z");
+  }
+
+  #[test]
+  fn sanitize_file_names() {
+    assert_eq!(sanitize_file_name("Emily/Phase4"), "Emily_Phase4");
+    assert_eq!(sanitize_file_name("a:b*c?"), "a_b_c_");
+    assert_eq!(sanitize_file_name(" ... "), "_");
+    assert_eq!(sanitize_file_name("さくら"), "さくら");
+  }
+
+  #[test]
+  fn records_lines_sidecar_and_session() {
+    let dir = std::env::temp_dir().join(format!("ghost_speaker_record_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    init(dir.parent().unwrap().to_str().unwrap());
+    // 同じバイナリのほかのテストに影響しないよう、最後に元に戻す
+    let saved = SETTINGS
+      .read()
+      .map(|s| (s.record_session, s.subtitle_format.clone()))
+      .unwrap();
+    if let Ok(mut s) = SETTINGS.write() {
+      s.record_session = true;
+      s.subtitle_format = SubtitleFormat::Both;
+    }
+    if let Ok(mut d) = RECORD_DIR.write() {
+      *d = dir.clone();
+    }
+    let ghost = "RecordGhost";
+    let mut info = GhostVoiceInfo::new(2);
+    info.record = true;
+    info.character_names = vec!["さくら".to_string(), "うにゅう".to_string()];
+    if let Ok(mut s) = SETTINGS.write() {
+      s.ghosts_voices.insert(ghost.to_string(), info);
+    }
+    assert!(is_recording(ghost));
+
+    record_talk(
+      ghost,
+      vec![seg(0, "こんにちは。"), seg(0, "元気？"), seg(1, "はい。")],
+    );
+    record_talk(ghost, vec![seg(1, "またね")]);
+    end_session(ghost);
+
+    let mut files: Vec<String> = std::fs::read_dir(&dir)
+      .unwrap()
+      .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+      .filter(|f| f.contains(ghost))
+      .collect();
+    files.sort();
+    // 2回のトークで台詞のファイルが3つ、JSON が2つ、セッションの音声と字幕が3つ
+    assert_eq!(files.len(), 8, "{:?}", files);
+    let session = files.iter().find(|f| f.ends_with("_session.wav")).unwrap();
+    let sakura = files
+      .iter()
+      .find(|f| f.ends_with("_01_さくら.wav"))
+      .unwrap();
+    let json = files.iter().find(|f| f.ends_with(".json")).unwrap();
+
+    // さくらの台詞は2つのセグメントをつなげたもの（6文字 + 3文字）
+    let sakura_wav = std::fs::read(dir.join(sakura)).unwrap();
+    assert_eq!(wav_duration(&sakura_wav), Some(Duration::from_millis(450)));
+
+    let record: serde_json::Value =
+      serde_json::from_slice(&std::fs::read(dir.join(json)).unwrap()).unwrap();
+    let segments = record["segments"].as_array().unwrap();
+    assert_eq!(segments.len(), 3);
+    assert_eq!(segments[1]["text"], "元気？");
+    assert_eq!(segments[1]["start_ms"], 300);
+    assert_eq!(segments[2]["character"], "うにゅう");
+    assert_eq!(segments[2]["start_ms"], 450);
+    assert_eq!(record["session_offset_ms"], 0);
+
+    // セッション: 1回目（600ms）+ 間（500ms）+ 2回目（150ms）
+    let session_wav = std::fs::read(dir.join(session)).unwrap();
+    assert_eq!(
+      wav_duration(&session_wav),
+      Some(Duration::from_millis(1250))
+    );
+
+    // 字幕はセッションの音声と同じ時間軸に並ぶ
+    let srt = files.iter().find(|f| f.ends_with("_session.srt")).unwrap();
+    let srt = std::fs::read_to_string(dir.join(srt)).unwrap();
+    assert!(srt.starts_with("1\n00:00:00,000 --> 00:00:00,300\nさくら: こんにちは。\n"));
+    assert!(srt.contains("4\n00:00:01,100 --> 00:00:01,250\nうにゅう: またね\n"));
+    let vtt = files.iter().find(|f| f.ends_with("_session.vtt")).unwrap();
+    let vtt = std::fs::read_to_string(dir.join(vtt)).unwrap();
+    assert!(vtt.contains("00:00:00.450 --> 00:00:00.600\n<v うにゅう>はい。\n"));
+    if let Ok(mut s) = SETTINGS.write() {
+      (s.record_session, s.subtitle_format) = saved;
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+}
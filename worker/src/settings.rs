@@ -0,0 +1,488 @@
+//! 設定の保存先: ワーカーが設定を持ち、dll_dir の vars.yaml に保存する
+//!
+//! 変更はしばらく続かないのを待ってから保存し、vars.yaml が外部で編集されたら読み込み直す。
+//! 設定が変わったら DLL に SettingsChanged で通知し、DLL はその写しを表示に使う
+use crate::ipc::send_event;
+use crate::migration::{migrate, SCHEMA_VERSION};
+use crate::recorder;
+use ghost_speaker_common::{SettingChange, Settings, WorkerEvent};
+use log::{debug, error, info, warn};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::Mutex as StdMutex;
+use std::sync::RwLock;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+pub const VAR_PATH: &str = "vars.yaml";
+/// 残しておく vars.yaml のバックアップの数
+const BACKUP_COUNT: usize = 3;
+/// 保存と vars.yaml の編集を確かめる間隔
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// 最後の変更からこの時間、設定が変わらなければ保存する
+const SAVE_DELAY: Duration = Duration::from_secs(3);
+
+pub static SETTINGS: Lazy<RwLock<Settings>> = Lazy::new(|| RwLock::new(Settings::default()));
+
+/// 保存先の状態。None なら保存しない
+static STORE: Lazy<StdMutex<Option<Store>>> = Lazy::new(|| StdMutex::new(None));
+static SAVER: Lazy<StdMutex<Option<Saver>>> = Lazy::new(|| StdMutex::new(None));
+
+struct Saver {
+  stop: Sender<()>,
+  handle: JoinHandle<()>,
+}
+
+struct Store {
+  dir: PathBuf,
+  /// 保存していない変更があれば、最後に変更した時刻
+  dirty_since: Option<Instant>,
+  /// 最後に書いた、または読んだ vars.yaml の内容
+  file_content: Option<String>,
+  /// vars.yaml が読み込めない間は、手での編集を上書きしないよう保存しない
+  file_broken: bool,
+  /// 保存していない変更。保存する前に vars.yaml が編集されたら、読み込み直した設定に重ねる
+  pending: Vec<SettingChange>,
+}
+
+/// 設定を読み込む。persist が true なら dll_dir の vars.yaml から読み、変更を保存していく
+pub fn init(dll_dir: &str, persist: bool) {
+  if !persist {
+    return;
+  }
+  let dir = PathBuf::from(dll_dir);
+  let path = dir.join(VAR_PATH);
+  let mut settings = match read(&path) {
+    Ok(Some(s)) => s,
+    Ok(None) => Settings::default(),
+    Err(e) => {
+      error!("Failed to load {}: {}", path.display(), e);
+      // 壊れたファイルは調べられるように残し、次の保存でバックアップに回らないようにする
+      let broken_path = dir.join(format!("{}.broken", VAR_PATH));
+      if let Err(e) = std::fs::rename(&path, &broken_path) {
+        error!("Failed to move {}: {}", path.display(), e);
+      }
+      read_backup(&dir).unwrap_or_default()
+    }
+  };
+  settings.last_version = Some(env!("CARGO_PKG_VERSION").to_string());
+  match SETTINGS.write() {
+    Ok(mut s) => *s = settings,
+    Err(e) => error!("Failed to write SETTINGS: {}", e),
+  }
+  match STORE.lock() {
+    Ok(mut store) => {
+      *store = Some(Store {
+        file_content: std::fs::read_to_string(&path).ok(),
+        dir,
+        dirty_since: None,
+        file_broken: false,
+        pending: Vec::new(),
+      })
+    }
+    Err(e) => error!("Failed to lock STORE: {}", e),
+  }
+  start_saver();
+}
+
+/// 今の設定
+pub fn get() -> Settings {
+  match SETTINGS.read() {
+    Ok(s) => s.clone(),
+    Err(e) => {
+      error!("Failed to read SETTINGS: {}", e);
+      Settings::default()
+    }
+  }
+}
+
+/// DLL からの変更を適用する
+pub fn patch(changes: &[SettingChange]) -> Result<Settings, String> {
+  let (old, new) = {
+    let mut settings = SETTINGS
+      .write()
+      .map_err(|e| format!("Failed to write SETTINGS: {}", e))?;
+    let new = settings.apply_changes(changes)?;
+    (std::mem::replace(&mut *settings, new.clone()), new)
+  };
+  changed(&old, &new);
+  Ok(new)
+}
+
+/// ワーカー自身が設定を変える（検出したエンジンのパスなど）
+pub fn update(f: impl FnOnce(&mut Settings)) {
+  let (old, new) = match SETTINGS.write() {
+    Ok(mut settings) => {
+      let old = settings.clone();
+      f(&mut settings);
+      (old, settings.clone())
+    }
+    Err(e) => {
+      error!("Failed to write SETTINGS: {}", e);
+      return;
+    }
+  };
+  changed(&old, &new);
+}
+
+/// 保存処理を止め、保存していない変更を書き出す
+pub fn shutdown() {
+  let saver = match SAVER.lock() {
+    Ok(mut s) => s.take(),
+    Err(e) => {
+      error!("Failed to lock SAVER: {}", e);
+      None
+    }
+  };
+  if let Some(Saver { stop, handle }) = saver {
+    let _ = stop.send(());
+    let _ = handle.join();
+  }
+  match STORE.lock() {
+    Ok(mut store) => {
+      if let Some(store) = store.as_mut() {
+        if !store.file_broken || store.dirty_since.is_some() {
+          store.save();
+        }
+      }
+    }
+    Err(e) => error!("Failed to lock STORE: {}", e),
+  }
+}
+
+fn changed(old: &Settings, new: &Settings) {
+  apply_side_effects(old, new);
+  match STORE.lock() {
+    Ok(mut store) => {
+      if let Some(store) = store.as_mut() {
+        store.dirty_since = Some(Instant::now());
+        store.pending.extend(setting_changes(old, new));
+      }
+    }
+    Err(e) => error!("Failed to lock STORE: {}", e),
+  }
+  send_event(WorkerEvent::SettingsChanged {
+    settings: Box::new(new.clone()),
+  });
+}
+
+/// 設定が変わったときに、読み上げ中の状態を合わせる
+fn apply_side_effects(old: &Settings, new: &Settings) {
+  // 録音をやめたゴーストのセッションを区切る
+  for (ghost_name, info) in old.ghosts_voices.iter() {
+    if info.record && !new.ghosts_voices.get(ghost_name).is_some_and(|i| i.record) {
+      recorder::end_session(ghost_name);
+    }
+  }
+  // 音声と字幕の時間軸がずれないよう、録音の設定が変わったら新しいセッションにする
+  if old.record_session != new.record_session || old.subtitle_format != new.subtitle_format {
+    recorder::end_all_sessions();
+  }
+}
+
+fn start_saver() {
+  let (stop, stop_rx) = channel();
+  let handle = match std::thread::Builder::new()
+    .name("settings-saver".to_string())
+    .spawn(move || {
+      // stop が送られるか、送り手がなくなったら終わる
+      while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(POLL_INTERVAL) {
+        tick();
+      }
+      debug!("Settings saver finished");
+    }) {
+    Ok(h) => h,
+    Err(e) => {
+      error!("Failed to spawn settings saver: {}", e);
+      return;
+    }
+  };
+  match SAVER.lock() {
+    Ok(mut s) => *s = Some(Saver { stop, handle }),
+    Err(e) => error!("Failed to lock SAVER: {}", e),
+  }
+}
+
+fn tick() {
+  let mut store = match STORE.lock() {
+    Ok(s) => s,
+    Err(e) => {
+      error!("Failed to lock STORE: {}", e);
+      return;
+    }
+  };
+  let store = match store.as_mut() {
+    Some(s) => s,
+    None => return,
+  };
+
+  let path = store.dir.join(VAR_PATH);
+  let file_content = std::fs::read_to_string(&path).ok();
+  if file_content != store.file_content {
+    store.file_content = file_content;
+    if store.file_content.is_some() {
+      store.reload();
+      return;
+    }
+  }
+  if !store.file_broken && store.dirty_since.is_some_and(|t| t.elapsed() >= SAVE_DELAY) {
+    store.save();
+  }
+}
+
+impl Store {
+  fn save(&mut self) {
+    let mut settings = get();
+    settings.schema_version = SCHEMA_VERSION;
+    save(&self.dir, &settings);
+    // 自分で書いた内容を外部での編集と取り違えないように覚えておく
+    self.file_content = std::fs::read_to_string(self.dir.join(VAR_PATH)).ok();
+    self.dirty_since = None;
+    self.pending.clear();
+  }
+
+  /// 外部で編集された vars.yaml を読み込み直す
+  fn reload(&mut self) {
+    let path = self.dir.join(VAR_PATH);
+    let settings = match read(&path) {
+      Ok(Some(s)) => s,
+      Ok(None) => return,
+      Err(e) => {
+        error!("Failed to reload {}: {}", path.display(), e);
+        self.file_broken = true;
+        return;
+      }
+    };
+    info!("Reloading settings from {}", path.display());
+    self.file_broken = false;
+    // メニューでの変更を失わないよう、保存していない変更は編集後の内容に重ねて保存し直す
+    let settings = if self.pending.is_empty() {
+      settings
+    } else {
+      match settings.apply_changes(&self.pending) {
+        Ok(merged) => {
+          warn!(
+            "{} was edited while {} changes were unsaved. Keeping the unsaved changes",
+            path.display(),
+            self.pending.len()
+          );
+          merged
+        }
+        Err(e) => {
+          error!("Failed to merge unsaved changes: {}", e);
+          self.pending.clear();
+          settings
+        }
+      }
+    };
+    self.dirty_since = if self.pending.is_empty() {
+      None
+    } else {
+      Some(Instant::now())
+    };
+    let old = match SETTINGS.write() {
+      Ok(mut s) => std::mem::replace(&mut *s, settings.clone()),
+      Err(e) => {
+        error!("Failed to write SETTINGS: {}", e);
+        return;
+      }
+    };
+    apply_side_effects(&old, &settings);
+    send_event(WorkerEvent::SettingsChanged {
+      settings: Box::new(settings),
+    });
+  }
+}
+
+/// old から new への変更を、値が変わった項目ごとの SettingChange にする
+fn setting_changes(old: &Settings, new: &Settings) -> Vec<SettingChange> {
+  let mut changes = Vec::new();
+  match (serde_json::to_value(old), serde_json::to_value(new)) {
+    (Ok(old), Ok(new)) => diff_values(&old, &new, &mut Vec::new(), &mut changes),
+    (Err(e), _) | (_, Err(e)) => error!("Failed to serialize settings. {}", e),
+  }
+  changes
+}
+
+fn diff_values(old: &Value, new: &Value, path: &mut Vec<String>, changes: &mut Vec<SettingChange>) {
+  if old == new {
+    return;
+  }
+  match (old, new) {
+    // 項目が消えた場合は1つずつ消せないので、親ごと置き換える
+    (Value::Object(o), Value::Object(n)) if o.keys().all(|k| n.contains_key(k)) => {
+      for (key, value) in n {
+        path.push(key.clone());
+        diff_values(o.get(key).unwrap_or(&Value::Null), value, path, changes);
+        path.pop();
+      }
+    }
+    _ => changes.push(SettingChange {
+      path: path.clone(),
+      value: new.clone(),
+    }),
+  }
+}
+
+/// 設定ファイルを読み、現在の形式に移行する。ファイルがなければ None
+fn read(path: &Path) -> Result<Option<Settings>, String> {
+  let yaml_str = match std::fs::read_to_string(path) {
+    Ok(s) => s,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+    Err(e) => return Err(e.to_string()),
+  };
+  let mut vars: serde_yaml::Mapping = serde_yaml::from_str(&yaml_str).map_err(|e| e.to_string())?;
+  migrate(&mut vars);
+  let mut settings: Settings =
+    serde_yaml::from_value(serde_yaml::Value::Mapping(vars)).map_err(|e| e.to_string())?;
+  remove_worker_paths(&mut settings);
+  Ok(Some(settings))
+}
+
+/// 読み込めるバックアップのうち、最も新しいものを読む
+fn read_backup(dir: &Path) -> Option<Settings> {
+  for n in 1..=BACKUP_COUNT {
+    let path = backup_path(dir, n);
+    match read(&path) {
+      Ok(Some(s)) => {
+        error!("Loaded settings from backup {}", path.display());
+        return Some(s);
+      }
+      Ok(None) => (),
+      Err(e) => error!("Failed to load {}: {}", path.display(), e),
+    }
+  }
+  None
+}
+
+/// Remove corrupted engine paths that point to the worker itself
+fn remove_worker_paths(settings: &mut Settings) {
+  let current_exe = std::env::current_exe().ok();
+  settings.engine_path.retain(|engine, path| {
+    let path = Path::new(path);
+    let is_worker = path
+      .file_name()
+      .is_some_and(|name| name == "ghost_speaker_worker.exe")
+      || current_exe.as_deref() == Some(path);
+    if is_worker {
+      log::warn!(
+        "Removing corrupted engine path for {}: points to worker itself",
+        engine.name()
+      );
+    }
+    !is_worker
+  });
+}
+
+fn save(dir: &Path, settings: &Settings) {
+  let yaml_str = match serde_yaml::to_string(settings) {
+    Ok(s) => s,
+    Err(e) => {
+      error!("Failed to serialize settings. {}", e);
+      return;
+    }
+  };
+  let path = dir.join(VAR_PATH);
+  if std::fs::read_to_string(&path).is_ok_and(|s| s == yaml_str) {
+    debug!("Settings are unchanged");
+    return;
+  }
+
+  // 一時ファイルに書き切ってから置き換え、書き込み中に落ちても vars.yaml が壊れないようにする
+  let tmp_path = dir.join(format!("{}.tmp", VAR_PATH));
+  if let Err(e) = write_synced(&tmp_path, &yaml_str) {
+    error!("Failed to write {}. {}", tmp_path.display(), e);
+    return;
+  }
+  rotate_backups(dir);
+  if let Err(e) = std::fs::rename(&tmp_path, &path) {
+    error!("Failed to save settings. {}", e);
+    return;
+  }
+  debug!("Saved settings to {}", path.display());
+}
+
+fn backup_path(dir: &Path, n: usize) -> PathBuf {
+  dir.join(format!("{}.bak{}", VAR_PATH, n))
+}
+
+fn write_synced(path: &Path, content: &str) -> std::io::Result<()> {
+  let mut file = std::fs::File::create(path)?;
+  file.write_all(content.as_bytes())?;
+  file.sync_all()
+}
+
+/// 今の vars.yaml をバックアップの先頭に加え、古いものを1つずつずらす
+fn rotate_backups(dir: &Path) {
+  let path = dir.join(VAR_PATH);
+  // 手で編集している途中などで読み込めないファイルはバックアップにしない
+  if !matches!(read(&path), Ok(Some(_))) {
+    return;
+  }
+  for n in (1..BACKUP_COUNT).rev() {
+    let from = backup_path(dir, n);
+    if from.exists() {
+      if let Err(e) = std::fs::rename(&from, backup_path(dir, n + 1)) {
+        error!("Failed to rotate {}: {}", from.display(), e);
+      }
+    }
+  }
+  if let Err(e) = std::fs::copy(&path, backup_path(dir, 1)) {
+    error!("Failed to back up {}: {}", path.display(), e);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ghost_speaker_common::GhostVoiceInfo;
+
+  #[test]
+  fn loads_last_backup_when_vars_is_broken() {
+    let dir = std::env::temp_dir().join(format!("ghost_speaker_vars_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut settings = Settings {
+      volume: 0.5,
+      ..Default::default()
+    };
+    save(&dir, &settings);
+    settings.volume = 0.8;
+    save(&dir, &settings);
+    assert!(!dir.join(format!("{}.tmp", VAR_PATH)).exists());
+    assert_eq!(read(&dir.join(VAR_PATH)).unwrap().unwrap().volume, 0.8);
+
+    std::fs::write(dir.join(VAR_PATH), "volume: [").unwrap();
+    assert!(read(&dir.join(VAR_PATH)).is_err());
+    assert_eq!(read_backup(&dir).unwrap().volume, 0.5);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn unsaved_changes_are_kept_over_external_edits() {
+    let old = Settings::default();
+    let mut new = old.clone();
+    new.volume = 0.3;
+    new
+      .ghosts_voices
+      .insert("Ghost".to_string(), GhostVoiceInfo::new(2));
+    let changes = setting_changes(&old, &new);
+    assert_eq!(changes.len(), 2);
+
+    // 保存する前に vars.yaml で別の項目が編集された
+    let mut edited = old.clone();
+    edited.speak_by_punctuation = !old.speak_by_punctuation;
+    edited
+      .ghosts_voices
+      .insert("Other".to_string(), GhostVoiceInfo::new(1));
+    let merged = edited.apply_changes(&changes).unwrap();
+    assert_eq!(merged.volume, 0.3);
+    assert_eq!(merged.speak_by_punctuation, edited.speak_by_punctuation);
+    assert!(merged.ghosts_voices.contains_key("Ghost"));
+    assert!(merged.ghosts_voices.contains_key("Other"));
+  }
+}
@@ -0,0 +1,125 @@
+//! 録音したトークの字幕（SRT・WebVTT）
+use ghost_speaker_common::SubtitleFormat;
+
+/// 字幕の1項目。時刻はセッションの先頭からのミリ秒
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+  pub start_ms: u64,
+  pub end_ms: u64,
+  pub speaker: String,
+  pub text: String,
+}
+
+/// 設定に応じて、書き出すファイルの拡張子と内容を返す
+pub fn render(format: SubtitleFormat, cues: &[Cue]) -> Vec<(&'static str, String)> {
+  match format {
+    SubtitleFormat::Off => Vec::new(),
+    SubtitleFormat::Srt => vec![("srt", to_srt(cues))],
+    SubtitleFormat::WebVtt => vec![("vtt", to_webvtt(cues))],
+    SubtitleFormat::Both => vec![("srt", to_srt(cues)), ("vtt", to_webvtt(cues))],
+  }
+}
+
+pub fn to_srt(cues: &[Cue]) -> String {
+  let mut s = String::new();
+  for (i, cue) in cues.iter().enumerate() {
+    s.push_str(&format!(
+      "{}\n{} --> {}\n{}: {}\n\n",
+      i + 1,
+      timestamp(cue.start_ms, ','),
+      timestamp(cue.end_ms, ','),
+      cue.speaker,
+      single_line(&cue.text),
+    ));
+  }
+  s
+}
+
+pub fn to_webvtt(cues: &[Cue]) -> String {
+  let mut s = String::from("WEBVTT\n\n");
+  for cue in cues {
+    s.push_str(&format!(
+      "{} --> {}\n<v {}>{}\n\n",
+      timestamp(cue.start_ms, '.'),
+      timestamp(cue.end_ms, '.'),
+      escape_vtt(&cue.speaker),
+      escape_vtt(&single_line(&cue.text)),
+    ));
+  }
+  s
+}
+
+/// HH:MM:SS,mmm（SRT）または HH:MM:SS.mmm（WebVTT）
+fn timestamp(ms: u64, separator: char) -> String {
+  format!(
+    "{:02}:{:02}:{:02}{}{:03}",
+    ms / 3_600_000,
+    ms / 60_000 % 60,
+    ms / 1000 % 60,
+    separator,
+    ms % 1000
+  )
+}
+
+/// 空行は項目の区切りになるため、改行をつぶして1行にする
+fn single_line(text: &str) -> String {
+  text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn escape_vtt(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn cues() -> Vec<Cue> {
+    vec![
+      Cue {
+        start_ms: 0,
+        end_ms: 1200,
+        speaker: "さくら".to_string(),
+        text: "こんにちは。".to_string(),
+      },
+      Cue {
+        start_ms: 3_723_004,
+        end_ms: 3_724_500,
+        speaker: "うにゅう".to_string(),
+        text: "a<b>\n\nc".to_string(),
+      },
+    ]
+  }
+
+  #[test]
+  fn srt() {
+    assert_eq!(
+      to_srt(&cues()),
+      "1\n00:00:00,000 --> 00:00:01,200\nさくら: こんにちは。\n\n\
+       2\n01:02:03,004 --> 01:02:04,500\nうにゅう: a<b> c\n\n"
+    );
+  }
+
+  #[test]
+  fn webvtt() {
+    assert_eq!(
+      to_webvtt(&cues()),
+      "WEBVTT\n\n\
+       00:00:00.000 --> 00:00:01.200\n<v さくら>こんにちは。\n\n\
+       01:02:03.004 --> 01:02:04.500\n<v うにゅう>a&lt;b&gt; c\n\n"
+    );
+  }
+
+  #[test]
+  fn render_by_format() {
+    assert!(render(SubtitleFormat::Off, &cues()).is_empty());
+    let files: Vec<&str> = render(SubtitleFormat::Both, &cues())
+      .into_iter()
+      .map(|(ext, _)| ext)
+      .collect();
+    assert_eq!(files, ["srt", "vtt"]);
+  }
+}
@@ -7,6 +7,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex as StdMutex;
 use sysinfo::{Pid, ProcessExt, System, SystemExt};
 
+#[cfg(windows)]
 use winapi::um::winbase::CREATE_NO_WINDOW;
 
 static PORT_OPENER_MUTEX: Lazy<StdMutex<Option<System>>> = Lazy::new(|| StdMutex::new(None));
@@ -26,8 +27,6 @@ pub async fn get_port_opener_path(
 }
 
 fn get_port_opener_path_sync(port: &str, shutting_down: &AtomicBool) -> Option<String> {
-  use std::os::windows::process::CommandExt;
-
   // チェックポイント1: ロック取得前（高速パス）
   if shutting_down.load(Ordering::Acquire) {
     log::debug!("shutting down, skipping port check for {}", port);
@@ -42,7 +41,7 @@ fn get_port_opener_path_sync(port: &str, shutting_down: &AtomicBool) -> Option<S
     }
   };
 
-  // チェックポイント2: ロック取得後、netstat実行前（待機後の再確認）
+  // チェックポイント2: ロック取得後、待ち受けプロセスの検索前（待機後の再確認）
   if shutting_down.load(Ordering::Acquire) {
     log::debug!(
       "shutting down after lock acquired, skipping port check for {}",
@@ -51,98 +50,167 @@ fn get_port_opener_path_sync(port: &str, shutting_down: &AtomicBool) -> Option<S
     return None;
   }
 
-  let output = match Command::new("cmd")
-    .args([
-      "/C",
-      &format!("netstat -ano | findstr LISTENING | findstr {}", port),
-    ])
-    .creation_flags(CREATE_NO_WINDOW)
-    .output()
-  {
-    Ok(output) => output,
+  let pids = match listening_pids(port) {
+    Ok(pids) => pids,
     Err(e) => {
-      log::error!("{}", e);
+      log::error!("Failed to find listening process on port {}: {}", port, e);
       return None;
     }
   };
+  if pids.is_empty() {
+    log::debug!("No listening process found on port {}", port);
+    return None;
+  }
+  log::debug!(
+    "found listening process on port {}, querying process info",
+    port
+  );
+  log::logger().flush();
 
-  if output.status.success() {
-    let output_str = match String::from_utf8(output.stdout) {
-      Ok(s) => s,
-      Err(e) => {
-        log::error!("Failed to parse stdout as UTF-8: {}", e);
-        return None;
-      }
-    };
+  // チェックポイント3: refresh_processes()呼び出し直前（クラッシュサイト防御）
+  if shutting_down.load(Ordering::Acquire) {
     log::debug!(
-      "netstat found listening process on port {}, querying process info",
+      "shutting down before refresh_processes, skipping port check for {}",
       port
     );
-    log::logger().flush();
-
-    // チェックポイント3: refresh_processes()呼び出し直前（クラッシュサイト防御）
-    if shutting_down.load(Ordering::Acquire) {
-      log::debug!(
-        "shutting down before refresh_processes, skipping port check for {}",
-        port
-      );
-      return None;
-    }
+    return None;
+  }
 
-    // sysinfo呼び出しをcatch_unwindで保護 + Systemインスタンスをキャッシュ
-    if guard.is_none() {
-      log::debug!("Creating new System instance");
-      match std::panic::catch_unwind(std::panic::AssertUnwindSafe(System::new)) {
-        Ok(system) => *guard = Some(system),
-        Err(e) => {
-          log::error!("sysinfo System::new() panicked: {:?}", e);
-          return None;
-        }
+  // sysinfo呼び出しをcatch_unwindで保護 + Systemインスタンスをキャッシュ
+  if guard.is_none() {
+    log::debug!("Creating new System instance");
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(System::new)) {
+      Ok(system) => *guard = Some(system),
+      Err(e) => {
+        log::error!("sysinfo System::new() panicked: {:?}", e);
+        return None;
       }
     }
-    let system = guard.as_mut().unwrap();
-    let refresh_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-      system.refresh_processes();
-    }));
-    if let Err(e) = refresh_result {
-      log::error!("sysinfo refresh_processes panicked: {:?}", e);
-      // パニック後のSystemは不定状態なので破棄
-      *guard = None;
-      return None;
+  }
+  let system = guard.as_mut().unwrap();
+  let refresh_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+    system.refresh_processes();
+  }));
+  if let Err(e) = refresh_result {
+    log::error!("sysinfo refresh_processes panicked: {:?}", e);
+    // パニック後のSystemは不定状態なので破棄
+    *guard = None;
+    return None;
+  }
+
+  log::logger().flush();
+  for pid in pids {
+    if let Some(path) = extract_parent_process_path(Pid::from(pid), system) {
+      return Some(path);
+    } else {
+      log::error!("Failed to extract parent process for pid: {}", pid);
     }
+  }
+  log::logger().flush();
+  None
+}
 
-    log::logger().flush();
-    for line in output_str.lines() {
-      let parts: Vec<&str> = line.split_whitespace().collect();
-      if let Some(pid_str) = parts.last() {
-        match pid_str.parse::<usize>() {
-          Ok(pid) => {
-            if let Some(path) = extract_parent_process_path(Pid::from(pid), system) {
-              return Some(path);
-            } else {
-              log::error!("Failed to extract parent process for pid: {}", pid);
-            }
-          }
-          Err(e) => log::error!("failed to parse pid: {}: {}", pid_str, e),
-        }
-      }
+/// port で待ち受けているプロセスの PID を netstat で調べる
+#[cfg(windows)]
+fn listening_pids(port: &str) -> Result<Vec<usize>, String> {
+  use std::os::windows::process::CommandExt;
+
+  let output = Command::new("cmd")
+    .args([
+      "/C",
+      &format!("netstat -ano | findstr LISTENING | findstr {}", port),
+    ])
+    .creation_flags(CREATE_NO_WINDOW)
+    .output()
+    .map_err(|e| e.to_string())?;
+
+  // findstr は見つからなければ失敗を返す
+  if !output.status.success() {
+    let error_str = String::from_utf8_lossy(&output.stderr);
+    if error_str.is_empty() {
+      return Ok(Vec::new());
     }
-  } else {
-    let error_str = match String::from_utf8(output.stderr) {
-      Ok(s) => s,
-      Err(e) => {
-        log::error!("Failed to parse stderr as UTF-8: {}", e);
-        "Unknown error".to_string()
+    return Err(format!("netstat command failed: {}", error_str));
+  }
+  let output_str = String::from_utf8(output.stdout)
+    .map_err(|e| format!("Failed to parse stdout as UTF-8: {}", e))?;
+  let mut pids = Vec::new();
+  for line in output_str.lines() {
+    if let Some(pid_str) = line.split_whitespace().last() {
+      match pid_str.parse::<usize>() {
+        Ok(pid) => pids.push(pid),
+        Err(e) => log::error!("failed to parse pid: {}: {}", pid_str, e),
       }
+    }
+  }
+  Ok(pids)
+}
+
+/// port で待ち受けているプロセスの PID を /proc から調べる。
+/// /proc/net/tcp(6) で待ち受けソケットの inode を探し、それを開いているプロセスを探す
+#[cfg(not(windows))]
+fn listening_pids(port: &str) -> Result<Vec<usize>, String> {
+  let port: u16 = port
+    .parse()
+    .map_err(|e| format!("invalid port {}: {}", port, e))?;
+  let mut inodes = Vec::new();
+  for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+    match std::fs::read_to_string(table) {
+      Ok(content) => inodes.extend(listening_inodes(&content, port)),
+      // IPv6 が無効な環境では tcp6 が無い
+      Err(e) => log::debug!("Failed to read {}: {}", table, e),
+    }
+  }
+  if inodes.is_empty() {
+    return Ok(Vec::new());
+  }
+
+  let mut pids = Vec::new();
+  let proc_entries = std::fs::read_dir("/proc").map_err(|e| e.to_string())?;
+  for entry in proc_entries.flatten() {
+    let name = entry.file_name();
+    let pid = match name.to_str().and_then(|n| n.parse::<usize>().ok()) {
+      Some(pid) => pid,
+      None => continue,
     };
-    if error_str.is_empty() {
-      log::debug!("No listening process found on port {}", port);
-    } else {
-      log::error!("netstat command failed for port {}: {}", port, error_str);
+    // 他のユーザーのプロセスの fd は読めないので飛ばす
+    let fds = match std::fs::read_dir(entry.path().join("fd")) {
+      Ok(fds) => fds,
+      Err(_) => continue,
+    };
+    let opens_socket = fds.flatten().any(|fd| {
+      std::fs::read_link(fd.path()).is_ok_and(|link| {
+        link
+          .to_str()
+          .and_then(|l| l.strip_prefix("socket:["))
+          .and_then(|l| l.strip_suffix(']'))
+          .is_some_and(|inode| inodes.iter().any(|i| i == inode))
+      })
+    });
+    if opens_socket {
+      pids.push(pid);
     }
   }
-  log::logger().flush();
-  None
+  Ok(pids)
+}
+
+/// /proc/net/tcp(6) の内容から、port で待ち受けているソケットの inode を取り出す
+#[cfg(not(windows))]
+fn listening_inodes(table: &str, port: u16) -> Vec<String> {
+  // 状態 0A は LISTEN
+  const TCP_LISTEN: &str = "0A";
+  table
+    .lines()
+    .skip(1)
+    .filter_map(|line| {
+      let fields: Vec<&str> = line.split_whitespace().collect();
+      let local_port = fields.get(1)?.rsplit(':').next()?;
+      if u16::from_str_radix(local_port, 16).ok()? != port || *fields.get(3)? != TCP_LISTEN {
+        return None;
+      }
+      fields.get(9).map(|inode| inode.to_string())
+    })
+    .collect()
 }
 
 pub fn cleanup_system_cache() {
@@ -158,6 +226,7 @@ pub fn cleanup_system_cache() {
 }
 
 // check the file exists on "C:\Windows\*"
+#[cfg(windows)]
 fn is_os_level_executable(path: &Path) -> bool {
   if let Ok(current_exe) = std::env::current_exe() {
     if path == current_exe.as_path() {
@@ -167,6 +236,21 @@ fn is_os_level_executable(path: &Path) -> bool {
   path.starts_with("C:\\Windows\\") || path.ends_with("explorer.exe") || path.ends_with("ssp.exe")
 }
 
+// シェルやデスクトップ環境などのシステムのプログラムまでは遡らない。
+// AppImage で起動したエンジンは AppImage のファイル自体が見つかる
+#[cfg(not(windows))]
+fn is_os_level_executable(path: &Path) -> bool {
+  if let Ok(current_exe) = std::env::current_exe() {
+    if path == current_exe.as_path() {
+      return true;
+    }
+  }
+  ["/usr/", "/bin/", "/sbin/", "/lib/", "/lib64/"]
+    .iter()
+    .any(|dir| path.starts_with(dir))
+    || path.as_os_str().is_empty()
+}
+
 fn extract_parent_process_path(pid: Pid, system: &mut System) -> Option<String> {
   if let Some(process) = system.process(pid) {
     let mut r = process;
@@ -216,15 +300,43 @@ pub fn boot_engine(
     }
   }
 
+  let mut command = Command::new(path);
+  command
+    .stdin(std::process::Stdio::null())
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null());
+  #[cfg(windows)]
   {
     use std::os::windows::process::CommandExt;
-    Command::new(path)
-      .stdin(std::process::Stdio::null())
-      .stdout(std::process::Stdio::null())
-      .stderr(std::process::Stdio::null())
-      .creation_flags(CREATE_NO_WINDOW)
-      .spawn()?;
+    command.creation_flags(CREATE_NO_WINDOW);
   }
+  command.spawn()?;
   log::debug!("booted {}", engine.name());
   Ok(())
 }
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_listening_socket_inodes() {
+    // 50021 = 0xC365。待ち受け(0A)と接続済み(01)、別のポートが混ざっている
+    let table = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:C365 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 123456 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:C365 0100007F:D431 01 00000000:00000000 00:00000000 00000000  1000        0 123457 1 0000000000000000 20 4 30 10 -1
+   2: 00000000:0277 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 222222 1 0000000000000000 100 0 0 10 0
+";
+    assert_eq!(listening_inodes(table, 50021), vec!["123456".to_string()]);
+    assert!(listening_inodes(table, 50032).is_empty());
+  }
+
+  #[test]
+  fn finds_own_listening_process() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let pids = listening_pids(&port.to_string()).unwrap();
+    assert!(pids.contains(&(std::process::id() as usize)));
+  }
+}